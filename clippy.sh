@@ -9,3 +9,4 @@ cargo clippy --no-default-features --features crc64 -- -D warnings
 cargo clippy --no-default-features --features bcj -- -D warnings
 cargo clippy --no-default-features --features sha256 -- -D warnings
 cargo clippy --no-default-features --features delta -- -D warnings
+cargo clippy --no-default-features --features futures-io -- -D warnings
@@ -9,6 +9,10 @@ fn main() {
     //Note: This is 3GB, decide yourself if you want the decoder to allocate this much memory if the possibly untrustworthy input file requires it.
     let max_alloc_size = xz4rust::DICT_SIZE_MAX;
     let mut decoder = XzDecoder::in_heap_with_alloc_dict_size(initial_alloc_size, max_alloc_size);
+    //Alternatively set a memory limit that also accounts for the decoder itself.
+    //Exceeding it returns XzError::MemoryLimitExceeded with the required amount of memory,
+    //the limit can be raised and decoding resumed with the same input.
+    decoder.set_memlimit(64 * 1024 * 1024);
 
     let mut input_position = 0usize;
     loop {
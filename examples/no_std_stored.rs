@@ -0,0 +1,37 @@
+use xz4rust::{XzCheckType, XzNextBlockResult, XzStoredEncoder};
+
+/// I am aware that the println! macro is not available in no_std, but other than that everything
+/// here should work in no_std environments.
+fn main() {
+    let data = b"Hello\nWorld!";
+    // The output buffer can have any size, the encoder continues where it stopped on the next call.
+    let mut output_buffer = [0u8; 16];
+    let mut encoder = XzStoredEncoder::new(XzCheckType::Crc32);
+
+    let mut input_position = 0usize;
+    while input_position < data.len() {
+        match encoder.encode(&data[input_position..], &mut output_buffer) {
+            Ok(XzNextBlockResult::NeedMoreData(input_consumed, output_produced)) => {
+                input_position += input_consumed;
+                // Write the output to wherever it should go.
+                println!("{:02x?}", &output_buffer[..output_produced]);
+            }
+            Ok(XzNextBlockResult::EndOfStream(_, _)) => unreachable!(),
+            Err(err) => panic!("Compression failed {}", err),
+        }
+    }
+
+    loop {
+        match encoder.finish(&mut output_buffer) {
+            Ok(XzNextBlockResult::NeedMoreData(_, output_produced)) => {
+                println!("{:02x?}", &output_buffer[..output_produced]);
+            }
+            Ok(XzNextBlockResult::EndOfStream(_, output_produced)) => {
+                println!("{:02x?}", &output_buffer[..output_produced]);
+                println!("Finished!");
+                break;
+            }
+            Err(err) => panic!("Compression failed {}", err),
+        }
+    }
+}
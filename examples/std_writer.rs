@@ -0,0 +1,12 @@
+use std::fs::File;
+use std::io::Write;
+use xz4rust::XzWriter;
+
+fn main() -> std::io::Result<()> {
+    let file = File::create("hello.xz")?;
+    // Preset levels 0-9 use the dictionary sizes DICT_SIZE_PROFILE_0 to DICT_SIZE_PROFILE_9.
+    let mut writer = XzWriter::new_with_preset(file, 6);
+    writer.write_all(b"Hello\nWorld!")?;
+    writer.finish()?;
+    Ok(())
+}
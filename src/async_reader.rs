@@ -0,0 +1,164 @@
+extern crate std;
+
+use crate::input_buffer::InputBuffer;
+use crate::{XzDecoder, XzError, XzNextBlockResult};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::future::poll_fn;
+use core::num::NonZeroUsize;
+use core::pin::Pin;
+use core::task::{ready, Context, Poll};
+use futures_io::AsyncRead;
+
+/// Asynchronous version of `XzReader` that decodes from a `futures_io::AsyncRead`.
+///
+/// The underlying reader must be `Unpin`, wrap it with `Box::pin` if it is not.
+#[derive(Debug)]
+pub struct AsyncXzReader<R: AsyncRead + Unpin> {
+    /// the inner decoder, on the heap.
+    decoder: Box<XzDecoder<'static>>,
+    /// the underlying stream
+    reader: R,
+    /// Data read from the underlying stream that was not decoded yet.
+    input: InputBuffer,
+    /// Are we at the end of a valid xz stream and should return eof?
+    eos: bool,
+}
+
+impl<R: AsyncRead + Unpin> AsyncXzReader<R> {
+    /// Creates a new instance of `AsyncXzReader`
+    /// This reader will heap allocate an internal 8k io buffer to read from R.
+    /// This reader will allocate up to about 3GB of additional memory in heap for the lzma dictionary
+    /// depending on the input file.
+    #[allow(clippy::missing_panics_doc)] //We never actually panic.
+    #[must_use]
+    pub fn new(r: R) -> Self {
+        Self::new_with_buffer_size(r, NonZeroUsize::new(8192).expect("Impossible to fail"))
+    }
+
+    #[must_use]
+    pub fn new_with_buffer_size(r: R, buffer_size: NonZeroUsize) -> Self {
+        Self::new_with_buffer_size_and_decoder(r, buffer_size, XzDecoder::in_heap())
+    }
+
+    #[must_use]
+    pub fn new_with_buffer_size_and_decoder(
+        r: R,
+        buffer_size: NonZeroUsize,
+        decoder: Box<XzDecoder<'static>>,
+    ) -> Self {
+        Self {
+            decoder,
+            reader: r,
+            input: InputBuffer::new(buffer_size),
+            eos: false,
+        }
+    }
+
+    /// Enables or disables multi stream mode of the decoder. See `XzReader::set_multi_stream`.
+    pub const fn set_multi_stream(&mut self, multi_stream: bool) {
+        self.decoder.set_multi_stream(multi_stream);
+    }
+
+    /// Reset the decoder to possibly decode the next fresh stream.
+    pub fn reset(&mut self) {
+        self.eos = false;
+        self.decoder.reset();
+    }
+
+    /// Returns true if the xz stream is end of a valid xz stream.
+    #[must_use]
+    pub const fn is_eos(&self) -> bool {
+        self.eos
+    }
+
+    /// Fill the inner buffer if it is empty. Returns false if the underlying stream is at eof.
+    /// The buffer is only modified once the underlying stream is ready.
+    fn poll_try_fill_buffer(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<bool>> {
+        self.input.poll_try_fill(Pin::new(&mut self.reader), cx)
+    }
+
+    /// Take a peek at raw data without consuming it.
+    /// The param fn is guaranteed to be called with at least 1 byte of data.
+    /// # Errors
+    /// propagated from the underlying stream.
+    /// This fn fails with `UnexpectedEof` if no data is available and no data can be read from the stream.
+    pub async fn peek_inner<T>(&mut self, peeker: impl FnOnce(&[u8]) -> T) -> std::io::Result<T> {
+        if !poll_fn(|cx| self.poll_try_fill_buffer(cx)).await? {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof));
+        }
+
+        Ok(peeker(self.input.data()))
+    }
+
+    /// Returns the underlying reader as well as the (possibly empty)
+    /// buffer that may contain some unprocessed data.
+    #[must_use]
+    pub fn into_inner(self) -> (R, Vec<u8>) {
+        (self.reader, self.input.into_vec())
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncXzReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        if this.eos {
+            return Poll::Ready(Ok(0));
+        }
+
+        loop {
+            if !ready!(this.poll_try_fill_buffer(cx))? {
+                if !this.decoder.is_multi_stream() {
+                    return Poll::Ready(Err(std::io::Error::from(
+                        std::io::ErrorKind::UnexpectedEof,
+                    )));
+                }
+
+                return Poll::Ready(match this.decoder.finish() {
+                    Ok(()) => {
+                        this.eos = true;
+                        Ok(0)
+                    }
+                    Err(XzError::UnexpectedEndOfInput) => Err(std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        XzError::UnexpectedEndOfInput,
+                    )),
+                    Err(err) => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, err)),
+                });
+            }
+
+            return Poll::Ready(match this.decoder.decode(this.input.data(), buf) {
+                Ok(XzNextBlockResult::NeedMoreData(in_count, outcount)) => {
+                    this.input.consume(in_count);
+                    if outcount == 0 {
+                        continue;
+                    }
+                    Ok(outcount)
+                }
+                Ok(XzNextBlockResult::EndOfStream(in_count, outcount))
+                    if this.decoder.is_multi_stream() =>
+                {
+                    this.input.consume(in_count);
+                    if outcount == 0 {
+                        continue;
+                    }
+                    Ok(outcount)
+                }
+                Ok(XzNextBlockResult::EndOfStream(in_count, outcount)) => {
+                    this.input.consume(in_count);
+                    this.eos = true;
+                    Ok(outcount)
+                }
+                Err(err) => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, err)),
+            });
+        }
+    }
+}
@@ -21,6 +21,125 @@ pub struct BcjFilterState {
     pub buf: [u8; 16],
 }
 
+/// Converts the relative branch addresses of machine code to absolute addresses, which compresses better.
+///
+/// This is the encoding direction of the bcj filters of .xz files,
+/// the output is identical to the output of the bcj filters of liblzma.
+#[derive(Clone, Debug)]
+pub struct BcjEncoder {
+    /// Filter type of the encoder.
+    filter: BcjFilter,
+    /// Position of the next byte to convert.
+    pos: u32,
+    /// special mask marker by the x86 bcj filter. Unused by all other filters.
+    x86_prev_mask: usize,
+}
+
+impl BcjEncoder {
+    /// Creates an encoder for the given filter. The start offset is the position of the first byte,
+    /// it must be the same as the start offset used when decoding.
+    ///
+    /// # Errors
+    /// `XzError::BcjFilterOffsetUnaligned` if the start offset is not aligned to the instruction size of the architecture.
+    pub const fn new(filter: BcjFilter, start_offset: u32) -> Result<Self, XzError> {
+        if start_offset % filter.alignment() != 0 {
+            return Err(XzError::BcjFilterOffsetUnaligned(start_offset));
+        }
+
+        Ok(Self {
+            filter,
+            pos: start_offset,
+            x86_prev_mask: 0,
+        })
+    }
+
+    /// Returns the filter type of the encoder.
+    #[must_use]
+    pub const fn filter(&self) -> BcjFilter {
+        self.filter
+    }
+
+    /// Returns the position of the next byte that is converted.
+    #[must_use]
+    pub const fn pos(&self) -> u32 {
+        self.pos
+    }
+
+    /// Converts the bytes of buf in place and returns the amount of bytes at the start of buf that were converted.
+    ///
+    /// The remaining bytes may be the start of an instruction, they must be passed again
+    /// at the start of buf in the next call together with the data that follows them.
+    /// Once there is no more data, the remaining bytes are left as they are.
+    pub fn encode(&mut self, buf: &mut [u8]) -> usize {
+        bcj_convert(
+            self.filter,
+            &mut self.pos,
+            &mut self.x86_prev_mask,
+            buf,
+            true,
+        )
+    }
+}
+
+/// Converts the absolute branch addresses produced by `BcjEncoder` back to relative addresses.
+///
+/// This is the decoding direction of the bcj filters of .xz files, it uses the same code as `XzDecoder`.
+#[derive(Clone, Debug)]
+pub struct BcjDecoder {
+    /// Filter type of the decoder.
+    filter: BcjFilter,
+    /// Position of the next byte to convert.
+    pos: u32,
+    /// special mask marker by the x86 bcj filter. Unused by all other filters.
+    x86_prev_mask: usize,
+}
+
+impl BcjDecoder {
+    /// Creates a decoder for the given filter. The start offset is the position of the first byte,
+    /// it must be the same as the start offset used when encoding.
+    ///
+    /// # Errors
+    /// `XzError::BcjFilterOffsetUnaligned` if the start offset is not aligned to the instruction size of the architecture.
+    pub const fn new(filter: BcjFilter, start_offset: u32) -> Result<Self, XzError> {
+        if start_offset % filter.alignment() != 0 {
+            return Err(XzError::BcjFilterOffsetUnaligned(start_offset));
+        }
+
+        Ok(Self {
+            filter,
+            pos: start_offset,
+            x86_prev_mask: 0,
+        })
+    }
+
+    /// Returns the filter type of the decoder.
+    #[must_use]
+    pub const fn filter(&self) -> BcjFilter {
+        self.filter
+    }
+
+    /// Returns the position of the next byte that is converted.
+    #[must_use]
+    pub const fn pos(&self) -> u32 {
+        self.pos
+    }
+
+    /// Converts the bytes of buf in place and returns the amount of bytes at the start of buf that were converted.
+    ///
+    /// The remaining bytes may be the start of an instruction, they must be passed again
+    /// at the start of buf in the next call together with the data that follows them.
+    /// Once there is no more data, the remaining bytes are left as they are.
+    pub fn decode(&mut self, buf: &mut [u8]) -> usize {
+        bcj_convert(
+            self.filter,
+            &mut self.pos,
+            &mut self.x86_prev_mask,
+            buf,
+            false,
+        )
+    }
+}
+
 impl BcjFilterState {
     /// Creates a new `BcjFilterState` in its default configuration.
     pub const fn new() -> Self {
@@ -45,17 +164,16 @@ impl BcjFilterState {
         self.next_filter_done = true;
     }
 
-    /// Resets/Initializes the filter for the given filter type directly from the xz stream header.
-    /// # Errors
-    /// if the filter type with the given id is not supported by the implementation.
-    pub fn reset(&mut self, id: u8) -> Result<(), XzError> {
-        self.bcj_filter_type = BcjFilter::try_from(id)?;
+    /// Resets/Initializes the filter for the given filter type and start offset from the xz block header.
+    /// The start offset must be aligned to the instruction size of the architecture.
+    pub fn reset(&mut self, filter: BcjFilter, start_offset: u32) {
+        debug_assert_eq!(start_offset % filter.alignment(), 0);
+        self.bcj_filter_type = filter;
         self.next_filter_done = false;
-        self.pos = 0;
+        self.pos = start_offset;
         self.x86_prev_mask = 0;
         self.filtered = 0;
         self.size = 0;
-        Ok(())
     }
 
     /// flush the filtered bytes to the output buffer.
@@ -151,32 +269,50 @@ impl BcjFilterState {
     }
 
     /// apply the bcj filter to some bytes that the lzma decoder returned.
-    fn apply(&mut self, mut buf: &mut [u8], mut pos: usize, mut size: usize) -> usize {
+    fn apply(&mut self, mut buf: &mut [u8], pos: usize, mut size: usize) -> usize {
         buf = &mut buf[pos..];
         size = size.wrapping_sub(pos);
         let sl_buf = &mut buf[..size];
 
-        let filtered = match self.bcj_filter_type {
-            BcjFilter::X86 => {
-                let (flt, mask) = bcj_x86(self.pos, sl_buf, self.x86_prev_mask);
-                self.x86_prev_mask = mask;
-                flt
-            }
-            BcjFilter::PowerPc => bcj_powerpc(self.pos, sl_buf),
-            BcjFilter::IntelIthanium64 => bcj_ia64(self.pos, sl_buf),
-            BcjFilter::Arm => bcj_arm(self.pos, sl_buf),
-            BcjFilter::ArmThumb => bcj_armthumb(self.pos, sl_buf),
-            BcjFilter::Sparc => bcj_sparc(self.pos, sl_buf),
-            BcjFilter::Arm64 => bcj_arm64(self.pos, sl_buf),
-            BcjFilter::RiscV => bcj_riscv(self.pos, sl_buf),
-        };
-        pos = pos.wrapping_add(filtered);
+        let filtered = bcj_convert(
+            self.bcj_filter_type,
+            &mut self.pos,
+            &mut self.x86_prev_mask,
+            sl_buf,
+            false,
+        );
+        pos.wrapping_add(filtered)
+    }
+}
 
-        //This wrap is needed and tests hit it.
-        self.pos = self.pos.wrapping_add(clamp_us_to_u32(filtered));
+/// Runs the bcj filter in the given direction on buf and advances pos by the amount of bytes converted.
+/// Returns the amount of bytes converted, the remaining bytes need more data after them to be converted.
+fn bcj_convert(
+    filter: BcjFilter,
+    pos: &mut u32,
+    x86_prev_mask: &mut usize,
+    buf: &mut [u8],
+    encode: bool,
+) -> usize {
+    let filtered = match filter {
+        BcjFilter::X86 => {
+            let (flt, mask) = bcj_x86(*pos, buf, *x86_prev_mask, encode);
+            *x86_prev_mask = mask;
+            flt
+        }
+        BcjFilter::PowerPc => bcj_powerpc(*pos, buf, encode),
+        BcjFilter::IntelIthanium64 => bcj_ia64(*pos, buf, encode),
+        BcjFilter::Arm => bcj_arm(*pos, buf, encode),
+        BcjFilter::ArmThumb => bcj_armthumb(*pos, buf, encode),
+        BcjFilter::Sparc => bcj_sparc(*pos, buf, encode),
+        BcjFilter::Arm64 => bcj_arm64(*pos, buf, encode),
+        BcjFilter::RiscV if encode => bcj_riscv_encode(*pos, buf),
+        BcjFilter::RiscV => bcj_riscv(*pos, buf),
+    };
 
-        pos
-    }
+    //This wrap is needed and tests hit it.
+    *pos = pos.wrapping_add(clamp_us_to_u32(filtered));
+    filtered
 }
 
 /// ?
@@ -185,7 +321,7 @@ const fn bcj_x86_test_msbyte(b: u8) -> bool {
 }
 
 /// runs the x86 bcj filter (filtered, mask)
-fn bcj_x86(s_pos: u32, buf: &mut [u8], mask: usize) -> (usize, usize) {
+fn bcj_x86(s_pos: u32, buf: &mut [u8], mask: usize, encode: bool) -> (usize, usize) {
     static MASK_TO_ALLOWED_STATUS: [bool; 8] = [true, true, true, false, true, false, false, false];
     static MASK_TO_BIT_NUM: [usize; 8] = [0, 1, 2, 2, 3, 3, 3, 3];
     let mut position: usize = 0;
@@ -230,12 +366,15 @@ fn bcj_x86(s_pos: u32, buf: &mut [u8], mask: usize) -> (usize, usize) {
                 buf[position + 3],
                 buf[position + 4],
             ]);
+            let pc = s_pos
+                .wrapping_add(clamp_us_to_u32(position))
+                .wrapping_add(5);
             loop {
-                dest = src.wrapping_sub(
-                    s_pos
-                        .wrapping_add(clamp_us_to_u32(position))
-                        .wrapping_add(5),
-                );
+                dest = if encode {
+                    src.wrapping_add(pc)
+                } else {
+                    src.wrapping_sub(pc)
+                };
                 if prev_mask == 0 {
                     break;
                 }
@@ -269,14 +408,19 @@ fn bcj_x86(s_pos: u32, buf: &mut [u8], mask: usize) -> (usize, usize) {
 }
 
 /// runs the powerpc bcj filter
-fn bcj_powerpc(s_pos: u32, buf: &mut [u8]) -> usize {
+fn bcj_powerpc(s_pos: u32, buf: &mut [u8], encode: bool) -> usize {
     let mut i: usize = 0;
     let size = buf.len() as u64 & !(3i32 as u64);
     while (i as u64) < size {
         let mut instr = u32::from_be_bytes([buf[i], buf[i + 1], buf[i + 2], buf[i + 3]]);
         if instr & 0xfc00_0003 == 0x4800_0001 {
             instr &= 0x03ff_fffc;
-            instr = instr.wrapping_sub(s_pos.wrapping_add(clamp_us_to_u32(i)));
+            let pc = s_pos.wrapping_add(clamp_us_to_u32(i));
+            instr = if encode {
+                instr.wrapping_add(pc)
+            } else {
+                instr.wrapping_sub(pc)
+            };
             instr &= 0x03ff_fffc;
             instr |= 0x4800_0001;
             let as_be = u32::to_be_bytes(instr);
@@ -291,7 +435,7 @@ fn bcj_powerpc(s_pos: u32, buf: &mut [u8]) -> usize {
 }
 
 /// runs the ia64 bcj filter
-fn bcj_ia64(s_pos: u32, buf: &mut [u8]) -> usize {
+fn bcj_ia64(s_pos: u32, buf: &mut [u8], encode: bool) -> usize {
     static BRANCH_TABLE: [u8; 32] = [
         0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4, 4, 6, 6, 0, 0, 7, 7, 4, 4, 0, 0, 4, 4,
         0, 0,
@@ -328,7 +472,12 @@ fn bcj_ia64(s_pos: u32, buf: &mut [u8]) -> usize {
             let mut addr = clamp_u64_to_u32(norm >> 13 & 0xfffff);
             addr |= ((norm >> 36) as u32 & 1) << 20;
             addr <<= 4;
-            addr = addr.wrapping_sub(s_pos.wrapping_add(clamp_us_to_u32(i)));
+            let pc = s_pos.wrapping_add(clamp_us_to_u32(i));
+            addr = if encode {
+                addr.wrapping_add(pc)
+            } else {
+                addr.wrapping_sub(pc)
+            };
             addr >>= 4;
             norm &= !((0x008f_ffff) << 13);
             norm |= u64::from(addr & 0x000f_ffff) << 13;
@@ -350,7 +499,7 @@ fn bcj_ia64(s_pos: u32, buf: &mut [u8]) -> usize {
 }
 
 /// runs the arm bcj filter
-fn bcj_arm(s_pos: u32, buf: &mut [u8]) -> usize {
+fn bcj_arm(s_pos: u32, buf: &mut [u8], encode: bool) -> usize {
     let mut i = 0;
     let size = buf.len() & !3;
     while i < size {
@@ -360,7 +509,12 @@ fn bcj_arm(s_pos: u32, buf: &mut [u8]) -> usize {
                 | u32::from(buf[i.wrapping_add(2)]) << 16;
 
             addr <<= 2;
-            addr = addr.wrapping_sub(s_pos.wrapping_add(clamp_us_to_u32(i)).wrapping_add(8));
+            let pc = s_pos.wrapping_add(clamp_us_to_u32(i)).wrapping_add(8);
+            addr = if encode {
+                addr.wrapping_add(pc)
+            } else {
+                addr.wrapping_sub(pc)
+            };
             addr >>= 2;
             buf[i] = clamp_u32_to_u8(addr);
             buf[i.wrapping_add(1)] = clamp_u32_to_u8(addr >> 8);
@@ -372,7 +526,7 @@ fn bcj_arm(s_pos: u32, buf: &mut [u8]) -> usize {
 }
 
 /// runs the armthumb bcj filter
-fn bcj_armthumb(s_pos: u32, buf: &mut [u8]) -> usize {
+fn bcj_armthumb(s_pos: u32, buf: &mut [u8], encode: bool) -> usize {
     let mut i: usize = 0;
     if buf.len() < 4 {
         return 0;
@@ -390,7 +544,12 @@ fn bcj_armthumb(s_pos: u32, buf: &mut [u8]) -> usize {
             | u32::from(buf[i + 2]);
 
         addr <<= 1;
-        addr = addr.wrapping_sub(s_pos.wrapping_add(clamp_us_to_u32(i)).wrapping_add(4));
+        let pc = s_pos.wrapping_add(clamp_us_to_u32(i)).wrapping_add(4);
+        addr = if encode {
+            addr.wrapping_add(pc)
+        } else {
+            addr.wrapping_sub(pc)
+        };
         addr >>= 1;
         buf[i + 1] = clamp_u32_to_u8(0xf0 | addr >> 19 & 0x7);
         buf[i] = clamp_u32_to_u8(addr >> 11);
@@ -402,14 +561,19 @@ fn bcj_armthumb(s_pos: u32, buf: &mut [u8]) -> usize {
 }
 
 /// runs the sparc bcj filter
-fn bcj_sparc(s_pos: u32, buf: &mut [u8]) -> usize {
+fn bcj_sparc(s_pos: u32, buf: &mut [u8], encode: bool) -> usize {
     let mut i: usize = 0;
     let size = buf.len() & !3;
     while i < size {
         let mut instr = u32::from_be_bytes([buf[i], buf[i + 1], buf[i + 2], buf[i + 3]]);
         if instr >> 22 == 0x100 || instr >> 22 == 0x1ff {
             instr <<= 2;
-            instr = instr.wrapping_sub(s_pos.wrapping_add(clamp_us_to_u32(i)));
+            let pc = s_pos.wrapping_add(clamp_us_to_u32(i));
+            instr = if encode {
+                instr.wrapping_add(pc)
+            } else {
+                instr.wrapping_sub(pc)
+            };
             instr >>= 2;
             instr = 0x4000_0000u32.wrapping_sub(instr & 0x0040_0000)
                 | 0x4000_0000
@@ -422,13 +586,18 @@ fn bcj_sparc(s_pos: u32, buf: &mut [u8]) -> usize {
 }
 
 /// runs the arm64 bcj filter
-fn bcj_arm64(s_pos: u32, buf: &mut [u8]) -> usize {
+fn bcj_arm64(s_pos: u32, buf: &mut [u8], encode: bool) -> usize {
     let mut i: usize = 0;
-    let size = buf.len() & !15;
+    let size = buf.len() & !3;
     while i < size {
         let mut instr = u32::from_le_bytes([buf[i], buf[i + 1], buf[i + 2], buf[i + 3]]);
         if instr >> 26i32 == 0x25i32 as u32 {
-            let addr = instr.wrapping_sub(s_pos.wrapping_add(clamp_us_to_u32(i)) >> 2i32);
+            let pc = s_pos.wrapping_add(clamp_us_to_u32(i)) >> 2i32;
+            let addr = if encode {
+                instr.wrapping_add(pc)
+            } else {
+                instr.wrapping_sub(pc)
+            };
             instr = 0x9400_0000 | addr & 0x03ff_ffff;
             buf[i..i + 4].copy_from_slice(instr.to_le_bytes().as_slice());
             i += 4;
@@ -442,7 +611,12 @@ fn bcj_arm64(s_pos: u32, buf: &mut [u8]) -> usize {
 
         let mut addr = instr >> 29 & 3 | instr >> 3i32 & 0x001f_fffc;
         if addr.wrapping_add(0x20000) & 0x001c_0000 == 0 {
-            addr = addr.wrapping_sub(s_pos.wrapping_add(clamp_us_to_u32(i)) >> 12i32);
+            let pc = s_pos.wrapping_add(clamp_us_to_u32(i)) >> 12i32;
+            addr = if encode {
+                addr.wrapping_add(pc)
+            } else {
+                addr.wrapping_sub(pc)
+            };
             instr &= 0x9000_001f;
             instr |= (addr & 3) << 29i32;
             instr |= (addr & 0x3fffc) << 3i32;
@@ -531,10 +705,91 @@ fn bcj_riscv(s_pos: u32, buf: &mut [u8]) -> usize {
     i
 }
 
+/// runs the riscv bcj filter in the encoding direction
+fn bcj_riscv_encode(s_pos: u32, buf: &mut [u8]) -> usize {
+    let mut i: usize = 0;
+
+    let Some(size) = buf.len().checked_sub(8) else {
+        return 0;
+    };
+
+    while i <= size {
+        let mut instr = u32::from(buf[i]);
+
+        if instr == 0xef {
+            // JAL
+            let b1 = u32::from(buf[i + 1]);
+            if b1 & 0xd != 0 {
+                i += 2;
+                continue;
+            }
+
+            let b2 = u32::from(buf[i + 2]);
+            let b3 = u32::from(buf[i + 3]);
+            let mut addr = (b1 & 0xf0) << 8
+                | (b2 & 0xf) << 16
+                | (b2 & 0x10) << 7
+                | (b2 & 0xe0) >> 4
+                | (b3 & 0x7f) << 4
+                | (b3 & 0x80) << 13;
+            addr = addr.wrapping_add(s_pos.wrapping_add(clamp_us_to_u32(i)));
+            buf[i + 1] = clamp_u32_to_u8(b1 & 0xf | addr >> 13 & 0xf0);
+            buf[i + 2] = clamp_u32_to_u8(addr >> 9);
+            buf[i + 3] = clamp_u32_to_u8(addr >> 1);
+            i += 4;
+            continue;
+        }
+
+        if instr & 0x7f != 0x17 {
+            i += 2;
+            continue;
+        }
+
+        // AUIPC
+        instr |= u32::from_le_bytes([0, buf[i + 1], buf[i + 2], buf[i + 3]]);
+
+        if instr & 0xe80 != 0 {
+            // rd is neither x0 nor x2, only convert if the next instruction uses the same register.
+            let instr2 = u32::from_le_bytes([buf[i + 4], buf[i + 5], buf[i + 6], buf[i + 7]]);
+            if (instr << 8 ^ instr2.wrapping_sub(3)) & 0xf8003 != 0 {
+                i += 6;
+                continue;
+            }
+
+            let mut addr = (instr & 0xffff_f000)
+                .wrapping_add(instr2 >> 20)
+                .wrapping_sub(instr2 >> 19 & 0x1000);
+            addr = addr.wrapping_add(s_pos.wrapping_add(clamp_us_to_u32(i)));
+            instr = 0x17 | 2 << 7 | instr2 << 12;
+
+            buf[i..i + 4].copy_from_slice(&instr.to_le_bytes());
+            buf[i + 4..i + 8].copy_from_slice(&addr.to_be_bytes());
+            i += 8;
+            continue;
+        }
+
+        // rd is x0 or x2, swap the fields so the decoder does not convert it.
+        let fake_rs1 = instr >> 27;
+        if instr.wrapping_sub(0x3117) << 18 >= fake_rs1 & 0x1d {
+            i += 4;
+            continue;
+        }
+
+        let fake_addr = u32::from_le_bytes([buf[i + 4], buf[i + 5], buf[i + 6], buf[i + 7]]);
+        let fake_instr2 = instr >> 12 | fake_addr << 20;
+        instr = 0x17 | fake_rs1 << 7 | fake_addr & 0xffff_f000;
+
+        buf[i..i + 4].copy_from_slice(&instr.to_le_bytes());
+        buf[i + 4..i + 8].copy_from_slice(&fake_instr2.to_le_bytes());
+        i += 8;
+    }
+    i
+}
+
 /// All supported bcj filters.
-#[derive(Debug, Eq, PartialEq, Default, Copy, Clone)]
+#[derive(Debug, Eq, PartialEq, Default, Copy, Clone, Hash)]
 #[repr(u8)]
-enum BcjFilter {
+pub enum BcjFilter {
     #[default]
     /// filter for RISC-V 32-bit and 64-bit architecture.
     RiscV = 0, //only so zero alloc is guaranteed to be valid!
@@ -561,6 +816,34 @@ enum BcjFilter {
     X86,
 }
 
+impl BcjFilter {
+    /// Returns the filter id of the filter in the block header of .xz files.
+    #[must_use]
+    pub const fn id(self) -> u8 {
+        match self {
+            Self::X86 => 4,
+            Self::PowerPc => 5,
+            Self::IntelIthanium64 => 6,
+            Self::Arm => 7,
+            Self::ArmThumb => 8,
+            Self::Sparc => 9,
+            Self::Arm64 => 10,
+            Self::RiscV => 11,
+        }
+    }
+
+    /// Alignment of the start offset, this is the instruction alignment of the architecture.
+    #[must_use]
+    pub const fn alignment(self) -> u32 {
+        match self {
+            Self::X86 => 1,
+            Self::ArmThumb | Self::RiscV => 2,
+            Self::PowerPc | Self::Arm | Self::Arm64 | Self::Sparc => 4,
+            Self::IntelIthanium64 => 16,
+        }
+    }
+}
+
 impl TryFrom<u8> for BcjFilter {
     type Error = XzError;
 
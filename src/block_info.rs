@@ -0,0 +1,223 @@
+#[cfg(feature = "bcj")]
+use crate::bcj::BcjFilter;
+#[cfg(feature = "bcj")]
+use crate::clamp::clamp_u64_to_u32;
+use crate::crc32::crc32;
+use crate::decoder::XzLzma2Decoder;
+use crate::vli::VliDecoder;
+use crate::XzError;
+
+/// Maximum amount of filters in a block, the last one is always lzma2.
+const FILTERS_MAX: usize = 4;
+
+/// A filter of the filter chain of a block in the order of the block header.
+/// Encoding applies them from first to last, decoding from last to first.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum XzFilterInfo {
+    /// LZMA2, this is always the last filter.
+    Lzma2 {
+        /// Size of the dictionary in bytes.
+        dict_size: usize,
+    },
+    /// Delta filter.
+    #[cfg(feature = "delta")]
+    Delta {
+        /// Distance in bytes, 1 to 256.
+        distance: usize,
+    },
+    /// Branch/Call/Jump filter.
+    #[cfg(feature = "bcj")]
+    Bcj {
+        /// The architecture.
+        filter: BcjFilter,
+        /// The start offset, usually 0.
+        start_offset: u32,
+    },
+}
+
+/// Information from a block header.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct XzBlockInfo {
+    /// Size of the block header in bytes.
+    header_size: usize,
+    /// Declared compressed size, `u64::MAX` if the header does not contain it.
+    compressed_size: u64,
+    /// Declared uncompressed size, `u64::MAX` if the header does not contain it.
+    uncompressed_size: u64,
+    /// The filters, only the first `filter_count` are used.
+    filters: [XzFilterInfo; FILTERS_MAX],
+    /// Amount of filters, 0 for an empty instance.
+    filter_count: usize,
+}
+
+impl XzBlockInfo {
+    /// Instance without any filters that is used before the first block header was decoded.
+    pub(crate) const fn empty() -> Self {
+        Self {
+            header_size: 0,
+            compressed_size: u64::MAX,
+            uncompressed_size: u64::MAX,
+            filters: [XzFilterInfo::Lzma2 { dict_size: 0 }; FILTERS_MAX],
+            filter_count: 0,
+        }
+    }
+
+    /// Parses a block header. The slice must start with the block header, any data after it is ignored.
+    ///
+    /// # Errors
+    /// - `XzError::UnexpectedEndOfInput` if the block header is incomplete.
+    /// - `XzError::BlockHeaderCrc32Mismatch` if the block header is corrupted.
+    /// - the errors of `XzDecoder::decode` for unsupported options and filters.
+    pub fn parse(block_header: &[u8]) -> Result<Self, XzError> {
+        let Some(&size_byte) = block_header.first() else {
+            return Err(XzError::UnexpectedEndOfInput);
+        };
+        if size_byte == 0 {
+            return Err(XzError::BlockHeaderTooSmall);
+        }
+
+        let size = (usize::from(size_byte) + 1) * 4;
+        if block_header.len() < size {
+            return Err(XzError::UnexpectedEndOfInput);
+        }
+
+        let buf = &block_header[..size];
+        let expected_crc =
+            u32::from_le_bytes([buf[size - 4], buf[size - 3], buf[size - 2], buf[size - 1]]);
+        let actual_crc = crc32(0, &buf[..size - 4]);
+        if actual_crc != expected_crc {
+            return Err(XzError::BlockHeaderCrc32Mismatch(actual_crc, expected_crc));
+        }
+
+        let buf = &buf[..size - 4];
+        if buf[1] & 0x3C != 0 {
+            return Err(XzError::UnsupportedBlockHeaderOption);
+        }
+
+        let mut info = Self::empty();
+        info.header_size = size;
+
+        let mut vli_decoder = VliDecoder::new();
+        let mut pos = 2usize;
+        if buf[1] & 0x40 != 0 {
+            let Some((vli, len)) = vli_decoder.decode_single(&buf[pos..]) else {
+                return Err(XzError::CorruptedCompressedLengthVliInBlockHeader);
+            };
+            pos += len;
+            info.compressed_size = vli;
+        }
+
+        if buf[1] & 0x80 != 0 {
+            let Some((vli, len)) = vli_decoder.decode_single(&buf[pos..]) else {
+                return Err(XzError::CorruptedUncompressedLengthVliInBlockHeader);
+            };
+            pos += len;
+            info.uncompressed_size = vli;
+        }
+
+        // Every filter is encoded as its id followed by the size of its properties and the properties.
+        info.filter_count = usize::from(buf[1] & 0x03) + 1;
+        for i in 0..info.filter_count {
+            let Some((id, len)) = vli_decoder.decode_single(&buf[pos..]) else {
+                return Err(XzError::BlockHeaderTooSmall);
+            };
+            pos += len;
+            let Some((props_size, len)) = vli_decoder.decode_single(&buf[pos..]) else {
+                return Err(XzError::BlockHeaderTooSmall);
+            };
+            pos += len;
+            let Some(props) = usize::try_from(props_size)
+                .ok()
+                .and_then(|props_size| buf.get(pos..pos.checked_add(props_size)?))
+            else {
+                return Err(XzError::BlockHeaderTooSmall);
+            };
+            pos += props.len();
+
+            let last = i + 1 == info.filter_count;
+            info.filters[i] = match (id, props) {
+                (0x21, &[props]) if last => XzFilterInfo::Lzma2 {
+                    dict_size: XzLzma2Decoder::dict_size_from_props(props)?,
+                },
+                _ if last => return Err(XzError::UnsupportedBlockHeaderOption),
+                #[cfg(feature = "delta")]
+                (3, &[props]) => XzFilterInfo::Delta {
+                    distance: usize::from(props) + 1,
+                },
+                #[cfg(feature = "delta")]
+                (3, _) => return Err(XzError::UnsupportedBlockHeaderOption),
+                #[cfg(not(feature = "delta"))]
+                (3, _) => return Err(XzError::DeltaFilterUnsupported),
+                // Every other filter before lzma2 is treated as bcj filter.
+                // An unknown filter id takes precedence over unsupported properties.
+                #[cfg(feature = "bcj")]
+                (id, props) => {
+                    let filter = u8::try_from(id)
+                        .map_err(|_| XzError::UnsupportedBcjFilter(clamp_u64_to_u32(id)))
+                        .and_then(BcjFilter::try_from)?;
+                    let start_offset = match *props {
+                        [] => 0,
+                        [a, b, c, d] => u32::from_le_bytes([a, b, c, d]),
+                        _ => return Err(XzError::UnsupportedBlockHeaderOption),
+                    };
+                    if start_offset % filter.alignment() != 0 {
+                        return Err(XzError::BcjFilterOffsetUnaligned(start_offset));
+                    }
+                    XzFilterInfo::Bcj {
+                        filter,
+                        start_offset,
+                    }
+                }
+                #[cfg(not(feature = "bcj"))]
+                _ => return Err(XzError::BcjFilterNotSupported),
+            };
+        }
+
+        if buf[pos..].iter().any(|&padding| padding != 0) {
+            return Err(XzError::UnsupportedBlockHeaderOption);
+        }
+
+        Ok(info)
+    }
+
+    /// Size of the block header in bytes.
+    #[must_use]
+    pub const fn header_size(&self) -> usize {
+        self.header_size
+    }
+
+    /// Compressed size of the block as declared in the block header, it is optional.
+    /// This is the size of the compressed data without the header, the padding and the check.
+    #[must_use]
+    pub const fn compressed_size(&self) -> Option<u64> {
+        if self.compressed_size == u64::MAX {
+            return None;
+        }
+        Some(self.compressed_size)
+    }
+
+    /// Uncompressed size of the block as declared in the block header, it is optional.
+    #[must_use]
+    pub const fn uncompressed_size(&self) -> Option<u64> {
+        if self.uncompressed_size == u64::MAX {
+            return None;
+        }
+        Some(self.uncompressed_size)
+    }
+
+    /// The filter chain of the block, the last filter is always `XzFilterInfo::Lzma2`.
+    #[must_use]
+    pub const fn filters(&self) -> &[XzFilterInfo] {
+        self.filters.split_at(self.filter_count).0
+    }
+
+    /// Dictionary size of the lzma2 filter in bytes.
+    #[must_use]
+    pub const fn dict_size(&self) -> usize {
+        match self.filters() {
+            [.., XzFilterInfo::Lzma2 { dict_size }] => *dict_size,
+            _ => 0,
+        }
+    }
+}
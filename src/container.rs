@@ -0,0 +1,404 @@
+use crate::clamp::clamp_u64_to_u8;
+use crate::crc32::crc32;
+#[cfg(feature = "crc64")]
+use crate::crc64xz::crc64xz;
+use crate::decoder::XzDecoderHash;
+#[cfg(feature = "sha256")]
+use crate::sha256::XzSha256;
+use crate::vli::{VliDecoder, VliResult};
+use crate::{XzCheckType, XzError};
+
+/// Size of the stream header as well as the stream footer in bytes.
+pub const STREAM_HEADER_SIZE: usize = 12;
+
+/// Magic number at the start of every stream header.
+pub const STREAM_HEADER_MAGIC: &[u8; 6] = b"\xFD7zXZ\0";
+
+/// Size of the block header written by the encoders.
+pub const BLOCK_HEADER_SIZE: usize = 12;
+
+/// Validates the 12 byte stream header and returns its check type.
+pub fn parse_stream_header(buf: &[u8]) -> Result<XzCheckType, XzError> {
+    if buf.len() < STREAM_HEADER_SIZE {
+        return Err(XzError::UnexpectedEndOfInput);
+    }
+
+    if &buf[0..STREAM_HEADER_MAGIC.len()] != STREAM_HEADER_MAGIC {
+        return Err(XzError::StreamHeaderMagicNumberMismatch);
+    }
+
+    let expected_crc = u32::from_le_bytes([buf[8], buf[9], buf[10], buf[11]]);
+    let actual_crc = crc32(0, &buf[6..8]);
+    if actual_crc != expected_crc {
+        return Err(XzError::StreamHeaderCrc32Mismatch(actual_crc, expected_crc));
+    }
+
+    if buf[6] != 0 {
+        return Err(XzError::UnsupportedStreamHeaderOption);
+    }
+
+    if buf[7] > 15 {
+        return Err(XzError::UnsupportedStreamHeaderOption);
+    }
+
+    XzCheckType::try_from(buf[7])
+}
+
+/// Validates the magic number and the crc32 of the 12 byte stream footer.
+/// Returns the size of the index in bytes and the stream flags, which must match the stream header.
+pub fn parse_stream_footer(buf: &[u8]) -> Result<(u64, [u8; 2]), XzError> {
+    if buf.len() < STREAM_HEADER_SIZE {
+        return Err(XzError::UnexpectedEndOfInput);
+    }
+
+    if &buf[10..12] != b"YZ" {
+        return Err(XzError::FooterMagicNumberMismatch);
+    }
+
+    let expected_crc = u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]);
+    let actual_crc = crc32(0, &buf[4..10]);
+    if actual_crc != expected_crc {
+        return Err(XzError::FooterCrc32Mismatch(actual_crc, expected_crc));
+    }
+
+    let backward_size = u64::from(u32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]));
+    Ok(((backward_size + 1) * 4, [buf[8], buf[9]]))
+}
+
+/// Position of the index decoder within the index.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum XzIndexSequence {
+    /// The index indicator, a zero byte.
+    #[default]
+    Indicator,
+    /// The amount of records.
+    Count,
+    /// The unpadded size of a record.
+    Unpadded,
+    /// The uncompressed size of a record.
+    Uncompressed,
+    /// Zero bytes up to the next multiple of 4 bytes.
+    Padding,
+    /// The crc32 of everything before it.
+    Crc32,
+}
+
+/// Streaming decoder for the index of a stream.
+#[derive(Clone, Debug, Default)]
+pub struct XzIndexDecoder {
+    /// Position within the index.
+    sequence: XzIndexSequence,
+    /// Decoder for the current vli.
+    vli_decoder: VliDecoder,
+    /// Amount of records that still need to be decoded.
+    remaining: u64,
+    /// Unpadded size of the current record.
+    unpadded: u64,
+    /// Amount of bytes decoded before the crc32.
+    size: u64,
+    /// Crc32 of the bytes decoded before the crc32.
+    crc32: u32,
+    /// The crc32 stored in the index.
+    stored_crc32: [u8; 4],
+    /// Amount of bytes of `stored_crc32` that were decoded.
+    stored_crc32_pos: usize,
+    /// Hash of all records.
+    hash: XzDecoderHash,
+}
+
+impl XzIndexDecoder {
+    /// Constructor
+    pub const fn new() -> Self {
+        Self {
+            sequence: XzIndexSequence::Indicator,
+            vli_decoder: VliDecoder::new(),
+            remaining: 0,
+            unpadded: 0,
+            size: 0,
+            crc32: 0,
+            stored_crc32: [0; 4],
+            stored_crc32_pos: 0,
+            hash: XzDecoderHash::new(),
+        }
+    }
+
+    /// Reset to the start of an index.
+    pub const fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Size of the index including the crc32, only complete once the entire index was decoded.
+    pub const fn size(&self) -> u64 {
+        self.size + 4
+    }
+
+    /// Hash of all records, only complete once the entire index was decoded.
+    pub const fn hash(&self) -> &XzDecoderHash {
+        &self.hash
+    }
+
+    /// Decodes the next part of the index starting with the index indicator.
+    /// `expected_count` is the amount of records the index must contain, if it is known.
+    /// `record` is called with the unpadded size and the uncompressed size of every block in order.
+    /// Returns the amount of bytes consumed and whether the index was decoded entirely.
+    pub fn decode(
+        &mut self,
+        input: &[u8],
+        expected_count: Option<u64>,
+        record: impl FnMut(u64, u64),
+    ) -> Result<(usize, bool), XzError> {
+        let mut pos = self.decode_records(input, expected_count, record)?;
+        self.crc32 = crc32(self.crc32, &input[..pos]);
+        self.size += pos as u64;
+        if self.sequence != XzIndexSequence::Crc32 {
+            return Ok((pos, false));
+        }
+
+        let count = (self.stored_crc32.len() - self.stored_crc32_pos).min(input.len() - pos);
+        self.stored_crc32[self.stored_crc32_pos..][..count]
+            .copy_from_slice(&input[pos..pos + count]);
+        self.stored_crc32_pos += count;
+        pos += count;
+        if self.stored_crc32_pos < self.stored_crc32.len() {
+            return Ok((pos, false));
+        }
+
+        let expected_crc = u32::from_le_bytes(self.stored_crc32);
+        if self.crc32 != expected_crc {
+            return Err(XzError::IndexCrc32Mismatch(self.crc32, expected_crc));
+        }
+
+        Ok((pos, true))
+    }
+
+    /// Decodes everything before the crc32. Returns the amount of bytes consumed.
+    fn decode_records(
+        &mut self,
+        input: &[u8],
+        expected_count: Option<u64>,
+        mut record: impl FnMut(u64, u64),
+    ) -> Result<usize, XzError> {
+        let mut pos = 0;
+        loop {
+            match self.sequence {
+                XzIndexSequence::Indicator => {
+                    let Some(indicator) = input.get(pos) else {
+                        return Ok(pos);
+                    };
+                    if *indicator != 0 {
+                        return Err(XzError::CorruptedDataInBlockIndex);
+                    }
+                    pos += 1;
+                    self.sequence = XzIndexSequence::Count;
+                }
+                XzIndexSequence::Count
+                | XzIndexSequence::Unpadded
+                | XzIndexSequence::Uncompressed => {
+                    let vli = match self.vli_decoder.decode(&input[pos..]) {
+                        VliResult::Ok(vli, length) => {
+                            pos += length;
+                            vli
+                        }
+                        VliResult::MoreDataNeeded(length) => return Ok(pos + length),
+                        VliResult::InvalidVli => return Err(XzError::CorruptedDataInBlockIndex),
+                    };
+
+                    match self.sequence {
+                        XzIndexSequence::Count => {
+                            if expected_count.is_some_and(|count| count != vli) {
+                                return Err(XzError::CorruptedDataInBlockIndex);
+                            }
+                            self.remaining = vli;
+                            self.sequence = XzIndexSequence::Unpadded;
+                        }
+                        XzIndexSequence::Unpadded => {
+                            self.unpadded = vli;
+                            self.hash.unpadded = self.hash.unpadded.wrapping_add(vli);
+                            self.sequence = XzIndexSequence::Uncompressed;
+                        }
+                        _ => {
+                            self.hash.uncompressed = self.hash.uncompressed.wrapping_add(vli);
+                            self.hash.calculate_crc32();
+                            record(self.unpadded, vli);
+                            self.remaining -= 1;
+                            self.sequence = XzIndexSequence::Unpadded;
+                        }
+                    }
+
+                    if self.remaining == 0 {
+                        self.sequence = XzIndexSequence::Padding;
+                    }
+                }
+                XzIndexSequence::Padding => {
+                    if self.size.wrapping_add(pos as u64) % 4 == 0 {
+                        self.sequence = XzIndexSequence::Crc32;
+                        return Ok(pos);
+                    }
+                    let Some(padding) = input.get(pos) else {
+                        return Ok(pos);
+                    };
+                    if *padding != 0 {
+                        return Err(XzError::CorruptedData);
+                    }
+                    pos += 1;
+                }
+                XzIndexSequence::Crc32 => return Ok(pos),
+            }
+        }
+    }
+}
+
+/// Encodes a stream header.
+pub fn enc_stream_header(check_type: XzCheckType) -> [u8; STREAM_HEADER_SIZE] {
+    let mut buf = [0u8; STREAM_HEADER_SIZE];
+    buf[..6].copy_from_slice(STREAM_HEADER_MAGIC);
+    buf[7] = check_type.into();
+    let crc = crc32(0, &buf[6..8]);
+    buf[8..].copy_from_slice(&crc.to_le_bytes());
+    buf
+}
+
+/// Encodes a stream footer for an index of the given size in bytes.
+pub fn enc_stream_footer(index_size: u64, flags: u8) -> [u8; STREAM_HEADER_SIZE] {
+    let mut buf = [0u8; STREAM_HEADER_SIZE];
+    let backward_size = u32::try_from(index_size / 4 - 1).unwrap_or(u32::MAX);
+    buf[4..8].copy_from_slice(&backward_size.to_le_bytes());
+    buf[9] = flags;
+    buf[10..12].copy_from_slice(b"YZ");
+    let crc = crc32(0, &buf[4..10]);
+    buf[0..4].copy_from_slice(&crc.to_le_bytes());
+    buf
+}
+
+/// Encodes the header of a block with a single lzma2 filter and without compressed or uncompressed size.
+pub fn enc_lzma2_block_header(dict_size_props: u8) -> [u8; BLOCK_HEADER_SIZE] {
+    // Header size / 4 - 1, flags (1 filter, no sizes), lzma2 filter id, properties size, dict size
+    let mut buf = [0u8; BLOCK_HEADER_SIZE];
+    buf[..5].copy_from_slice(&[2, 0, 0x21, 1, dict_size_props]);
+    let crc = crc32(0, &buf[..BLOCK_HEADER_SIZE - 4]);
+    buf[BLOCK_HEADER_SIZE - 4..].copy_from_slice(&crc.to_le_bytes());
+    buf
+}
+
+/// Encodes a vli into buf, which must be at least 9 bytes long.
+/// Returns the amount of bytes written.
+fn enc_vli(mut value: u64, buf: &mut [u8]) -> usize {
+    let mut pos = 0;
+    while value >= 0x80 {
+        buf[pos] = clamp_u64_to_u8(value) | 0x80;
+        value >>= 7;
+        pos += 1;
+    }
+    buf[pos] = clamp_u64_to_u8(value);
+    pos + 1
+}
+
+/// Index and stream footer of a stream that contains at most a single block.
+/// Passing it to a decoder after the block verifies the sizes of the block against its index record.
+#[derive(Debug, Clone, Copy)]
+pub struct BlockTrailer {
+    /// The index followed by the stream footer.
+    buf: [u8; 40],
+    /// Amount of bytes in buf.
+    size: usize,
+}
+
+impl BlockTrailer {
+    /// Creates the trailer for a block.
+    pub fn new(unpadded_size: u64, uncompressed_size: u64, check_type: XzCheckType) -> Self {
+        Self::with_record(Some((unpadded_size, uncompressed_size)), check_type)
+    }
+
+    /// Creates the trailer of a stream without blocks.
+    pub fn empty(check_type: XzCheckType) -> Self {
+        Self::with_record(None, check_type)
+    }
+
+    /// Creates the trailer with an index that contains the given record, if any.
+    fn with_record(record: Option<(u64, u64)>, check_type: XzCheckType) -> Self {
+        let mut buf = [0u8; 40];
+        let mut size = 2;
+        if let Some((unpadded_size, uncompressed_size)) = record {
+            buf[1] = 1;
+            size += enc_vli(unpadded_size, &mut buf[size..]);
+            size += enc_vli(uncompressed_size, &mut buf[size..]);
+        }
+        size = (size + 3) & !3;
+        let crc = crc32(0, &buf[..size]);
+        buf[size..size + 4].copy_from_slice(&crc.to_le_bytes());
+        size += 4;
+        buf[size..size + STREAM_HEADER_SIZE]
+            .copy_from_slice(&enc_stream_footer(size as u64, check_type.into()));
+        size += STREAM_HEADER_SIZE;
+        Self { buf, size }
+    }
+
+    /// The bytes of the trailer.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.buf[..self.size]
+    }
+}
+
+/// Running check of the uncompressed data of a block.
+#[derive(Debug, Clone)]
+pub enum XzCheckState {
+    /// No check
+    None,
+    /// Crc32 of the data so far
+    Crc32(u32),
+    /// Crc64 of the data so far
+    #[cfg(feature = "crc64")]
+    Crc64(u64),
+    /// Sha256 of the data so far
+    #[cfg(feature = "sha256")]
+    Sha256(XzSha256),
+}
+
+impl XzCheckState {
+    /// Constructor
+    pub const fn new(check_type: XzCheckType) -> Self {
+        match check_type {
+            XzCheckType::None => Self::None,
+            XzCheckType::Crc32 => Self::Crc32(0),
+            #[cfg(feature = "crc64")]
+            XzCheckType::Crc64 => Self::Crc64(0),
+            #[cfg(feature = "sha256")]
+            XzCheckType::Sha256 => Self::Sha256(XzSha256::new()),
+        }
+    }
+
+    /// Update the check with some data.
+    pub fn update(&mut self, buf: &[u8]) {
+        match self {
+            Self::None => {}
+            Self::Crc32(crc) => *crc = crc32(*crc, buf),
+            #[cfg(feature = "crc64")]
+            Self::Crc64(crc) => *crc = crc64xz(*crc, buf),
+            #[cfg(feature = "sha256")]
+            Self::Sha256(sha) => sha.update(buf),
+        }
+    }
+
+    /// Writes the check into buf and returns its size. The check is reset afterward.
+    pub fn finish(&mut self, buf: &mut [u8; 32]) -> usize {
+        match self {
+            Self::None => 0,
+            Self::Crc32(crc) => {
+                buf[..4].copy_from_slice(&crc.to_le_bytes());
+                *crc = 0;
+                4
+            }
+            #[cfg(feature = "crc64")]
+            Self::Crc64(crc) => {
+                buf[..8].copy_from_slice(&crc.to_le_bytes());
+                *crc = 0;
+                8
+            }
+            #[cfg(feature = "sha256")]
+            Self::Sha256(sha) => {
+                *buf = sha.finish();
+                32
+            }
+        }
+    }
+}
@@ -1,12 +1,13 @@
 #[cfg(feature = "bcj")]
 use crate::bcj::BcjFilterState;
+use crate::block_info::{XzBlockInfo, XzFilterInfo};
 use crate::clamp::{clamp_u32_to_u16, clamp_u32_to_u8, clamp_u64_to_u32, clamp_us_to_u32};
+use crate::container::{parse_stream_footer, parse_stream_header, XzIndexDecoder};
 use crate::crc32::crc32;
 #[cfg(feature = "delta")]
 use crate::delta::DeltaDecoder;
 #[cfg(feature = "sha256")]
 use crate::sha256::XzSha256;
-use crate::vli::{VliDecoder, VliResult};
 use crate::DICT_SIZE_MAX;
 #[cfg(feature = "alloc")]
 use alloc::boxed::Box;
@@ -14,9 +15,14 @@ use alloc::boxed::Box;
 use alloc::vec;
 use core::fmt::{Debug, Display, Formatter};
 use core::mem;
-#[cfg(feature = "delta")]
-use core::num::NonZeroUsize;
-use core::ops::{Deref, DerefMut, Sub};
+use core::num::NonZeroU64;
+use core::ops::{Deref, DerefMut};
+
+/// Size of the stack buffer that receives the output of blocks with bcj or delta filters in `XzDecoder::verify`.
+const VERIFY_BUFFER_SIZE: usize = 4096;
+
+/// Amount of bytes that are dropped from the temp buffer once it is full while searching for the next block.
+const RESYNC_WINDOW_DROP: usize = 512;
 
 /// Input Output Buffer
 #[derive(Debug)]
@@ -29,6 +35,10 @@ pub struct XzInOutBuffer<'a> {
     out: &'a mut [u8],
     /// Output position
     out_pos: usize,
+    /// Output length, only differs from the length of `out` if the output is discarded.
+    out_len: usize,
+    /// The output is only counted and never written to `out`.
+    discard: bool,
 }
 
 impl<'a> XzInOutBuffer<'a> {
@@ -37,22 +47,43 @@ impl<'a> XzInOutBuffer<'a> {
         Self {
             input,
             input_pos: 0,
+            out_len: output.len(),
             out: output,
             out_pos: 0,
+            discard: false,
+        }
+    }
+
+    /// Constructor for a buffer that counts the output instead of writing it.
+    /// The output is full after the first flush of the dictionary, see `XzDictBuffer::discarded`.
+    pub const fn discard(input: &'a [u8]) -> Self {
+        Self {
+            input,
+            input_pos: 0,
+            out: &mut [],
+            out_pos: 0,
+            out_len: usize::MAX,
+            discard: true,
         }
     }
 
+    /// Returns true if the output is discarded.
+    pub const fn is_discard(&self) -> bool {
+        self.discard
+    }
+
+    /// Counts `amount` bytes of discarded output, afterward the output is full.
+    const fn discard_output(&mut self, amount: usize) {
+        debug_assert!(self.discard);
+        self.out_pos += amount;
+        self.out_len = self.out_pos;
+    }
+
     /// get the input position
     pub const fn input_position(&self) -> usize {
         self.input_pos
     }
 
-    /// Set the input position
-    pub const fn input_seek_set(&mut self, position: usize) {
-        debug_assert!(position <= self.input.len(),);
-        self.input_pos = position;
-    }
-
     /// Copy input to output at current position.
     /// # Panics
     /// if `copy_size` is larger than the remaining bytes in the output or input.
@@ -115,7 +146,7 @@ impl<'a> XzInOutBuffer<'a> {
 
     /// Returns the full size of the output
     pub const fn output_len(&self) -> usize {
-        self.out.len()
+        self.out_len
     }
 
     /// Returns the output slice starting at `out_pos`.
@@ -184,8 +215,9 @@ impl<'a> XzInOutBuffer<'a> {
     }
 }
 
-/// The lzma2 decoder
+/// The lzma2 decoder, also used to decode lzma1 streams.
 #[derive(Debug)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct XzLzma2Decoder {
     /// Range decoder
     rc: RcDecoder,
@@ -207,11 +239,21 @@ pub struct XzLzma2Decoder {
     temp_size: usize,
     /// small temporary buffer
     temp_buf: [u8; 63],
+    /// Is the end of payload marker allowed? Only lzma1 streams may contain it.
+    allow_eopm: bool,
+    /// Must the next symbol be the end of payload marker?
+    eopm_expected: bool,
+    /// Was the end of payload marker decoded?
+    eopm_found: bool,
+    /// Remaining uncompressed size of a lzma1 stream, `u64::MAX` if unknown.
+    lzma1_uncompressed: u64,
+    /// Is the end of the lzma1 input known? If true then `compressed` is the exact amount of remaining input.
+    lzma1_input_end: bool,
 }
 
 impl XzLzma2Decoder {
     /// Constructor.
-    const fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
             rc: RcDecoder::new(),
             sequence: LzmaStreamState::Control,
@@ -223,6 +265,11 @@ impl XzLzma2Decoder {
             lzma: LzmaDecoderState::new(),
             temp_size: 0,
             temp_buf: [0; 63],
+            allow_eopm: false,
+            eopm_expected: false,
+            eopm_found: false,
+            lzma1_uncompressed: 0,
+            lzma1_input_end: false,
         }
     }
 
@@ -232,13 +279,21 @@ impl XzLzma2Decoder {
         self.rc.reset();
     }
 
-    /// reset lzma decoder.
-    fn xz_dec_lzma2_reset(&mut self, props: u8, d: &mut XzDictBuffer) -> Result<(), XzError> {
+    /// Decodes the dictionary size from the lzma2 dictionary properties byte.
+    pub fn dict_size_from_props(props: u8) -> Result<usize, XzError> {
         if props > 39 {
             return Err(XzError::UnsupportedLzmaProperties(u32::from(props)));
         }
-        let mut dict_size = 2 + usize::from(props & 1);
-        dict_size <<= (props >> 1) + 11;
+        let dict_size = 2 + usize::from(props & 1);
+        Ok(dict_size << ((props >> 1) + 11))
+    }
+
+    /// reset lzma decoder to decode a new lzma2 stream with the given dictionary size.
+    pub fn reset_dict_size(
+        &mut self,
+        dict_size: usize,
+        d: &mut XzDictBuffer,
+    ) -> Result<(), XzError> {
         if dict_size > d.max_size() {
             return Err(XzError::DictionaryTooLarge(dict_size as u64));
         }
@@ -248,9 +303,51 @@ impl XzLzma2Decoder {
         self.sequence = LzmaStreamState::Control;
         self.need_dict_reset = true;
         self.temp_size = 0;
+        self.allow_eopm = false;
+        self.eopm_expected = false;
+        self.eopm_found = false;
+        Ok(())
+    }
+
+    /// reset lzma decoder to decode a new lzma1 stream.
+    ///
+    /// `props` is the lc/lp/pb properties byte, `uncompressed` is the uncompressed size
+    /// of the stream or `u64::MAX` if unknown. Streams of unknown size must end with the end of payload marker.
+    pub fn reset_lzma1(
+        &mut self,
+        props: u8,
+        dict_size: usize,
+        uncompressed: u64,
+        allow_eopm: bool,
+        d: &mut XzDictBuffer,
+    ) -> Result<(), XzError> {
+        if dict_size > d.max_size() {
+            return Err(XzError::DictionaryTooLarge(dict_size as u64));
+        }
+
+        self.lzma_props(props)?;
+        d.alloc_dict(dict_size)?;
+        d.dict_reset();
+
+        self.sequence = LzmaStreamState::LzmaPrepare;
+        self.need_dict_reset = false;
+        self.need_props = false;
+        self.temp_size = 0;
+        self.compressed = usize::MAX;
+        self.allow_eopm = allow_eopm || uncompressed == u64::MAX;
+        self.eopm_expected = false;
+        self.eopm_found = false;
+        self.lzma1_uncompressed = uncompressed;
+        self.lzma1_input_end = false;
         Ok(())
     }
 
+    /// Input bytes that were buffered by the decoder but are located after the end of the lzma1 stream.
+    /// Only meaningful after a lzma1 stream ended.
+    pub fn lzma1_trailing_input(&self) -> &[u8] {
+        &self.temp_buf[..self.temp_size]
+    }
+
     /// main lzma2 decoding loop
     fn lzma_main(&mut self, rcb: &mut RcBuf, d: &mut XzDictBuffer) -> Result<(), XzError> {
         if d.dict_has_space() && self.lzma.len > 0 {
@@ -260,11 +357,14 @@ impl XzLzma2Decoder {
             }
         }
 
-        while d.dict_has_space() && !rcb.limit_exceeded() {
+        while (d.dict_has_space() || self.eopm_expected) && !rcb.limit_exceeded() {
             let pos_state = d.dict_pos() & self.lzma.pos_mask;
             let index = (16 * self.lzma.state.num()) + pos_state;
 
             if self.rc.rc_bit(&mut self.lzma.is_match[index], rcb) {
+                if self.eopm_expected {
+                    return Err(XzError::CorruptedDataInLzma);
+                }
                 self.lzma_literal(rcb, d);
                 continue;
             }
@@ -274,10 +374,19 @@ impl XzLzma2Decoder {
                 .rc_bit(&mut self.lzma.is_rep[self.lzma.state as usize], rcb)
             {
                 self.lzma_match(clamp_us_to_u32(pos_state), rcb);
+                if self.allow_eopm && self.lzma.rep0 == u32::MAX {
+                    self.lzma.len = 0;
+                    self.eopm_found = true;
+                    break;
+                }
             } else {
                 self.lzma_rep_match(clamp_us_to_u32(pos_state), rcb);
             }
 
+            if self.eopm_expected {
+                return Err(XzError::CorruptedDataInLzma);
+            }
+
             self.lzma.len -= d.dict_repeat(self.lzma.rep0 as usize, self.lzma.len)?;
         }
         self.rc.normalize(rcb);
@@ -341,6 +450,9 @@ impl XzLzma2Decoder {
 
             b.input_seek_add(rcb.in_pos.wrapping_sub(self.temp_size));
             self.temp_size = 0;
+            if self.eopm_found {
+                return Ok(());
+            }
         }
 
         let mut in_avail = b.in_size().wrapping_sub(b.input_pos);
@@ -351,7 +463,7 @@ impl XzLzma2Decoder {
                 in_limit: 0,
             };
 
-            if in_avail >= self.compressed + 21 {
+            if in_avail >= self.compressed.saturating_add(21) {
                 rcb.in_limit = b.input_pos + self.compressed;
             } else {
                 rcb.in_limit = b.in_size() - 21;
@@ -367,6 +479,9 @@ impl XzLzma2Decoder {
             //TODO doesnt wrap!
             self.compressed = self.compressed.wrapping_sub(in_avail);
             b.input_pos = rcb.in_pos;
+            if self.eopm_found {
+                return Ok(());
+            }
         }
         in_avail = b.input_remaining();
         if in_avail < 21 {
@@ -531,6 +646,80 @@ impl XzLzma2Decoder {
         }
     }
 
+    /// process a lzma1 stream that was set up by `reset_lzma1`.
+    ///
+    /// `input_finished` signals that no more input follows the input in `b`.
+    pub fn xz_dec_lzma1_run(
+        &mut self,
+        b: &mut XzInOutBuffer,
+        d: &mut XzDictBuffer,
+        input_finished: bool,
+    ) -> Result<DecodeResult, XzError> {
+        if self.sequence == LzmaStreamState::LzmaPrepare {
+            if !self.rc.read_init(b) {
+                if input_finished {
+                    return Err(XzError::UnexpectedEndOfInput);
+                }
+                return Ok(DecodeResult::NeedMoreData);
+            }
+            self.sequence = LzmaStreamState::LzmaRun;
+        }
+
+        loop {
+            if input_finished && !self.lzma1_input_end {
+                self.compressed = self.temp_size + b.input_remaining();
+                self.lzma1_input_end = true;
+            }
+
+            let known_size = self.lzma1_uncompressed != u64::MAX;
+            let out_max = if known_size {
+                usize::try_from(self.lzma1_uncompressed)
+                    .unwrap_or(usize::MAX)
+                    .min(b.output_remaining())
+            } else {
+                b.output_remaining()
+            };
+
+            d.dict_limit(out_max);
+
+            self.lzma2_lzma(b, d)?;
+
+            let flushed = d.dict_flush(b);
+            if known_size {
+                self.lzma1_uncompressed -= flushed as u64;
+            }
+
+            if self.eopm_found {
+                if (known_size && self.lzma1_uncompressed != 0) || !self.rc.is_finished() {
+                    return Err(XzError::CorruptedDataInLzma);
+                }
+                return Ok(DecodeResult::EndOfDataStructure);
+            }
+
+            if known_size && self.lzma1_uncompressed == 0 && !self.eopm_expected {
+                if self.lzma.len > 0 {
+                    return Err(XzError::CorruptedDataInLzma);
+                }
+                if self.rc.is_finished() {
+                    return Ok(DecodeResult::EndOfDataStructure);
+                }
+                if !self.allow_eopm {
+                    return Err(XzError::CorruptedDataInLzma);
+                }
+                self.eopm_expected = true;
+                continue;
+            }
+
+            if b.output_remaining() == 0 && !self.eopm_expected {
+                return Ok(DecodeResult::NeedMoreData);
+            }
+
+            if b.input_remaining() == 0 && !self.lzma1_input_end {
+                return Ok(DecodeResult::NeedMoreData);
+            }
+        }
+    }
+
     /// Decode the length of the match into self.lzma.len.
     fn lzma_len(&mut self, is_rep: bool, pos_state: u32, rcb: &mut RcBuf) {
         let l = if is_rep {
@@ -901,7 +1090,7 @@ impl Default for LzmaLenDecoder {
 /// either short or long repeated match, and `NonLit` means any non-literal.
 #[derive(Clone, Debug, Copy, Default, Ord, PartialEq, Eq, PartialOrd, Hash)]
 #[repr(u8)]
-enum LzmaState {
+pub enum LzmaState {
     #[default]
     LitLit = 0,
     ///TODO
@@ -930,7 +1119,7 @@ enum LzmaState {
 
 impl LzmaState {
     /// numeric value of the state, used in some computations.
-    const fn num(self) -> usize {
+    pub const fn num(self) -> usize {
         match self {
             Self::LitLit => 0,
             Self::MatchLitLit => 1,
@@ -949,7 +1138,7 @@ impl LzmaState {
 
     /// State transition
     #[allow(clippy::match_same_arms)]
-    const fn u32_literal(self) -> Self {
+    pub const fn u32_literal(self) -> Self {
         match self {
             Self::LitLit => Self::LitLit,
             Self::MatchLitLit => Self::LitLit,
@@ -968,7 +1157,7 @@ impl LzmaState {
 
     /// State transition
     #[allow(clippy::match_same_arms)]
-    const fn u32_match(self) -> Self {
+    pub const fn u32_match(self) -> Self {
         match self {
             Self::LitLit => Self::LitMatch,
             Self::MatchLitLit => Self::LitMatch,
@@ -987,7 +1176,7 @@ impl LzmaState {
 
     /// State transition
     #[allow(clippy::match_same_arms)]
-    const fn u32_long_rep(self) -> Self {
+    pub const fn u32_long_rep(self) -> Self {
         match self {
             Self::LitLit => Self::LitLongRep,
             Self::MatchLitLit => Self::LitLongRep,
@@ -1006,7 +1195,7 @@ impl LzmaState {
 
     /// State transition
     #[allow(clippy::match_same_arms)]
-    const fn u32_short_rep(self) -> Self {
+    pub const fn u32_short_rep(self) -> Self {
         match self {
             Self::LitLit => Self::LitShortRep,
             Self::MatchLitLit => Self::LitShortRep,
@@ -1024,7 +1213,7 @@ impl LzmaState {
     }
 
     /// is the state literal.
-    const fn u32_is_literal(self) -> bool {
+    pub const fn u32_is_literal(self) -> bool {
         matches!(
             self,
             Self::LitLit
@@ -1252,7 +1441,7 @@ impl RcDecoder {
 
 /// Holds the actual buffer allocation.
 #[derive(Debug)]
-enum XzDictBufferAllocation<'a> {
+pub enum XzDictBufferAllocation<'a> {
     /// dynamic allocation in the heap. We use Vec for this.
     #[cfg(feature = "alloc")]
     Alloc(vec::Vec<u8>, usize),
@@ -1297,11 +1486,13 @@ pub struct XzDictBuffer<'a> {
     dict_full: usize,
     ///TODO CONSOLIDATE
     dict_limit: usize,
+    /// Start of the data of the last flush into a discarded output.
+    discarded_start: usize,
 }
 
 impl<'a> XzDictBuffer<'a> {
     /// Constructor
-    const fn new(buffer: XzDictBufferAllocation<'a>) -> Self {
+    pub const fn new(buffer: XzDictBufferAllocation<'a>) -> Self {
         Self {
             buffer,
             dict_start: 0,
@@ -1309,6 +1500,7 @@ impl<'a> XzDictBuffer<'a> {
             dict_size: 0,
             dict_full: 0,
             dict_limit: 0,
+            discarded_start: 0,
         }
     }
 
@@ -1446,6 +1638,14 @@ impl<'a> XzDictBuffer<'a> {
             let src = &b.input_slice()[..copy_size];
             target.copy_from_slice(src);
 
+            if b.is_discard() {
+                b.input_seek_add(copy_size);
+                b.discard_output(copy_size);
+                self.discarded_start = buf_pos;
+            } else {
+                b.copy_in_to_out(copy_size);
+            }
+
             self.set_dict_pos(self.dict_pos().wrapping_add(copy_size));
             if (self.dict_full()) < self.dict_pos() {
                 self.set_dict_full();
@@ -1455,7 +1655,6 @@ impl<'a> XzDictBuffer<'a> {
                 self.set_dict_pos(0);
             }
 
-            b.copy_in_to_out(copy_size);
             self.set_dict_start();
         }
 
@@ -1503,13 +1702,31 @@ impl<'a> XzDictBuffer<'a> {
         }
 
         let dict_start = self.dict_start();
-        let source = &self.buffer()[dict_start..dict_start + copy_size];
-        b.copy_to_output(source);
+        if b.is_discard() {
+            //Only advances dict_start, the data stays in the dictionary for discarded().
+            if copy_size > 0 {
+                b.discard_output(copy_size);
+                self.discarded_start = dict_start;
+            }
+        } else {
+            let source = &self.buffer()[dict_start..dict_start + copy_size];
+            b.copy_to_output(source);
+        }
 
         self.set_dict_start();
         copy_size
     }
 
+    /// Returns the `len` bytes of the last flush into a discarded output.
+    /// Only valid until the dictionary is written again.
+    fn discarded(&self, len: usize) -> &[u8] {
+        if len == 0 {
+            return &[];
+        }
+
+        &self.buffer()[self.discarded_start..self.discarded_start + len]
+    }
+
     /// sets the dictionary size.
     const fn dict_size(&self) -> usize {
         self.dict_size
@@ -1609,20 +1826,24 @@ enum XzDecoderState {
     StreamStart,
     /// TODO
     BlockHeader,
+    /// The block header is in the temp buffer, its memory usage is checked before it is decoded.
+    BlockHeaderDecode,
     /// TODO
     BlockUncompress,
     /// TODO
     BlockPadding,
     /// TODO
     BlockCheck,
-    /// TODO
+    /// The index including its padding and crc32.
     Index,
     /// TODO
-    IndexPadding,
-    /// TODO
-    IndexCrc32,
-    /// TODO
     StreamFooter,
+    /// Zero bytes after a stream footer, only consumed in multi stream mode.
+    StreamPadding,
+    /// Skips the rest of a damaged block whose end is known.
+    BlockSkip,
+    /// Searches for the next block header or the stream footer after a damaged block.
+    Resync,
 }
 
 #[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash, PartialOrd, Ord)]
@@ -1651,8 +1872,9 @@ impl Display for XzCheckType {
 }
 
 impl XzCheckType {
-    /// returns the size of the check in bytes.
-    const fn check_size(self) -> usize {
+    /// Returns the size of the check in bytes.
+    #[must_use]
+    pub const fn check_size(self) -> usize {
         match self {
             #[cfg(feature = "sha256")]
             Self::Sha256 => 32,
@@ -1706,6 +1928,9 @@ impl TryFrom<u8> for XzCheckType {
 #[derive(Debug)]
 pub enum XzNextBlockResult {
     NeedMoreData(usize, usize),
+    /// A stream ended.
+    /// In multi stream mode this is returned at the end of every stream
+    /// and decoding may continue with the next stream without calling reset.
     EndOfStream(usize, usize),
 }
 
@@ -1735,6 +1960,105 @@ impl XzNextBlockResult {
     }
 }
 
+/// Result of the integrity check of a block.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+#[repr(u8)]
+pub enum XzCheckResult {
+    /// The stream has no check, the data of the block was not verified.
+    None,
+    /// The check of the block matched its data.
+    Verified,
+    /// The check was not computed because the decoder ignores checks.
+    Ignored,
+}
+
+/// Information about a block that was completely decoded, see `XzDecoder::set_stop_at_block_end`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub struct XzBlockEnd {
+    /// Index of the stream that contains the block.
+    pub stream_index: u64,
+    /// Index of the block in its stream.
+    pub block_index: u64,
+    /// Size of the block header, the compressed data and the check. Same as in the index.
+    pub unpadded_size: u64,
+    /// Size of the compressed data of the block, without the block header, the padding and the check.
+    pub compressed_size: u64,
+    /// Size of the uncompressed data of the block.
+    pub uncompressed_size: u64,
+    /// Check type of the stream.
+    pub check_type: XzCheckType,
+    /// Result of the integrity check.
+    pub check_result: XzCheckResult,
+}
+
+/// Snapshot of the progress of a decoder, see `XzDecoder::stats`.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub struct XzDecoderStats {
+    /// Compressed bytes consumed since the decoder was created or reset, this includes all headers and padding.
+    pub compressed_consumed: u64,
+    /// Uncompressed bytes produced since the decoder was created or reset.
+    pub uncompressed_produced: u64,
+    /// Index of the current stream, only larger than 0 in multi stream mode.
+    pub stream_index: u64,
+    /// Index of the current block in the current stream. This is the amount of blocks that were already checked.
+    pub block_index: u64,
+    /// Check type of the current stream, or of the last stream until the next stream header was decoded.
+    pub check_type: XzCheckType,
+    /// Dictionary size of the current block in bytes, 0 until the first block header was decoded.
+    pub dict_size: usize,
+    /// Compressed bytes of the current block consumed so far, excluding the block header.
+    pub block_compressed_position: u64,
+    /// Uncompressed bytes of the current block produced so far.
+    pub block_uncompressed_position: u64,
+    /// True if the decoder ignores the checks of blocks, see `XzDecoder::set_ignore_check`.
+    pub check_ignored: bool,
+    /// Amount of blocks that were skipped in recovery mode, see `XzDecoder::set_recovery`.
+    pub damaged_blocks: u64,
+}
+
+/// A block that failed to decode in recovery mode, see `XzDecoder::set_recovery`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub struct XzDamagedBlock {
+    /// Index of the stream that contains the block.
+    pub stream_index: u64,
+    /// Index of the block in its stream. Blocks that were skipped while searching for the next block are not counted.
+    pub block_index: u64,
+    /// Offset of the block header in the compressed data, counted since the decoder was created or reset.
+    pub compressed_offset: u64,
+    /// Offset of the data of the block in the uncompressed data, counted since the decoder was created or reset.
+    pub uncompressed_offset: u64,
+    /// Uncompressed bytes of the block that were produced before the error was detected.
+    /// This data was not verified and may be wrong.
+    pub uncompressed_produced: u64,
+    /// The error that damaged the block.
+    pub error: XzError,
+}
+
+/// Position of the decoder when `decode` failed, see `XzDecoder::error_context`.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub struct XzErrorContext {
+    /// Compressed bytes consumed before the error was detected, counted since the decoder was created or reset.
+    /// This is the offset of the first byte that was not consumed.
+    /// An error in the compressed data of a block may be detected in input that was read ahead of this offset.
+    pub compressed_offset: u64,
+    /// Uncompressed bytes produced before the error, counted since the decoder was created or reset.
+    pub uncompressed_offset: u64,
+    /// Index of the stream that failed.
+    pub stream_index: u64,
+    /// Index of the block that failed, or the amount of blocks if the stream failed outside a block.
+    pub block_index: u64,
+    /// Input consumed by the call to `decode` that failed.
+    pub input_consumed: usize,
+    /// Output produced by the call to `decode` that failed.
+    /// The data was decoded correctly, but the check of its block was not verified.
+    pub output_produced: usize,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 #[non_exhaustive]
 pub enum XzError {
@@ -1763,11 +2087,14 @@ pub enum XzError {
     Sha256NotSupported,
     UnsupportedLzmaProperties(u32),
     DictionaryTooLarge(u64),
+    MemoryLimitExceeded(u64), //Required
     UnsupportedCheckType(u32),
     #[cfg(feature = "bcj")]
-    BcjFilterWithOffsetNotSupported,
+    BcjFilterOffsetUnaligned(u32),
     #[cfg(feature = "bcj")]
     UnsupportedBcjFilter(u32),
+    #[cfg(feature = "delta")]
+    UnsupportedDeltaDistance(u64),
     #[cfg(not(feature = "delta"))]
     DeltaFilterUnsupported,
 
@@ -1788,9 +2115,96 @@ pub enum XzError {
     FooterCheckTypeMismatch(u32, XzCheckType), //Actual, Expected
     FooterCrc32Mismatch(u32, u32),             //Actual, Expected
     FooterDecoderIndexMismatch(u64, u64),      //Actual, Expected
+
+    InvalidStreamPadding,
+    UnexpectedEndOfInput,
+
+    LzipUnsupportedVersion(u8),
+    LzipInvalidDictionarySize(u8),
+    LzipCrc32Mismatch(u32, u32),      //Actual, Expected
+    LzipDataSizeMismatch(u64, u64),   //Actual, Expected
+    LzipMemberSizeMismatch(u64, u64), //Actual, Expected
+}
+
+impl XzError {
+    /// Returns true if the error is caused by corrupted or truncated data.
+    #[must_use]
+    pub const fn is_corruption(&self) -> bool {
+        match self {
+            Self::CorruptedData
+            | Self::CorruptedDataInLzma
+            | Self::DictionaryOverflow
+            | Self::LzmaPropertiesTooLarge
+            | Self::LzmaPropertiesInvalid
+            | Self::LzmaPropertiesMissing
+            | Self::LzmaDictionaryResetExcepted
+            | Self::MoreDataInBlockBodyThanHeaderIndicated
+            | Self::LessDataInBlockBodyThanHeaderIndicated
+            | Self::CorruptedDataInBlockIndex
+            | Self::BlockHeaderTooSmall
+            | Self::CorruptedCompressedLengthVliInBlockHeader
+            | Self::CorruptedUncompressedLengthVliInBlockHeader
+            | Self::ContentCrc32Mismatch(_, _)
+            | Self::IndexCrc32Mismatch(_, _)
+            | Self::StreamHeaderMagicNumberMismatch
+            | Self::StreamHeaderCrc32Mismatch(_, _)
+            | Self::BlockHeaderCrc32Mismatch(_, _)
+            | Self::FooterMagicNumberMismatch
+            | Self::FooterCheckTypeMismatch(_, _)
+            | Self::FooterCrc32Mismatch(_, _)
+            | Self::FooterDecoderIndexMismatch(_, _)
+            | Self::InvalidStreamPadding
+            | Self::UnexpectedEndOfInput
+            | Self::LzipInvalidDictionarySize(_)
+            | Self::LzipCrc32Mismatch(_, _)
+            | Self::LzipDataSizeMismatch(_, _)
+            | Self::LzipMemberSizeMismatch(_, _) => true,
+            #[cfg(feature = "crc64")]
+            Self::ContentCrc64Mismatch(_, _) => true,
+            #[cfg(feature = "sha256")]
+            Self::ContentSha256Mismatch(_, _) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns true if the data uses a feature that this crate does not support or that was disabled at compile time.
+    #[must_use]
+    pub const fn is_unsupported(&self) -> bool {
+        match self {
+            Self::UnsupportedStreamHeaderOption
+            | Self::UnsupportedBlockHeaderOption
+            | Self::UnsupportedLzmaProperties(_)
+            | Self::UnsupportedCheckType(_)
+            | Self::LzipUnsupportedVersion(_) => true,
+            #[cfg(not(feature = "bcj"))]
+            Self::BcjFilterNotSupported => true,
+            #[cfg(not(feature = "crc64"))]
+            Self::Crc64NotSupported => true,
+            #[cfg(not(feature = "sha256"))]
+            Self::Sha256NotSupported => true,
+            #[cfg(feature = "bcj")]
+            Self::BcjFilterOffsetUnaligned(_) | Self::UnsupportedBcjFilter(_) => true,
+            #[cfg(feature = "delta")]
+            Self::UnsupportedDeltaDistance(_) => true,
+            #[cfg(not(feature = "delta"))]
+            Self::DeltaFilterUnsupported => true,
+            _ => false,
+        }
+    }
+
+    /// Returns true if the data needs more memory than the dictionary or the memory limit allow.
+    /// Decoding may succeed with a larger dictionary or a higher memory limit.
+    #[must_use]
+    pub const fn is_resource_limit(&self) -> bool {
+        matches!(
+            self,
+            Self::DictionaryTooLarge(_) | Self::MemoryLimitExceeded(_)
+        )
+    }
 }
 
 impl Display for XzError {
+    #[allow(clippy::too_many_lines)]
     fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self {
             Self::NeedsReset => f.write_str("NeedsReset"),
@@ -1823,15 +2237,24 @@ impl Display for XzError {
             Self::DictionaryTooLarge(size) => {
                 f.write_fmt(format_args!("UnsupportedLzmaProperties(size={size} bytes)"))
             }
+            Self::MemoryLimitExceeded(required) => f.write_fmt(format_args!(
+                "MemoryLimitExceeded(required={required} bytes)"
+            )),
             Self::UnsupportedCheckType(typ) => {
-                f.write_fmt(format_args!("UnsupportedCheckType(type={typ})",))
+                f.write_fmt(format_args!("UnsupportedCheckType(type={typ})"))
             }
             #[cfg(feature = "bcj")]
-            Self::BcjFilterWithOffsetNotSupported => f.write_str("BcjFilterWithOffsetNotSupported"),
+            Self::BcjFilterOffsetUnaligned(offset) => {
+                f.write_fmt(format_args!("BcjFilterOffsetUnaligned(offset={offset})"))
+            }
             #[cfg(feature = "bcj")]
             Self::UnsupportedBcjFilter(flt) => {
-                f.write_fmt(format_args!("UnsupportedBcjFilter(type={flt})",))
+                f.write_fmt(format_args!("UnsupportedBcjFilter(type={flt})"))
             }
+            #[cfg(feature = "delta")]
+            Self::UnsupportedDeltaDistance(distance) => f.write_fmt(format_args!(
+                "UnsupportedDeltaDistance(distance={distance})"
+            )),
             #[cfg(not(feature = "delta"))]
             Self::DeltaFilterUnsupported => f.write_str("DeltaFilterUnsupported"),
             Self::ContentCrc32Mismatch(actual, expected) => f.write_fmt(format_args!(
@@ -1872,6 +2295,23 @@ impl Display for XzError {
                 f.write_str("CorruptedUncompressedLengthVliInBlockHeader")
             }
             Self::UnsupportedBlockHeaderOption => f.write_str("UnsupportedBlockHeaderOption"),
+            Self::InvalidStreamPadding => f.write_str("InvalidStreamPadding"),
+            Self::UnexpectedEndOfInput => f.write_str("UnexpectedEndOfInput"),
+            Self::LzipUnsupportedVersion(version) => {
+                f.write_fmt(format_args!("LzipUnsupportedVersion(version={version})"))
+            }
+            Self::LzipInvalidDictionarySize(coded) => {
+                f.write_fmt(format_args!("LzipInvalidDictionarySize(coded={coded})"))
+            }
+            Self::LzipCrc32Mismatch(actual, expected) => f.write_fmt(format_args!(
+                "LzipCrc32Mismatch(actual={actual}, expected={expected})"
+            )),
+            Self::LzipDataSizeMismatch(actual, expected) => f.write_fmt(format_args!(
+                "LzipDataSizeMismatch(actual={actual}, expected={expected})"
+            )),
+            Self::LzipMemberSizeMismatch(actual, expected) => f.write_fmt(format_args!(
+                "LzipMemberSizeMismatch(actual={actual}, expected={expected})"
+            )),
         }
     }
 }
@@ -1990,9 +2430,11 @@ impl<const T: usize> XzStaticDecoder<T> {
     /// is called after `XzNextBlockResult::EndOfStream` was returned.
     /// Failure to do so will lead to Err `XzError::NeedsReset` upon future calls.
     ///
-    /// This implementation will NOT parse the padding 0 bytes
+    /// By default, this implementation will NOT parse the padding 0 bytes
     /// mentioned in the XZ documentation that occur between concatenated streams of two xz files.
     /// The caller will have to skip all 0 bytes between such streams.
+    /// In multi stream mode (see `set_multi_stream`) the decoder skips the padding itself
+    /// and continues with the next stream after returning `XzNextBlockResult::EndOfStream`.
     ///
     /// # Errors
     /// Most errors returned by this fn are fatal and the decoder must be reset afterward.
@@ -2001,11 +2443,29 @@ impl<const T: usize> XzStaticDecoder<T> {
     /// - `XzError::NeedsLargerInputBuffer`
     ///     - Input buffer does not contain enough data to make progress
     ///
+    /// The input may be empty to get the output that can be decoded from the input given so far,
+    /// for example when the previous call filled the output buffer.
     pub fn decode(
         &mut self,
         input_data: &[u8],
         output_data: &mut [u8],
     ) -> Result<XzNextBlockResult, XzError> {
+        self.with_dict_buffer(|inner, d| inner.decode(input_data, output_data, d))
+    }
+
+    /// Decodes the input like `decode` but discards the decoded data, see `XzDecoder::verify`.
+    ///
+    /// # Errors
+    /// Same as `decode`.
+    pub fn verify(&mut self, input_data: &[u8]) -> Result<XzNextBlockResult, XzError> {
+        self.with_dict_buffer(|inner, d| inner.verify(input_data, d))
+    }
+
+    /// Runs the fn with the inner decoder and a dictionary buffer that borrows the fixed size dictionary.
+    fn with_dict_buffer<R>(
+        &mut self,
+        run: impl FnOnce(&mut XzInnerDecoder, &mut XzDictBuffer) -> R,
+    ) -> R {
         let mut dict_buf = self.dict_buf.as_mut_slice();
         if T > DICT_SIZE_MAX {
             dict_buf = &mut dict_buf[..DICT_SIZE_MAX];
@@ -2019,10 +2479,9 @@ impl<const T: usize> XzStaticDecoder<T> {
             dict_size: self.dict_size,
             dict_full: self.dict_full,
             dict_limit: self.dict_limit,
+            discarded_start: 0,
         };
-        let result = self
-            .inner
-            .decode(input_data, output_data, &mut dict_buf_borrow);
+        let result = run(&mut self.inner, &mut dict_buf_borrow);
         self.dict_pos = dict_buf_borrow.dict_pos;
         self.dict_size = dict_buf_borrow.dict_size;
         self.dict_start = dict_buf_borrow.dict_start;
@@ -2040,6 +2499,112 @@ impl<const T: usize> XzStaticDecoder<T> {
         self.dict_full = 0;
         self.dict_limit = 0;
     }
+
+    /// Returns a snapshot of the progress of the decoder.
+    #[must_use]
+    pub const fn stats(&self) -> XzDecoderStats {
+        self.inner.stats(self.dict_size)
+    }
+
+    /// Enables or disables stopping at the end of each block.
+    ///
+    /// If enabled, `decode` returns as soon as the check of a block was verified, even if more input is available.
+    /// `take_block_end` then returns the information about that block.
+    /// All output produced up to and including that call of `decode` belongs to verified blocks.
+    pub const fn set_stop_at_block_end(&mut self, stop_at_block_end: bool) {
+        self.inner.stop_at_block_end = stop_at_block_end;
+    }
+
+    /// Returns the information about the last block that ended, once.
+    ///
+    /// Without `set_stop_at_block_end` a call to `decode` may decode several blocks and only the last one is returned.
+    pub const fn take_block_end(&mut self) -> Option<XzBlockEnd> {
+        self.inner.block_end.take()
+    }
+
+    /// Enables or disables recovery mode.
+    ///
+    /// In recovery mode a block with corrupted data or a failed check does not fail decoding.
+    /// `decode` returns after the error instead and `take_damaged_block` reports the block.
+    /// The decoder then skips the rest of the block if its end is known from the compressed size in its block header,
+    /// or searches for the next valid block header or stream footer, and continues with the following blocks.
+    /// The data of a damaged block that was produced before the error was detected is not verified and may be wrong.
+    /// The index of a stream with damaged blocks is only checked for corruption, not compared with the blocks.
+    pub const fn set_recovery(&mut self, recovery: bool) {
+        self.inner.recovery = recovery;
+    }
+
+    /// Returns the last block that was damaged in recovery mode, once.
+    pub const fn take_damaged_block(&mut self) -> Option<XzDamagedBlock> {
+        self.inner.damaged_block.take()
+    }
+
+    /// Returns the position of the decoder when `decode` last failed.
+    /// This is kept until the decoder is reset, so it can be used after `decode` returned the error.
+    #[must_use]
+    pub const fn error_context(&self) -> Option<XzErrorContext> {
+        self.inner.error_context
+    }
+
+    /// Enables or disables stopping after each block header.
+    ///
+    /// If enabled, `decode` returns as soon as a block header was decoded, before any data of the block is decoded.
+    /// `block_info` then returns the information from that block header.
+    pub const fn set_stop_at_block_header(&mut self, stop_at_block_header: bool) {
+        self.inner.stop_at_block_header = stop_at_block_header;
+    }
+
+    /// Returns the information from the header of the current block,
+    /// or of the last block until the next block header was decoded.
+    /// Returns None if no block header was decoded since the decoder was created or reset.
+    #[must_use]
+    pub const fn block_info(&self) -> Option<&XzBlockInfo> {
+        if self.inner.block_info.filters().is_empty() {
+            return None;
+        }
+        Some(&self.inner.block_info)
+    }
+
+    /// Enables or disables multi stream mode.
+    ///
+    /// In multi stream mode the decoder does not stop after the first stream.
+    /// It returns `XzNextBlockResult::EndOfStream` after each stream, consumes the
+    /// stream padding that may follow and then continues with the next concatenated stream
+    /// when `decode` is called again. No call to `reset` is needed in between.
+    /// Since the decoder cannot know when the input ends, the caller should call `finish`
+    /// once all input was passed to `decode`.
+    pub const fn set_multi_stream(&mut self, multi_stream: bool) {
+        self.inner.multi_stream = multi_stream;
+    }
+
+    /// Returns true if multi stream mode is enabled.
+    #[must_use]
+    pub const fn is_multi_stream(&self) -> bool {
+        self.inner.multi_stream
+    }
+
+    /// Enables or disables ignoring the checks of blocks.
+    ///
+    /// If enabled, the check of each block is skipped without computing and comparing it,
+    /// so corrupted data may be returned without an error. Only use this for data that was verified otherwise.
+    /// Checks of the headers and the index are still verified. `stats` and `take_block_end` report this option.
+    /// It should only be changed before a block starts.
+    pub const fn set_ignore_check(&mut self, ignore_check: bool) {
+        self.inner.ignore_check = ignore_check;
+    }
+
+    /// Signals that there is no more input and checks that the input ended at a valid position.
+    ///
+    /// This is the case after `XzNextBlockResult::EndOfStream` was returned
+    /// and any stream padding that followed is a multiple of 4 bytes in size.
+    ///
+    /// # Errors
+    /// - `XzError::InvalidStreamPadding` if the stream padding is not a multiple of 4 bytes.
+    /// - `XzError::UnexpectedEndOfInput` if the input ended in the middle of a stream.
+    /// - `XzError::NeedsReset` if the decoder previously failed.
+    pub const fn finish(&self) -> Result<(), XzError> {
+        self.inner.finish()
+    }
 }
 
 #[derive(Debug)]
@@ -2170,9 +2735,11 @@ impl<'a> XzDecoder<'a> {
     /// is called after `XzNextBlockResult::EndOfStream` was returned.
     /// Failure to do so will lead to Err `XzError::NeedsReset` upon future calls.
     ///
-    /// This implementation will NOT parse the padding 0 bytes
+    /// By default, this implementation will NOT parse the padding 0 bytes
     /// mentioned in the XZ documentation that occur between concatenated streams of two xz files.
     /// The caller will have to skip all 0 bytes between such streams.
+    /// In multi stream mode (see `set_multi_stream`) the decoder skips the padding itself
+    /// and continues with the next stream after returning `XzNextBlockResult::EndOfStream`.
     ///
     /// # Errors
     /// Most errors returned by this fn are fatal, and the decoder must be reset afterward.
@@ -2181,6 +2748,8 @@ impl<'a> XzDecoder<'a> {
     /// - `XzError::NeedsLargerInputBuffer`
     ///     - Input buffer does not contain enough data to make progress
     ///
+    /// The input may be empty to get the output that can be decoded from the input given so far,
+    /// for example when the previous call filled the output buffer.
     pub fn decode(
         &mut self,
         input_data: &[u8],
@@ -2190,9 +2759,166 @@ impl<'a> XzDecoder<'a> {
             .decode(input_data, output_data, &mut self.dictionary_buffer)
     }
 
+    /// Decodes the input like `decode` but discards the decoded data, so no output buffer has to be provided.
+    /// All checks, the index and the stream footer are still verified.
+    ///
+    /// The decoded data is not copied out of the dictionary, except for blocks with bcj or delta filters
+    /// which are decoded in chunks of 4096 bytes into a buffer on the stack.
+    /// Returns once all input was consumed, the end of a stream was reached or the decoder stopped
+    /// because of `set_stop_at_block_end` or `set_stop_at_block_header`.
+    /// The output count of the result is the amount of decoded and discarded bytes.
+    ///
+    /// # Errors
+    /// Same as `decode`.
+    pub fn verify(&mut self, input_data: &[u8]) -> Result<XzNextBlockResult, XzError> {
+        self.inner.verify(input_data, &mut self.dictionary_buffer)
+    }
+
     /// Reset the decoder
     pub const fn reset(&mut self) {
         self.inner.reset();
+        self.dictionary_buffer.dict_size = 0;
+    }
+
+    /// Returns a snapshot of the progress of the decoder.
+    ///
+    /// The totals cover all streams since the decoder was created or reset.
+    #[must_use]
+    pub const fn stats(&self) -> XzDecoderStats {
+        self.inner.stats(self.dictionary_buffer.dict_size)
+    }
+
+    /// Enables or disables stopping at the end of each block.
+    ///
+    /// If enabled, `decode` returns as soon as the check of a block was verified, even if more input is available.
+    /// `take_block_end` then returns the information about that block.
+    /// All output produced up to and including that call of `decode` belongs to verified blocks.
+    pub const fn set_stop_at_block_end(&mut self, stop_at_block_end: bool) {
+        self.inner.stop_at_block_end = stop_at_block_end;
+    }
+
+    /// Returns the information about the last block that ended, once.
+    ///
+    /// Without `set_stop_at_block_end` a call to `decode` may decode several blocks and only the last one is returned.
+    pub const fn take_block_end(&mut self) -> Option<XzBlockEnd> {
+        self.inner.block_end.take()
+    }
+
+    /// Enables or disables recovery mode.
+    ///
+    /// In recovery mode a block with corrupted data or a failed check does not fail decoding.
+    /// `decode` returns after the error instead and `take_damaged_block` reports the block.
+    /// The decoder then skips the rest of the block if its end is known from the compressed size in its block header,
+    /// or searches for the next valid block header or stream footer, and continues with the following blocks.
+    /// The data of a damaged block that was produced before the error was detected is not verified and may be wrong.
+    /// The index of a stream with damaged blocks is only checked for corruption, not compared with the blocks.
+    pub const fn set_recovery(&mut self, recovery: bool) {
+        self.inner.recovery = recovery;
+    }
+
+    /// Returns the last block that was damaged in recovery mode, once.
+    pub const fn take_damaged_block(&mut self) -> Option<XzDamagedBlock> {
+        self.inner.damaged_block.take()
+    }
+
+    /// Returns the position of the decoder when `decode` last failed.
+    /// This is kept until the decoder is reset, so it can be used after `decode` returned the error.
+    #[must_use]
+    pub const fn error_context(&self) -> Option<XzErrorContext> {
+        self.inner.error_context
+    }
+
+    /// Enables or disables stopping after each block header.
+    ///
+    /// If enabled, `decode` returns as soon as a block header was decoded, before any data of the block is decoded.
+    /// `block_info` then returns the information from that block header.
+    pub const fn set_stop_at_block_header(&mut self, stop_at_block_header: bool) {
+        self.inner.stop_at_block_header = stop_at_block_header;
+    }
+
+    /// Returns the information from the header of the current block,
+    /// or of the last block until the next block header was decoded.
+    /// Returns None if no block header was decoded since the decoder was created or reset.
+    #[must_use]
+    pub const fn block_info(&self) -> Option<&XzBlockInfo> {
+        if self.inner.block_info.filters().is_empty() {
+            return None;
+        }
+        Some(&self.inner.block_info)
+    }
+
+    /// Enables or disables multi stream mode.
+    ///
+    /// In multi stream mode the decoder does not stop after the first stream.
+    /// It returns `XzNextBlockResult::EndOfStream` after each stream, consumes the
+    /// stream padding that may follow and then continues with the next concatenated stream
+    /// when `decode` is called again. No call to `reset` is needed in between.
+    /// Since the decoder cannot know when the input ends, the caller should call `finish`
+    /// once all input was passed to `decode`.
+    pub const fn set_multi_stream(&mut self, multi_stream: bool) {
+        self.inner.multi_stream = multi_stream;
+    }
+
+    /// Returns true if multi stream mode is enabled.
+    #[must_use]
+    pub const fn is_multi_stream(&self) -> bool {
+        self.inner.multi_stream
+    }
+
+    /// Enables or disables ignoring the checks of blocks.
+    ///
+    /// If enabled, the check of each block is skipped without computing and comparing it,
+    /// so corrupted data may be returned without an error. Only use this for data that was verified otherwise.
+    /// Checks of the headers and the index are still verified. `stats` and `take_block_end` report this option.
+    /// It should only be changed before a block starts.
+    pub const fn set_ignore_check(&mut self, ignore_check: bool) {
+        self.inner.ignore_check = ignore_check;
+    }
+
+    /// Sets the memory limit in bytes, it applies to every following block header. 0 is treated as 1.
+    ///
+    /// The memory usage of a block is the size of the decoder plus the size of its dictionary,
+    /// see `memory_usage`. If a block needs more memory then `decode` fails with `XzError::MemoryLimitExceeded`
+    /// before the dictionary is allocated. That error is not fatal, the caller can raise the limit and
+    /// call `decode` again with the same input.
+    /// By default, there is no limit other than the `max_dict` given when the decoder was created.
+    pub const fn set_memlimit(&mut self, memlimit: u64) {
+        self.inner.memlimit = NonZeroU64::new(if memlimit == 0 { 1 } else { memlimit });
+    }
+
+    /// Returns the memory limit in bytes, `u64::MAX` if there is no limit.
+    #[must_use]
+    pub const fn memlimit(&self) -> u64 {
+        match self.inner.memlimit {
+            Some(memlimit) => memlimit.get(),
+            None => u64::MAX,
+        }
+    }
+
+    /// Returns the amount of memory in bytes that a `XzDecoder` needs to decode the block with the given headers.
+    ///
+    /// `stream_header` must contain the 12 byte stream header and `block_header` the complete block header
+    /// including its size byte. Any data after the headers is ignored.
+    ///
+    /// # Errors
+    /// - `XzError::UnexpectedEndOfInput` if a header is incomplete.
+    /// - the errors of `decode` if a header is invalid or not supported.
+    pub fn memory_usage(stream_header: &[u8], block_header: &[u8]) -> Result<u64, XzError> {
+        parse_stream_header(stream_header)?;
+        memory_usage_of_block_header(block_header)
+    }
+
+    /// Signals that there is no more input and checks that the input ended at a valid position.
+    ///
+    /// This is the case after `XzNextBlockResult::EndOfStream` was returned
+    /// and any stream padding that followed is a multiple of 4 bytes in size.
+    ///
+    /// # Errors
+    /// - `XzError::InvalidStreamPadding` if the stream padding is not a multiple of 4 bytes.
+    /// - `XzError::UnexpectedEndOfInput` if the input ended in the middle of a stream.
+    /// - `XzError::NeedsReset` if the decoder previously failed.
+    pub const fn finish(&self) -> Result<(), XzError> {
+        self.inner.finish()
     }
 }
 
@@ -2219,13 +2945,12 @@ enum Filter {
 
 /// Contains the entire state of the decoder except for the dictionary buffer.
 #[derive(Debug)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct XzInnerDecoder {
     /// state machine state
     state: XzDecoderState,
     /// check algorithm to use
     check_type: XzCheckType,
-    /// VLI decoder
-    vli_decoder: VliDecoder,
     /// Crc32 and Crc64 state
     crc: u64,
     /// flag if we did not have enough data during the last call.
@@ -2236,12 +2961,52 @@ pub struct XzInnerDecoder {
     last_output_buffer_size: usize,
     /// Did we error and want to be reset?
     needs_reset: bool,
+    /// Continue with the next stream after a stream ended?
+    multi_stream: bool,
+    /// Memory limit for the next block, None means no limit.
+    memlimit: Option<NonZeroU64>,
+    /// Total amount of input bytes consumed.
+    total_in: u64,
+    /// Total amount of output bytes produced.
+    total_out: u64,
+    /// Index of the current stream.
+    stream_index: u64,
+    /// Skip computing and comparing the checks of blocks?
+    ignore_check: bool,
+    /// Return from `decode` after each block check?
+    stop_at_block_end: bool,
+    /// Return from `decode` after each block header?
+    stop_at_block_header: bool,
+    /// Did the last call to `decode` return because of `stop_at_block_end` or `stop_at_block_header`?
+    stopped: bool,
+    /// The current or last block header.
+    block_info: XzBlockInfo,
+    /// The last block that ended, if it was not taken yet.
+    block_end: Option<XzBlockEnd>,
+    /// Position of the last error.
+    error_context: Option<XzErrorContext>,
+    /// Skip damaged blocks instead of failing?
+    recovery: bool,
+    /// The last damaged block, if it was not taken yet.
+    damaged_block: Option<XzDamagedBlock>,
+    /// Amount of damaged blocks since the decoder was created or reset.
+    damaged_blocks: u64,
+    /// Was a block of the current stream damaged? The index cannot be compared with the blocks then.
+    stream_damaged: bool,
+    /// Offset of the current block header in the compressed data.
+    block_start: u64,
+    /// Offset of the data of the current block in the uncompressed data.
+    block_out_start: u64,
+    /// Compressed bytes of a damaged block that still need to be skipped.
+    resync_skip: u64,
+    /// Amount of stream padding bytes read modulo 4.
+    stream_padding: u8,
     /// current block header info
     block_header: XzBlockHeader,
     /// block decoding info
     block: XzDecBlock,
     /// index decoder
-    index: XzDecoderIndex,
+    index: XzIndexDecoder,
     /// temp buffer
     temp: XzTempBuffer,
     /// lzma decoder state
@@ -2282,16 +3047,35 @@ impl XzInnerDecoder {
     pub const fn new() -> Self {
         Self {
             state: XzDecoderState::StreamHeader,
-            vli_decoder: VliDecoder::new(),
             crc: 0,
             check_type: XzCheckType::None,
             had_not_enough_data: false,
             last_input_buffer_size: 0,
             last_output_buffer_size: 0,
             needs_reset: false,
+            multi_stream: false,
+            memlimit: None,
+            total_in: 0,
+            total_out: 0,
+            stream_index: 0,
+            ignore_check: false,
+            stop_at_block_end: false,
+            stop_at_block_header: false,
+            stopped: false,
+            block_info: XzBlockInfo::empty(),
+            block_end: None,
+            error_context: None,
+            recovery: false,
+            damaged_block: None,
+            damaged_blocks: 0,
+            stream_damaged: false,
+            block_start: 0,
+            block_out_start: 0,
+            resync_skip: 0,
+            stream_padding: 0,
             block_header: XzBlockHeader::new(),
             block: XzDecBlock::new(),
-            index: XzDecoderIndex::new(),
+            index: XzIndexDecoder::new(),
             temp: XzTempBuffer::new(),
             lzma2: XzLzma2Decoder::new(),
             #[cfg(feature = "sha256")]
@@ -2312,245 +3096,69 @@ impl XzInnerDecoder {
         }
     }
 
-    /// Updates the size and crc32 of the index.
-    fn index_update(&mut self, b: &mut XzInOutBuffer, in_start: usize) {
-        let position = b.input_position();
-        b.input_seek_set(in_start);
-        let in_used = position.sub(in_start);
-
-        self.index.size = self.index.size.wrapping_add(in_used as u64);
-        let x = &b.input_slice()[..in_used];
-        self.crc = u64::from(crc32(clamp_u64_to_u32(self.crc), x));
-
-        b.input_seek_set(position);
-    }
-
-    /// Decodes the block index.
-    fn dec_index(
-        &mut self,
-        b: &mut XzInOutBuffer,
-        in_start: usize,
-    ) -> Result<DecodeResult, XzError> {
-        loop {
-            let vli = match self.vli_decoder.decode(b.input_slice()) {
-                VliResult::Ok(vli, length) => {
-                    b.input_seek_add(length);
-                    vli
-                }
-                VliResult::MoreDataNeeded(length) => {
-                    b.input_seek_add(length);
-                    self.index_update(b, in_start);
-                    return Ok(DecodeResult::NeedMoreData);
-                }
-                VliResult::InvalidVli => {
-                    return Err(XzError::CorruptedDataInBlockIndex);
-                }
-            };
-            match self.index.sequence {
-                XzDecoderIndexSequence::Count => {
-                    self.index.count = vli;
-                    if self.index.count != self.block.count {
-                        return Err(XzError::CorruptedDataInBlockIndex);
-                    }
-                    self.index.sequence = XzDecoderIndexSequence::Unpadded;
-                }
-                XzDecoderIndexSequence::Unpadded => {
-                    self.index.hash.unpadded = self.index.hash.unpadded.wrapping_add(vli);
-                    self.index.sequence = XzDecoderIndexSequence::Uncompressed;
-                }
-                XzDecoderIndexSequence::Uncompressed => {
-                    self.index.hash.uncompressed = self.index.hash.uncompressed.wrapping_add(vli);
-                    self.index.hash.calculate_crc32();
-                    self.index.count = self.index.count.wrapping_sub(1);
-                    self.index.sequence = XzDecoderIndexSequence::Unpadded;
-                }
-            }
-            if self.index.count == 0 {
-                break;
-            }
-        }
-        Ok(DecodeResult::EndOfDataStructure)
-    }
-
-    /// decodes the stream footer and verifies that the magic number of the footer matches and the crc32 of the footer is equal to the one indicated in the header.
+    /// Decodes the stream footer and verifies that it matches the index and the stream header.
     fn dec_stream_footer(&self) -> Result<(), XzError> {
-        const MAGIC_NUMBER: &[u8] = b"YZ";
-        let buf = self.temp.buf();
-        if &buf[10..10 + MAGIC_NUMBER.len()] != MAGIC_NUMBER {
-            return Err(XzError::FooterMagicNumberMismatch);
-        }
-
-        let expected_crc = u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]);
-        let actual_crc = crc32(0, &buf[4..10]);
-        if actual_crc != expected_crc {
-            return Err(XzError::FooterCrc32Mismatch(actual_crc, expected_crc));
-        }
-
-        let actual_index = u64::from(u32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]));
-
-        if self.index.size >> 2i32 != actual_index {
+        let (index_size, flags) = parse_stream_footer(self.temp.buf())?;
+        if index_size != self.index.size() {
             return Err(XzError::FooterDecoderIndexMismatch(
-                actual_index,
-                self.index.size >> 2,
+                index_size / 4 - 1,
+                self.index.size() / 4 - 1,
             ));
         }
-        if self.temp.buf[8] != 0 || self.check_type != self.temp.buf[9] {
-            let actual = u32::from(u16::from_le_bytes([self.temp.buf[8], self.temp.buf[9]]));
+        if flags[0] != 0 || self.check_type != flags[1] {
+            let actual = u32::from(u16::from_le_bytes(flags));
             return Err(XzError::FooterCheckTypeMismatch(actual, self.check_type));
         }
         Ok(())
     }
 
-    /// decodes a block header from the stream.
-    #[allow(clippy::too_many_lines)] //Todo re-implement this function with some sort of borrowed cursor and split it into sections that make sense.
-    fn dec_block_header(&mut self, d: &mut XzDictBuffer) -> Result<(), XzError> {
-        //the temp buffer size is determined by the block header size, which should be at least 8 even with a malicious input file.
-        debug_assert!(self.temp.size >= 8);
-
-        let expected_crc = u32::from_le_bytes(self.temp.remove_trailing_4bytes());
-        let actual_crc = crc32(0, self.temp.buf());
-        if actual_crc != expected_crc {
-            return Err(XzError::BlockHeaderCrc32Mismatch(actual_crc, expected_crc));
-        }
-
-        debug_assert_eq!(self.temp.pos, 0);
-        let buf = self.temp.buf();
-
-        let mut pos = 2usize;
-        if buf[1] & 0x3C != 0 {
-            //TODO unreached.
-            return Err(XzError::UnsupportedBlockHeaderOption);
-        }
-        if buf[1] & 0x40 != 0 {
-            let Some((vli, len)) = self.vli_decoder.decode_single(&buf[pos..]) else {
-                // TODO unreached
-                return Err(XzError::CorruptedCompressedLengthVliInBlockHeader);
-            };
-
-            pos += len;
-            self.block_header.compressed = vli;
-        } else {
-            self.block_header.compressed = u64::MAX;
-        }
-
-        if buf[1] & 0x80 != 0 {
-            let Some((vli, len)) = self.vli_decoder.decode_single(&buf[pos..]) else {
-                return Err(XzError::CorruptedUncompressedLengthVliInBlockHeader);
-            };
-
-            pos += len;
-            self.block_header.uncompressed = vli;
-        } else {
-            self.block_header.uncompressed = u64::MAX;
-        }
-
-        let filter_count = (buf[1] & 0x03) as usize;
-        for i in 0..filter_count {
-            let bcj = buf[pos] != 3;
-            #[cfg(feature = "bcj")]
-            {
-                if bcj {
-                    self.filter_chain[i] = Filter::Bcj;
-                    if self.temp.size.wrapping_sub(pos) < 2 {
-                        //TODO unreached.
-                        return Err(XzError::BlockHeaderTooSmall);
-                    }
-                    let filter = buf[pos];
-                    pos += 1;
+    /// Sets up the filter chain and the lzma2 decoder for the block with the given block header.
+    #[cfg_attr(
+        not(any(feature = "bcj", feature = "delta")),
+        allow(unused_variables) //Without bcj and delta only lzma2 is left.
+    )]
+    fn dec_block_header(
+        &mut self,
+        info: &XzBlockInfo,
+        d: &mut XzDictBuffer,
+    ) -> Result<(), XzError> {
+        self.block_header.compressed = info.compressed_size().unwrap_or(u64::MAX);
+        self.block_header.uncompressed = info.uncompressed_size().unwrap_or(u64::MAX);
+
+        self.filter_chain = [Filter::Empty; 3];
+        for (i, filter) in info.filters().iter().enumerate() {
+            match *filter {
+                XzFilterInfo::Lzma2 { dict_size } => {
+                    self.lzma2.reset_dict_size(dict_size, d)?;
+                }
+                #[cfg(feature = "bcj")]
+                XzFilterInfo::Bcj {
+                    filter,
+                    start_offset,
+                } => {
                     let bcj_filter = match i {
                         0 => &mut self.bcj0,
                         1 => &mut self.bcj1,
                         2 => &mut self.bcj2,
                         _ => unreachable!(),
                     };
-                    bcj_filter.reset(filter)?;
-
-                    if buf[pos] != 0 {
-                        return Err(XzError::BcjFilterWithOffsetNotSupported);
-                    }
-                    pos += 1;
-                    continue;
-                }
-            }
-            #[cfg(not(feature = "bcj"))]
-            {
-                if bcj {
-                    return Err(XzError::BcjFilterNotSupported);
+                    bcj_filter.reset(filter, start_offset);
+                    self.filter_chain[i] = Filter::Bcj;
                 }
-            }
-            let delta = buf[pos] == 3;
-            #[cfg(feature = "delta")]
-            {
-                if delta {
-                    if self.temp.size.wrapping_sub(pos) < 2 {
-                        //TODO unreached
-                        return Err(XzError::BlockHeaderTooSmall);
-                    }
-                    pos += 1;
-                    //length of "distance" we only support 1 byte distance aka 1 to 256
-                    if buf[pos] != 1 {
-                        //TODO unreached
-                        return Err(XzError::UnsupportedBlockHeaderOption);
-                    }
-                    pos += 1;
-                    let distance = buf[pos]; //0 means distance of 1!
+                #[cfg(feature = "delta")]
+                XzFilterInfo::Delta { distance } => {
                     let delta_coder = match i {
                         0 => &mut self.delta0,
                         1 => &mut self.delta1,
                         2 => &mut self.delta2,
                         _ => unreachable!(),
                     };
-                    delta_coder.reset(
-                        NonZeroUsize::new((distance as usize) + 1)
-                            .ok_or(XzError::UnsupportedBlockHeaderOption)?,
-                    ); //ERR is unreachable!
+                    delta_coder.reset_with_distance(distance)?; //ERR is unreachable!
                     self.filter_chain[i] = Filter::Delta;
-                    pos += 1;
-                    continue;
-                }
-            }
-            #[cfg(not(feature = "delta"))]
-            {
-                if delta {
-                    return Err(XzError::DeltaFilterUnsupported);
                 }
             }
-
-            self.filter_chain[i] = Filter::Empty; //This should be unreachable!
-        }
-
-        for i in filter_count..self.filter_chain.len() {
-            self.filter_chain[i] = Filter::Empty;
-        }
-
-        if self.temp.size().saturating_sub(pos) < 2 {
-            return Err(XzError::BlockHeaderTooSmall);
-        }
-
-        if buf[pos] != 0x21 {
-            return Err(XzError::UnsupportedBlockHeaderOption);
-        }
-        pos += 1;
-
-        if self.temp.buf[pos] != 0x1 {
-            //TODO unreached
-            return Err(XzError::UnsupportedBlockHeaderOption);
         }
-        pos += 1;
 
-        if self.temp.size().saturating_sub(pos) < 1 {
-            return Err(XzError::BlockHeaderTooSmall);
-        }
-        self.lzma2.xz_dec_lzma2_reset(self.temp.buf[pos], d)?;
-        pos += 1;
-
-        while pos < self.temp.size() {
-            if buf[pos] != 0 {
-                return Err(XzError::UnsupportedBlockHeaderOption);
-            }
-            pos += 1;
-        }
-        self.temp.pos = 0; //why?
         self.block.compressed = 0;
         self.block.uncompressed = 0;
         Ok(())
@@ -2685,6 +3293,12 @@ impl XzInnerDecoder {
         let out_start = b.output_position();
 
         let ret = self.apply_filter(b, d)?;
+        let output = if b.is_discard() {
+            //A discarded output holds at most one flush, its data is still in the dictionary.
+            d.discarded(b.output_position() - out_start)
+        } else {
+            b.output_slice_look_back(out_start)
+        };
 
         //TODO probably doesnt wrap
         self.block.compressed = self
@@ -2704,18 +3318,16 @@ impl XzInnerDecoder {
         }
 
         match self.check_type {
+            _ if self.ignore_check => (),
             #[cfg(feature = "sha256")]
-            XzCheckType::Sha256 => self.sha256.update(b.output_slice_look_back(out_start)),
+            XzCheckType::Sha256 => self.sha256.update(output),
 
             #[cfg(feature = "crc64")]
             XzCheckType::Crc64 => {
-                self.crc = crate::crc64xz::crc64xz(self.crc, b.output_slice_look_back(out_start));
+                self.crc = crate::crc64xz::crc64xz(self.crc, output);
             }
             XzCheckType::Crc32 => {
-                self.crc = u64::from(crc32(
-                    clamp_u64_to_u32(self.crc),
-                    b.output_slice_look_back(out_start),
-                ));
+                self.crc = u64::from(crc32(clamp_u64_to_u32(self.crc), output));
             }
             XzCheckType::None => (),
         }
@@ -2735,17 +3347,10 @@ impl XzInnerDecoder {
             //TODO unreached
             return Err(XzError::LessDataInBlockBodyThanHeaderIndicated);
         }
-        self.block.hash.unpadded = self
-            .block
-            .hash
-            .unpadded
-            .wrapping_add((self.block_header.size as u64).wrapping_add(self.block.compressed));
-
-        self.block.hash.unpadded = self
-            .block
-            .hash
-            .unpadded
+        self.block.unpadded = (self.block_header.size as u64)
+            .wrapping_add(self.block.compressed)
             .wrapping_add(self.check_type.check_size() as u64);
+        self.block.hash.unpadded = self.block.hash.unpadded.wrapping_add(self.block.unpadded);
         self.block.hash.uncompressed = self
             .block
             .hash
@@ -2764,7 +3369,6 @@ impl XzInnerDecoder {
         b: &mut XzInOutBuffer,
         d: &mut XzDictBuffer,
     ) -> Result<DecodeResult, XzError> {
-        let mut in_start = b.input_position();
         loop {
             match self.state {
                 XzDecoderState::StreamHeader => {
@@ -2780,12 +3384,12 @@ impl XzInnerDecoder {
                     };
 
                     if m == 0 {
-                        in_start = b.input_pos;
-                        b.input_pos = b.input_pos.wrapping_add(1);
                         self.state = XzDecoderState::Index;
                         continue;
                     }
 
+                    self.block_start = self.total_in.wrapping_add(b.input_position() as u64);
+                    self.block_out_start = self.total_out.wrapping_add(b.output_position() as u64);
                     self.block_header.size = (m + 1) * 4;
                     self.temp.size = self.block_header.size;
                     self.temp.pos = 0;
@@ -2795,13 +3399,37 @@ impl XzInnerDecoder {
                     if !self.fill_temp(b) {
                         return Ok(DecodeResult::NeedMoreData);
                     }
-                    self.dec_block_header(d)?;
+                    self.state = XzDecoderState::BlockHeaderDecode;
+                }
+                XzDecoderState::BlockHeaderDecode => {
+                    let info = XzBlockInfo::parse(self.temp.buf())?;
+                    if let Some(memlimit) = self.memlimit {
+                        let required = memory_usage_of_dict(info.dict_size());
+                        if required > memlimit.get() {
+                            // Report the progress first, the error must not consume any input so the caller can resume.
+                            if b.input_position() != 0 || b.output_position() != 0 {
+                                return Ok(DecodeResult::NeedMoreData);
+                            }
+                            return Err(XzError::MemoryLimitExceeded(required));
+                        }
+                    }
+
+                    self.dec_block_header(&info, d)?;
+                    self.block_info = info;
 
                     #[cfg(feature = "sha256")]
                     if self.check_type == XzCheckType::Sha256 {
                         self.sha256.reset();
                     }
                     self.state = XzDecoderState::BlockUncompress;
+                    if self.stop_at_block_header {
+                        self.stopped = true;
+                        return Ok(DecodeResult::NeedMoreData);
+                    }
+                    if b.is_discard() && self.has_filters() {
+                        // The filters work in place on the output, see verify.
+                        return Ok(DecodeResult::NeedMoreData);
+                    }
                 }
                 XzDecoderState::BlockUncompress => match self.dec_block(b, d)? {
                     DecodeResult::EndOfDataStructure => {
@@ -2817,6 +3445,14 @@ impl XzInnerDecoder {
                 },
                 XzDecoderState::BlockCheck => {
                     match self.check_type {
+                        _ if self.ignore_check => {
+                            // The check is only skipped.
+                            self.temp.size = self.check_type.check_size();
+                            if !self.fill_temp(b) {
+                                return Ok(DecodeResult::NeedMoreData);
+                            }
+                            self.crc = 0;
+                        }
                         XzCheckType::Crc32 => {
                             self.temp.size = 4;
                             if !self.fill_temp(b) {
@@ -2871,69 +3507,212 @@ impl XzInnerDecoder {
                         XzCheckType::None => (),
                     }
                     self.state = XzDecoderState::StreamStart;
+
+                    self.block_end = Some(XzBlockEnd {
+                        stream_index: self.stream_index,
+                        block_index: self.block.count - 1,
+                        unpadded_size: self.block.unpadded,
+                        // block.compressed already includes the block padding.
+                        compressed_size: self.block.unpadded.wrapping_sub(
+                            (self.block_header.size + self.check_type.check_size()) as u64,
+                        ),
+                        uncompressed_size: self.block.uncompressed,
+                        check_type: self.check_type,
+                        check_result: match self.check_type {
+                            XzCheckType::None => XzCheckResult::None,
+                            _ if self.ignore_check => XzCheckResult::Ignored,
+                            _ => XzCheckResult::Verified,
+                        },
+                    });
+                    if self.stop_at_block_end {
+                        self.stopped = true;
+                        return Ok(DecodeResult::NeedMoreData);
+                    }
                 }
                 XzDecoderState::Index => {
-                    match self.dec_index(b, in_start)? {
-                        DecodeResult::EndOfDataStructure => (),
-                        other => return Ok(other),
+                    //The blocks of a damaged stream cannot be compared with its index.
+                    let expected_count = (!self.stream_damaged).then_some(self.block.count);
+                    let (consumed, done) =
+                        self.index
+                            .decode(b.input_slice(), expected_count, |_, _| ())?;
+                    b.input_seek_add(consumed);
+                    if !done {
+                        return Ok(DecodeResult::NeedMoreData);
                     }
 
-                    self.state = XzDecoderState::IndexPadding;
+                    if self.block.hash != *self.index.hash() && !self.stream_damaged {
+                        return Err(XzError::CorruptedData);
+                    }
+                    self.temp.size = 12;
+                    self.state = XzDecoderState::StreamFooter;
+                }
+                XzDecoderState::StreamFooter => {
+                    if !self.fill_temp(b) {
+                        return Ok(DecodeResult::NeedMoreData);
+                    }
+                    self.dec_stream_footer()?;
+                    self.state = XzDecoderState::StreamPadding;
+                    self.stream_padding = 0;
+                    return Ok(DecodeResult::EndOfDataStructure);
                 }
-                XzDecoderState::IndexPadding => {
-                    while self
-                        .index
-                        .size
-                        .wrapping_add((b.input_pos - in_start) as u64)
-                        & 3
-                        != 0
-                    {
-                        let Some(next_byte) = b.input_read_byte::<u8>() else {
-                            self.index_update(b, in_start);
+                XzDecoderState::StreamPadding => {
+                    loop {
+                        let Some(next_byte) = b.input_peek_byte::<u8>() else {
                             return Ok(DecodeResult::NeedMoreData);
                         };
                         if next_byte != 0 {
-                            return Err(XzError::CorruptedData);
+                            break;
                         }
+                        b.input_seek_add(1);
+                        self.stream_padding = (self.stream_padding + 1) & 3;
                     }
-                    self.index_update(b, in_start);
-                    if self.block.hash != self.index.hash {
-                        return Err(XzError::CorruptedData);
+
+                    if self.stream_padding != 0 {
+                        return Err(XzError::InvalidStreamPadding);
                     }
-                    self.state = XzDecoderState::IndexCrc32;
+
+                    self.stream_index += 1;
+                    self.reset_for_next_stream();
                 }
-                XzDecoderState::IndexCrc32 => {
-                    self.temp.size = 4;
-                    if !self.fill_temp(b) {
+                XzDecoderState::BlockSkip => {
+                    let skip = usize::try_from(self.resync_skip)
+                        .unwrap_or(usize::MAX)
+                        .min(b.input_remaining());
+                    b.input_seek_add(skip);
+                    self.resync_skip -= skip as u64;
+                    if self.resync_skip != 0 {
                         return Ok(DecodeResult::NeedMoreData);
                     }
-
-                    let expected_crc = u32::from_le_bytes([
-                        self.temp.buf[0],
-                        self.temp.buf[1],
-                        self.temp.buf[2],
-                        self.temp.buf[3],
-                    ]);
-                    let actual_crc = clamp_u64_to_u32(self.crc);
-                    if expected_crc != actual_crc {
-                        return Err(XzError::IndexCrc32Mismatch(actual_crc, expected_crc));
-                    }
-                    self.crc = 0;
-
-                    self.temp.size = 12;
-                    self.state = XzDecoderState::StreamFooter;
+                    self.state = XzDecoderState::StreamStart;
                 }
-                XzDecoderState::StreamFooter => {
-                    if !self.fill_temp(b) {
+                XzDecoderState::Resync => {
+                    if self.resync(b) {
+                        self.state = XzDecoderState::StreamPadding;
+                        self.stream_padding = 0;
+                        return Ok(DecodeResult::EndOfDataStructure);
+                    }
+                    if matches!(self.state, XzDecoderState::Resync) {
                         return Ok(DecodeResult::NeedMoreData);
                     }
-                    self.dec_stream_footer()?;
-                    return Ok(DecodeResult::EndOfDataStructure);
                 }
             }
         }
     }
 
+    /// Handles an error in recovery mode by skipping the damaged block.
+    /// Returns false if the error cannot be recovered from.
+    fn recover(&mut self, err: &XzError, b: &XzInOutBuffer) -> bool {
+        if !self.recovery || !err.is_corruption() {
+            return false;
+        }
+
+        // The compressed size is known once the compressed data of the block ended.
+        let (counted, compressed) = match self.state {
+            XzDecoderState::BlockHeaderDecode => (false, None),
+            XzDecoderState::BlockUncompress => (
+                false,
+                Some(self.block_header.compressed).filter(|&size| size != u64::MAX),
+            ),
+            XzDecoderState::BlockPadding | XzDecoderState::BlockCheck => {
+                (true, Some(self.block.compressed))
+            }
+            _ => return false,
+        };
+
+        let position = self.total_in.wrapping_add(b.input_position() as u64);
+        let out_position = self.total_out.wrapping_add(b.output_position() as u64);
+        self.damaged_block = Some(XzDamagedBlock {
+            stream_index: self.stream_index,
+            block_index: if counted {
+                self.block.count.saturating_sub(1)
+            } else {
+                self.block.count
+            },
+            compressed_offset: self.block_start,
+            uncompressed_offset: self.block_out_start,
+            uncompressed_produced: out_position.wrapping_sub(self.block_out_start),
+            error: err.clone(),
+        });
+        if !counted {
+            self.block.count += 1;
+        }
+        self.damaged_blocks += 1;
+        self.stream_damaged = true;
+        self.stopped = true;
+        self.crc = 0;
+
+        let end = compressed.map(|compressed| {
+            self.block_start
+                .wrapping_add(self.block_header.size as u64)
+                .wrapping_add(compressed.next_multiple_of(4))
+                .wrapping_add(self.check_type.check_size() as u64)
+        });
+        match end {
+            Some(end) if end >= position => {
+                self.resync_skip = end - position;
+                self.state = XzDecoderState::BlockSkip;
+            }
+            _ => {
+                // Block headers are aligned to 4 bytes, the window starts at the last aligned position.
+                let misaligned = (position.wrapping_sub(self.block_start) % 4) as usize;
+                self.temp.buf[..misaligned].fill(0);
+                self.temp.pos = misaligned;
+                self.state = XzDecoderState::Resync;
+            }
+        }
+        true
+    }
+
+    /// Reads input into the window of the temp buffer until it ends with a valid block header or stream footer.
+    /// Switches to the block header state if a block header was found, returns true if a stream footer was found.
+    fn resync(&mut self, b: &mut XzInOutBuffer) -> bool {
+        while let Some(byte) = b.input_read_byte::<u8>() {
+            if self.temp.pos == self.temp.buf.len() {
+                // Block headers larger than half of the window may be missed.
+                self.temp.buf.copy_within(RESYNC_WINDOW_DROP.., 0);
+                self.temp.pos -= RESYNC_WINDOW_DROP;
+            }
+            self.temp.buf[self.temp.pos] = byte;
+            self.temp.pos += 1;
+            if self.temp.pos % 4 != 0 {
+                continue;
+            }
+
+            let window = &self.temp.buf[..self.temp.pos];
+            if let [.., crc0, crc1, crc2, crc3, _, _, _, _, 0, check_type, b'Y', b'Z'] = *window {
+                let footer = &window[window.len() - 12..];
+                if self.check_type == check_type
+                    && crc32(0, &footer[4..10]) == u32::from_le_bytes([crc0, crc1, crc2, crc3])
+                {
+                    self.temp.pos = 0;
+                    return true;
+                }
+            }
+
+            let header = (0..window.len()).step_by(4).find(|&start| {
+                window[start] != 0
+                    && (usize::from(window[start]) + 1) * 4 == window.len() - start
+                    && XzBlockInfo::parse(&window[start..]).is_ok()
+            });
+            if let Some(start) = header {
+                let size = window.len() - start;
+                self.temp.buf.copy_within(start..start + size, 0);
+                self.temp.pos = 0;
+                self.temp.size = size;
+                self.block_header.size = size;
+                // The header may have started in an earlier call to decode.
+                self.block_start = self
+                    .total_in
+                    .wrapping_add(b.input_position() as u64)
+                    .wrapping_sub(size as u64);
+                self.block_out_start = self.total_out.wrapping_add(b.output_position() as u64);
+                self.state = XzDecoderState::BlockHeaderDecode;
+                return false;
+            }
+        }
+        false
+    }
+
     /// Determine if a more output or input buffer result should trigger error or not.
     fn should_buffer_error(&mut self, buf: &XzInOutBuffer) -> bool {
         if buf.input_position() != 0 || buf.output_position() != 0 {
@@ -2959,25 +3738,37 @@ impl XzInnerDecoder {
     }
 
     /// Begins decoding, high level function that's called externally.
-    /// Mainly takes care of error handling.
     fn decode(
         &mut self,
         input_data: &[u8],
         output_data: &mut [u8],
         d: &mut XzDictBuffer,
+    ) -> Result<XzNextBlockResult, XzError> {
+        self.decode_buffer(XzInOutBuffer::new(input_data, output_data), d)
+    }
+
+    /// Decodes from and into the buffer, mainly takes care of error handling.
+    fn decode_buffer(
+        &mut self,
+        mut buf: XzInOutBuffer,
+        d: &mut XzDictBuffer,
     ) -> Result<XzNextBlockResult, XzError> {
         if self.needs_reset {
             return Err(XzError::NeedsReset);
         }
-        if input_data.is_empty() {
-            return Err(XzError::NeedsLargerInputBuffer);
+        self.stopped = false;
+        let mut result = self.dec_main(&mut buf, d);
+        if let Err(err) = &result {
+            if self.recover(err, &buf) {
+                result = Ok(DecodeResult::NeedMoreData);
+            }
         }
-
-        let mut buf = XzInOutBuffer::new(input_data, output_data);
-        match self
-            .dec_main(&mut buf, d)
-            .inspect_err(|_| self.needs_reset = true)?
-        {
+        self.total_in = self.total_in.wrapping_add(buf.input_position() as u64);
+        self.total_out = self.total_out.wrapping_add(buf.output_position() as u64);
+        match result.inspect_err(|err| {
+            self.needs_reset = !matches!(err, XzError::MemoryLimitExceeded(_));
+            self.error_context = Some(self.error_context(&buf));
+        })? {
             DecodeResult::NeedMoreData => {
                 if self.should_buffer_error(&buf) {
                     return Err(XzError::NeedsLargerInputBuffer);
@@ -2989,7 +3780,10 @@ impl XzInnerDecoder {
                 ))
             }
             DecodeResult::EndOfDataStructure => {
-                self.needs_reset = true;
+                if !self.multi_stream {
+                    self.needs_reset = true;
+                }
+
                 Ok(XzNextBlockResult::EndOfStream(
                     buf.input_position(),
                     buf.output_position(),
@@ -2998,41 +3792,137 @@ impl XzInnerDecoder {
         }
     }
 
-    /// decodes the stream header and calculates/validates its crc32.
-    fn dec_stream_header(&mut self) -> Result<(), XzError> {
-        const MAGIC_NUMBER: &[u8] = b"\xFD7zXZ\0";
-        let buf = self.temp.buf();
-        if &buf[0..MAGIC_NUMBER.len()] != MAGIC_NUMBER {
-            return Err(XzError::StreamHeaderMagicNumberMismatch);
-        }
+    /// Returns true if the current block has a bcj or delta filter.
+    fn has_filters(&self) -> bool {
+        self.filter_chain[0] != Filter::Empty
+    }
+
+    /// Returns true while the data of a block with a bcj or delta filter is decoded.
+    fn in_filtered_block(&self) -> bool {
+        matches!(self.state, XzDecoderState::BlockUncompress) && self.has_filters()
+    }
+
+    /// Decodes the input without writing the output until the input is consumed or the decoder stops.
+    /// Only the output of blocks with filters is written to a buffer on the stack.
+    fn verify(
+        &mut self,
+        input_data: &[u8],
+        d: &mut XzDictBuffer,
+    ) -> Result<XzNextBlockResult, XzError> {
+        let mut filter_buffer = [0u8; VERIFY_BUFFER_SIZE];
+        let mut consumed = 0;
+        let mut produced = 0;
+        loop {
+            let filtered = self.in_filtered_block();
+            let input = &input_data[consumed..];
+            let result = if filtered {
+                self.decode(input, &mut filter_buffer, d)?
+            } else {
+                self.decode_buffer(XzInOutBuffer::discard(input), d)?
+            };
+            consumed += result.input_consumed();
+            produced += result.output_produced();
+            if result.is_end_of_stream() {
+                return Ok(XzNextBlockResult::EndOfStream(consumed, produced));
+            }
 
-        let expected_crc = u32::from_le_bytes([buf[8], buf[9], buf[10], buf[11]]);
-        let actual_crc = crc32(0, &buf[6..8]);
-        if actual_crc != expected_crc {
-            return Err(XzError::StreamHeaderCrc32Mismatch(actual_crc, expected_crc));
+            // Otherwise the decoder only returns with input left if the output is full
+            // or if a block with filters starts while the output is discarded.
+            let output_full = if filtered {
+                result.output_produced() == filter_buffer.len()
+            } else {
+                result.output_produced() > 0 || self.in_filtered_block()
+            };
+            if self.stopped || consumed == input_data.len() || !output_full {
+                return Ok(XzNextBlockResult::NeedMoreData(consumed, produced));
+            }
         }
+    }
 
-        if buf[6] != 0 {
-            return Err(XzError::UnsupportedStreamHeaderOption);
+    /// Checks that the input ended after a complete stream and valid stream padding.
+    const fn finish(&self) -> Result<(), XzError> {
+        if matches!(self.state, XzDecoderState::StreamPadding) {
+            if self.stream_padding != 0 {
+                return Err(XzError::InvalidStreamPadding);
+            }
+            return Ok(());
         }
 
-        if buf[7] > 15 {
-            return Err(XzError::UnsupportedStreamHeaderOption);
+        if self.needs_reset {
+            return Err(XzError::NeedsReset);
         }
 
-        self.check_type = XzCheckType::try_from(buf[7])?;
+        Err(XzError::UnexpectedEndOfInput)
+    }
+
+    /// decodes the stream header and calculates/validates its crc32.
+    fn dec_stream_header(&mut self) -> Result<(), XzError> {
+        self.check_type = parse_stream_header(self.temp.buf())?;
         Ok(())
     }
 
     /// Reset the entire decoder to its default state where it's ready to process a fresh stream.
     const fn reset(&mut self) {
+        self.needs_reset = false;
+        self.total_in = 0;
+        self.total_out = 0;
+        self.stream_index = 0;
+        self.check_type = XzCheckType::None;
+        self.stopped = false;
+        self.error_context = None;
+        self.damaged_block = None;
+        self.damaged_blocks = 0;
+        self.block_end = None;
+        self.block_info = XzBlockInfo::empty();
+        self.reset_for_next_stream();
+    }
+
+    /// Returns the current stats, the dictionary is not part of the inner decoder.
+    const fn stats(&self, dict_size: usize) -> XzDecoderStats {
+        XzDecoderStats {
+            compressed_consumed: self.total_in,
+            uncompressed_produced: self.total_out,
+            stream_index: self.stream_index,
+            block_index: self.block.count,
+            check_type: self.check_type,
+            dict_size,
+            block_compressed_position: self.block.compressed,
+            block_uncompressed_position: self.block.uncompressed,
+            check_ignored: self.ignore_check,
+            damaged_blocks: self.damaged_blocks,
+        }
+    }
+
+    /// Returns the position of the decoder after the bytes of the buffer were counted.
+    const fn error_context(&self, buf: &XzInOutBuffer) -> XzErrorContext {
+        // The block is already counted once its compressed data ended.
+        let block_index = match self.state {
+            XzDecoderState::BlockPadding | XzDecoderState::BlockCheck => {
+                self.block.count.saturating_sub(1)
+            }
+            _ => self.block.count,
+        };
+
+        XzErrorContext {
+            compressed_offset: self.total_in,
+            uncompressed_offset: self.total_out,
+            stream_index: self.stream_index,
+            block_index,
+            input_consumed: buf.input_position(),
+            output_produced: buf.output_position(),
+        }
+    }
+
+    /// Resets the per stream state so the next stream header can be decoded.
+    /// Unlike `reset` this keeps a pending error.
+    const fn reset_for_next_stream(&mut self) {
         self.state = XzDecoderState::StreamHeader;
         self.had_not_enough_data = false;
-        self.needs_reset = false;
+        self.stream_padding = 0;
         self.last_output_buffer_size = 0;
         self.last_input_buffer_size = 0;
-        self.vli_decoder.reset();
         self.crc = 0;
+        self.stream_damaged = false;
         self.block.reset();
         self.index.reset();
         self.temp.pos = 0;
@@ -3040,6 +3930,17 @@ impl XzInnerDecoder {
     }
 }
 
+/// Returns the amount of memory a `XzDecoder` needs to decode the block with the given block header.
+/// This is the size of the decoder itself plus the size of the dictionary.
+fn memory_usage_of_block_header(buf: &[u8]) -> Result<u64, XzError> {
+    Ok(memory_usage_of_dict(XzBlockInfo::parse(buf)?.dict_size()))
+}
+
+/// Returns the amount of memory a `XzDecoder` needs with a dictionary of the given size.
+pub const fn memory_usage_of_dict(dict_size: usize) -> u64 {
+    mem::size_of::<XzDecoder>() as u64 + dict_size as u64
+}
+
 /// Temporary buffer that is filled by some steps during decoding.
 #[derive(Clone, Debug)]
 pub struct XzTempBuffer {
@@ -3096,30 +3997,6 @@ impl XzTempBuffer {
         &self.buf.as_slice()[self.pos()..self.size()]
     }
 
-    /// removes 4 trailing bytes.
-    /// This fn should not be called if `available()` is less than 4.
-    ///
-    /// # Panics and Safety
-    /// always panics on debug builds if `available()` is less than 4.
-    /// behavior on release builds depends on how usize underflow's.
-    /// If `size()` is less than 4 and usize wraps around normally during an underflow (x86 for ex.)
-    /// then this fn will panic in release mode.
-    /// If usize clamps to 0 then this fn will return 4 "unexpected" bytes
-    /// and leave the temp buffer in an otherwise illegal state.
-    /// If `size()` is greater than 4 but `available()` is less than 4 then this fn will always leave the buffer
-    /// in an illegal state and return 4 "unexpected" bytes on release builds.
-    fn remove_trailing_4bytes(&mut self) -> [u8; 4] {
-        self.check_consistent();
-        self.size -= 4;
-        self.check_consistent();
-        [
-            self.buf[self.size],
-            self.buf[self.size + 1],
-            self.buf[self.size + 2],
-            self.buf[self.size + 3],
-        ]
-    }
-
     /// Returns a slice that is exactly `feed_count` elements big.
     /// This fn should not be called with a `feed_count` larger than `available()`.
     ///
@@ -3149,51 +4026,6 @@ impl Default for XzTempBuffer {
     }
 }
 
-/// Enum that defines the state of the index decoder state machine.
-#[derive(Clone, Default, Debug)]
-#[repr(u8)]
-pub enum XzDecoderIndexSequence {
-    #[default]
-    /// TODO
-    Count,
-    /// TODO
-    Unpadded,
-    /// TODO
-    Uncompressed,
-}
-
-#[derive(Clone, Default, Debug)]
-struct XzDecoderIndex {
-    /// state machine state
-    sequence: XzDecoderIndexSequence,
-    /// TODO
-    size: u64,
-    /// TODO
-    count: u64,
-    /// hash of the index
-    hash: XzDecoderHash,
-}
-
-impl XzDecoderIndex {
-    /// Constructor
-    const fn new() -> Self {
-        Self {
-            sequence: XzDecoderIndexSequence::Count,
-            size: 0,
-            count: 0,
-            hash: XzDecoderHash::new(),
-        }
-    }
-
-    /// Reset to default state.
-    const fn reset(&mut self) {
-        self.sequence = XzDecoderIndexSequence::Count;
-        self.size = 0;
-        self.count = 0;
-        self.hash.reset();
-    }
-}
-
 ///Hash information to verify the decoding state.
 #[derive(Clone, Default, Eq, PartialEq, Debug)]
 pub struct XzDecoderHash {
@@ -3207,7 +4039,7 @@ pub struct XzDecoderHash {
 
 impl XzDecoderHash {
     /// Constructor
-    const fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
             unpadded: 0,
             uncompressed: 0,
@@ -3223,7 +4055,7 @@ impl XzDecoderHash {
     }
 
     /// Calculates the crc32 of the block.
-    fn calculate_crc32(&mut self) {
+    pub fn calculate_crc32(&mut self) {
         let unpadded_bytes = self.unpadded.to_ne_bytes();
         let uncompressed_bytes = self.uncompressed.to_ne_bytes();
         let crc32_bytes = self.crc32.to_ne_bytes();
@@ -3261,6 +4093,8 @@ struct XzDecBlock {
     pub compressed: u64,
     /// Amount of uncompressed bytes
     pub uncompressed: u64,
+    /// Unpadded size of the last block, set once its data was decoded.
+    pub unpadded: u64,
     /// Amount of blocks already decoded.
     pub count: u64,
 
@@ -3274,6 +4108,7 @@ impl XzDecBlock {
         Self {
             compressed: 0,
             uncompressed: 0,
+            unpadded: 0,
             count: 0,
             hash: XzDecoderHash::new(),
         }
@@ -3297,6 +4132,7 @@ impl XzDecBlock {
     const fn reset(&mut self) {
         self.compressed = 0;
         self.uncompressed = 0;
+        self.unpadded = 0;
         self.count = 0;
         self.hash.reset();
     }
@@ -0,0 +1,164 @@
+extern crate std;
+
+use crate::{XzDecoder, XzError, XzNextBlockResult};
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::num::NonZeroUsize;
+use std::io::Write;
+
+/// Writer that decompresses the .xz data written to it and writes the decompressed data to the inner writer.
+///
+/// This is useful if the compressed data arrives in pieces, for example in callbacks, and cannot be read from a `Read`.
+/// Once the end of the stream was written, `write` only accepts the bytes up to the end of the stream and
+/// returns 0 afterward, unless multi stream mode is enabled.
+/// Call `finish` to check that a complete stream was written and get the inner writer back.
+///
+/// A part of the decompressed data may only be written to the inner writer by the next call to `write`.
+/// `flush` writes all data that can be decompressed from the data written so far.
+#[derive(Debug)]
+pub struct XzDecompressWriter<W: Write> {
+    /// the inner decoder, on the heap.
+    decoder: Box<XzDecoder<'static>>,
+    /// the underlying stream
+    writer: W,
+    /// Buffer for the decompressed data.
+    buffer: Vec<u8>,
+    /// Was the end of a valid xz stream written?
+    eos: bool,
+}
+
+impl<W: Write> XzDecompressWriter<W> {
+    /// Creates a new instance of `XzDecompressWriter`
+    /// This writer will heap allocate an internal 8k buffer for the decompressed data.
+    /// This writer will allocate up to about 3GB of additional memory in heap for the lzma dictionary
+    /// depending on the input file.
+    #[allow(clippy::missing_panics_doc)] //We never actually panic.
+    #[must_use]
+    pub fn new(w: W) -> Self {
+        Self::new_with_buffer_size_and_decoder(
+            w,
+            NonZeroUsize::new(8192).expect("Impossible to fail"),
+            XzDecoder::in_heap(),
+        )
+    }
+
+    #[must_use]
+    pub fn new_with_buffer_size_and_decoder(
+        w: W,
+        buffer_size: NonZeroUsize,
+        decoder: Box<XzDecoder<'static>>,
+    ) -> Self {
+        Self {
+            decoder,
+            writer: w,
+            buffer: vec![0; buffer_size.into()],
+            eos: false,
+        }
+    }
+
+    /// Enables or disables multi stream mode of the decoder.
+    ///
+    /// In multi stream mode the writer accepts all concatenated streams and the stream padding between them.
+    /// This means that data following the last stream must be stream padding or another stream.
+    pub const fn set_multi_stream(&mut self, multi_stream: bool) {
+        self.decoder.set_multi_stream(multi_stream);
+    }
+
+    /// Returns true if the end of a valid xz stream was written.
+    /// In multi stream mode this is true once the first stream is complete, use `finish` to check the last stream.
+    #[must_use]
+    pub const fn is_eos(&self) -> bool {
+        self.eos
+    }
+
+    /// Returns a reference to the underlying stream.
+    #[must_use]
+    pub const fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Returns a mutable reference to the underlying stream.
+    pub const fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Returns the underlying stream without checking that a complete stream was written.
+    #[must_use]
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Checks that a complete stream including its index and stream footer was written,
+    /// flushes the underlying stream and returns it.
+    ///
+    /// # Errors
+    /// `UnexpectedEof` if the data ended in the middle of a stream.
+    /// `InvalidData` if the stream padding after the last stream is invalid.
+    /// propagated from the underlying stream.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        match self.decoder.finish() {
+            Ok(()) => {}
+            Err(XzError::UnexpectedEndOfInput) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    XzError::UnexpectedEndOfInput,
+                ))
+            }
+            Err(err) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, err)),
+        }
+
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> Write for XzDecompressWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let mut consumed = 0;
+        while !self.eos || self.decoder.is_multi_stream() {
+            let result = self
+                .decoder
+                .decode(&buf[consumed..], &mut self.buffer)
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+
+            consumed += result.input_consumed();
+            let produced = result.output_produced();
+            self.writer.write_all(&self.buffer[..produced])?;
+            if let XzNextBlockResult::EndOfStream(_, _) = result {
+                self.eos = true;
+            }
+
+            // The decoder needs input to make progress, any remaining output is written by the next call.
+            if !result.made_progress() || consumed == buf.len() {
+                break;
+            }
+        }
+
+        Ok(consumed)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        //After the end of the stream the decoder needs a reset.
+        while !self.eos || self.decoder.is_multi_stream() {
+            let result = match self.decoder.decode(&[], &mut self.buffer) {
+                Ok(result) => result,
+                //Nothing is pending.
+                Err(XzError::NeedsLargerInputBuffer) => break,
+                Err(err) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, err)),
+            };
+
+            let produced = result.output_produced();
+            self.writer.write_all(&self.buffer[..produced])?;
+            if produced == 0 {
+                break;
+            }
+        }
+
+        self.writer.flush()
+    }
+}
@@ -1,9 +1,105 @@
 use crate::decoder::{DecodeResult, XzDictBuffer, XzInOutBuffer};
 use crate::XzError;
-use core::num::NonZeroUsize;
 
-/// Delta filter decoder
-#[derive(Debug)]
+/// Largest distance supported by the delta filter.
+const DISTANCE_MAX: usize = 256;
+
+/// Checks that the distance is supported by the delta filter.
+const fn check_distance(distance: usize) -> Result<(), XzError> {
+    if distance == 0 || distance > DISTANCE_MAX {
+        return Err(XzError::UnsupportedDeltaDistance(distance as u64));
+    }
+
+    Ok(())
+}
+
+/// Delta filter encoder, produces the same output as the delta filter of liblzma.
+///
+/// Each byte is replaced by its difference to the byte that is distance bytes before it.
+/// The data can be encoded in place in any amount of calls.
+#[derive(Debug, Clone)]
+pub struct DeltaEncoder {
+    /// index in the history buffer.
+    index: usize,
+    /// Current distance
+    distance: usize,
+    /// history buffer, initially 0, contains the last input bytes.
+    history: [u8; 256],
+}
+
+impl Default for DeltaEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeltaEncoder {
+    /// Creates a delta encoder with a distance of 1.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            index: 0,
+            distance: 1,
+            history: [0; 256],
+        }
+    }
+
+    /// Creates a delta encoder with the given distance (1-256).
+    ///
+    /// # Errors
+    /// `XzError::UnsupportedDeltaDistance` if the distance is not supported.
+    pub const fn with_distance(distance: usize) -> Result<Self, XzError> {
+        let mut encoder = Self::new();
+        match encoder.reset_with_distance(distance) {
+            Ok(()) => Ok(encoder),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Returns the distance of the encoder.
+    #[must_use]
+    pub const fn distance(&self) -> usize {
+        self.distance
+    }
+
+    /// Resets the encoder to the start of new data.
+    pub const fn reset(&mut self) {
+        self.index = 0;
+        self.history = [0; 256];
+    }
+
+    /// Resets the encoder to the start of new data with the given distance (1-256).
+    ///
+    /// # Errors
+    /// `XzError::UnsupportedDeltaDistance` if the distance is not supported.
+    pub const fn reset_with_distance(&mut self, distance: usize) -> Result<(), XzError> {
+        if let Err(err) = check_distance(distance) {
+            return Err(err);
+        }
+
+        self.distance = distance;
+        self.reset();
+        Ok(())
+    }
+
+    /// Encodes the data in place. The encoder continues where the previous call stopped.
+    pub fn encode(&mut self, data: &mut [u8]) {
+        for byte in data {
+            let previous = self.history[self.index];
+            self.history[self.index] = *byte;
+            *byte = byte.wrapping_sub(previous);
+            self.index += 1;
+            if self.index == self.distance {
+                self.index = 0;
+            }
+        }
+    }
+}
+
+/// Delta filter decoder, reverses the encoding of `DeltaEncoder`.
+///
+/// The data can be decoded in place in any amount of calls.
+#[derive(Debug, Clone)]
 pub struct DeltaDecoder {
     /// index in the history buffer.
     index: usize,
@@ -20,23 +116,54 @@ impl Default for DeltaDecoder {
 }
 
 impl DeltaDecoder {
-    /// Constructor
+    /// Creates a delta decoder with a distance of 1.
+    #[must_use]
     pub const fn new() -> Self {
         Self {
             index: 0,
-            distance: 0,
+            distance: 1,
             history: [0; 256],
         }
     }
 
-    /// Reset the delta decoder to use the given distance.
-    pub const fn reset(&mut self, distance: NonZeroUsize) {
-        debug_assert!(distance.get() <= 256);
+    /// Creates a delta decoder with the given distance (1-256).
+    ///
+    /// # Errors
+    /// `XzError::UnsupportedDeltaDistance` if the distance is not supported.
+    pub const fn with_distance(distance: usize) -> Result<Self, XzError> {
+        let mut decoder = Self::new();
+        match decoder.reset_with_distance(distance) {
+            Ok(()) => Ok(decoder),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Returns the distance of the decoder.
+    #[must_use]
+    pub const fn distance(&self) -> usize {
+        self.distance
+    }
+
+    /// Resets the decoder to the start of new data.
+    pub const fn reset(&mut self) {
         self.index = 0;
-        self.distance = distance.get();
         self.history = [0; 256];
     }
 
+    /// Resets the decoder to the start of new data with the given distance (1-256).
+    ///
+    /// # Errors
+    /// `XzError::UnsupportedDeltaDistance` if the distance is not supported.
+    pub const fn reset_with_distance(&mut self, distance: usize) -> Result<(), XzError> {
+        if let Err(err) = check_distance(distance) {
+            return Err(err);
+        }
+
+        self.distance = distance;
+        self.reset();
+        Ok(())
+    }
+
     /// Run the delta filter on fewer or equal than distance bytes of output.
     fn decode_small_buffer(&mut self, produced_data: &mut [u8]) {
         debug_assert!(produced_data.len() <= self.distance);
@@ -68,9 +195,8 @@ impl DeltaDecoder {
         }
     }
 
-    /// Run the delta filter on the `produced_data`.
-    fn decode(&mut self, produced_data: &mut [u8]) {
-        debug_assert!(self.distance != 0);
+    /// Decodes the data in place. The decoder continues where the previous call stopped.
+    pub fn decode(&mut self, produced_data: &mut [u8]) {
         if produced_data.len() <= self.distance {
             self.decode_small_buffer(produced_data);
             return;
@@ -97,7 +223,9 @@ impl DeltaDecoder {
     }
 
     /// Run the lzma decoder and then process the result using the filter.
-    pub fn run<T: FnMut(&mut XzInOutBuffer, &mut XzDictBuffer) -> Result<DecodeResult, XzError>>(
+    pub(crate) fn run<
+        T: FnMut(&mut XzInOutBuffer, &mut XzDictBuffer) -> Result<DecodeResult, XzError>,
+    >(
         &mut self,
         mut next_filter: T,
         b: &mut XzInOutBuffer,
@@ -0,0 +1,284 @@
+#[cfg(feature = "std")]
+extern crate std;
+
+use crate::container::{
+    parse_stream_footer, parse_stream_header, XzIndexDecoder, STREAM_HEADER_SIZE,
+};
+use crate::{XzCheckType, XzError};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Smallest possible unpadded size of a block.
+const UNPADDED_SIZE_MIN: u64 = 5;
+
+/// Decodes a complete index including its padding and crc32.
+/// `record` is called with the unpadded size and the uncompressed size of every block in order.
+fn dec_index(buf: &[u8], record: impl FnMut(u64, u64)) -> Result<(), XzError> {
+    let (consumed, done) = XzIndexDecoder::new().decode(buf, None, record)?;
+    if !done || consumed != buf.len() {
+        return Err(XzError::CorruptedDataInBlockIndex);
+    }
+
+    Ok(())
+}
+
+/// Information about a block as recorded in the index of its stream.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct XzIndexRecord {
+    /// Offset of the block header in the file.
+    pub compressed_offset: u64,
+    /// Offset of the first byte of the block in the uncompressed data of all streams.
+    pub uncompressed_offset: u64,
+    /// Size of the block header, the compressed data and the check, without the block padding.
+    pub unpadded_size: u64,
+    /// Amount of uncompressed bytes in the block.
+    pub uncompressed_size: u64,
+}
+
+impl XzIndexRecord {
+    /// Size of the block in the file including the block padding.
+    #[must_use]
+    pub const fn total_size(&self) -> u64 {
+        (self.unpadded_size + 3) & !3
+    }
+}
+
+/// Information about a stream taken from its stream header, stream footer and index.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct XzStreamInfo {
+    /// Offset of the stream header in the file.
+    pub compressed_offset: u64,
+    /// Offset of the first byte of the stream in the uncompressed data of all streams.
+    pub uncompressed_offset: u64,
+    /// Size of the stream from the start of the stream header to the end of the stream footer.
+    pub compressed_size: u64,
+    /// Amount of uncompressed bytes in the stream.
+    pub uncompressed_size: u64,
+    /// Check type of all blocks in the stream.
+    pub check_type: XzCheckType,
+    /// Size of the index in bytes.
+    pub index_size: u64,
+    /// Amount of stream padding bytes that follow the stream.
+    pub stream_padding: u64,
+    /// All blocks of the stream.
+    pub blocks: Vec<XzIndexRecord>,
+}
+
+/// Summary of an .xz file, equivalent to the output of "xz --list".
+/// It is created from the stream headers, stream footers and indices without decompressing any blocks.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct XzFileInfo {
+    /// All streams of the file in order.
+    pub streams: Vec<XzStreamInfo>,
+}
+
+impl XzFileInfo {
+    /// Parses the summary of a complete .xz file in memory.
+    ///
+    /// # Errors
+    /// Any error in the stream headers, stream footers, stream padding or indices.
+    /// `XzError::UnexpectedEndOfInput` if the data is empty.
+    pub fn from_slice(data: &[u8]) -> Result<Self, XzError> {
+        Self::parse(
+            data.len() as u64,
+            |offset, buf| {
+                let src = usize::try_from(offset)
+                    .ok()
+                    .and_then(|offset| data.get(offset..offset.checked_add(buf.len())?))
+                    .ok_or(XzError::UnexpectedEndOfInput)?;
+                buf.copy_from_slice(src);
+                Ok(())
+            },
+            |err| err,
+        )
+    }
+
+    /// Parses the summary of an .xz file by seeking to the end of it and reading backwards.
+    /// The position of the reader afterward is unspecified.
+    ///
+    /// # Errors
+    /// propagated from the underlying stream.
+    /// `InvalidData` if the stream headers, stream footers, stream padding or indices are not valid.
+    #[cfg(feature = "std")]
+    pub fn from_reader<R: std::io::Read + std::io::Seek>(r: &mut R) -> std::io::Result<Self> {
+        use std::io::SeekFrom;
+
+        let size = r.seek(SeekFrom::End(0))?;
+        Self::parse(
+            size,
+            |offset, buf| {
+                r.seek(SeekFrom::Start(offset))?;
+                r.read_exact(buf)
+            },
+            |err| match err {
+                XzError::UnexpectedEndOfInput => {
+                    std::io::Error::new(std::io::ErrorKind::UnexpectedEof, err)
+                }
+                err => std::io::Error::new(std::io::ErrorKind::InvalidData, err),
+            },
+        )
+    }
+
+    /// Reads the streams backwards from the end of the file.
+    fn parse<E>(
+        size: u64,
+        mut read_at: impl FnMut(u64, &mut [u8]) -> Result<(), E>,
+        invalid: impl Fn(XzError) -> E,
+    ) -> Result<Self, E> {
+        if size == 0 {
+            return Err(invalid(XzError::UnexpectedEndOfInput));
+        }
+
+        let mut streams = Vec::new();
+        let mut end = size;
+        while end > 0 {
+            //Skip the stream padding, which is a multiple of 4 bytes.
+            let mut stream_padding = 0;
+            let mut padding = [0u8; 4];
+            loop {
+                if end < 4 {
+                    return Err(invalid(XzError::InvalidStreamPadding));
+                }
+                read_at(end - 4, &mut padding)?;
+                if padding != [0; 4] {
+                    break;
+                }
+                end -= 4;
+                stream_padding += 4;
+            }
+
+            let footer_offset = end
+                .checked_sub(STREAM_HEADER_SIZE as u64)
+                .ok_or_else(|| invalid(XzError::UnexpectedEndOfInput))?;
+            let mut footer = [0u8; STREAM_HEADER_SIZE];
+            read_at(footer_offset, &mut footer)?;
+            let (index_size, flags) = parse_stream_footer(&footer).map_err(&invalid)?;
+            if flags[0] != 0 || flags[1] > 15 {
+                return Err(invalid(XzError::UnsupportedStreamHeaderOption));
+            }
+
+            let index_offset = footer_offset
+                .checked_sub(index_size)
+                .filter(|offset| *offset >= STREAM_HEADER_SIZE as u64)
+                .ok_or_else(|| invalid(XzError::CorruptedDataInBlockIndex))?;
+            let mut index = vec![
+                0u8;
+                usize::try_from(index_size)
+                    .map_err(|_| invalid(XzError::CorruptedDataInBlockIndex))?
+            ];
+            read_at(index_offset, &mut index)?;
+
+            let mut blocks = Vec::new();
+            let mut blocks_size = 0u64;
+            let mut uncompressed_size = 0u64;
+            let mut overflow = false;
+            let mut too_small = false;
+            dec_index(&index, |unpadded_size, block_uncompressed_size| {
+                let record = XzIndexRecord {
+                    compressed_offset: blocks_size,
+                    uncompressed_offset: uncompressed_size,
+                    unpadded_size,
+                    uncompressed_size: block_uncompressed_size,
+                };
+                let (size, overflow_compressed) = blocks_size.overflowing_add(record.total_size());
+                let (uncompressed, overflow_uncompressed) =
+                    uncompressed_size.overflowing_add(block_uncompressed_size);
+                overflow |= overflow_compressed || overflow_uncompressed;
+                too_small |= unpadded_size < UNPADDED_SIZE_MIN;
+                blocks_size = size;
+                uncompressed_size = uncompressed;
+                blocks.push(record);
+            })
+            .map_err(&invalid)?;
+
+            let compressed_offset = index_offset
+                .checked_sub(blocks_size)
+                .and_then(|offset| offset.checked_sub(STREAM_HEADER_SIZE as u64))
+                .filter(|_| !overflow && !too_small)
+                .ok_or_else(|| invalid(XzError::CorruptedDataInBlockIndex))?;
+            let mut header = [0u8; STREAM_HEADER_SIZE];
+            read_at(compressed_offset, &mut header)?;
+            let check_type = parse_stream_header(&header).map_err(&invalid)?;
+            if flags[1] != u8::from(check_type) {
+                return Err(invalid(XzError::FooterCheckTypeMismatch(
+                    u32::from(flags[1]),
+                    check_type,
+                )));
+            }
+
+            streams.push(XzStreamInfo {
+                compressed_offset,
+                uncompressed_offset: 0,
+                compressed_size: end - compressed_offset,
+                uncompressed_size,
+                check_type,
+                index_size,
+                stream_padding,
+                blocks,
+            });
+            end = compressed_offset;
+        }
+
+        //Offsets can only be calculated once all streams are known.
+        streams.reverse();
+        let mut uncompressed_offset = 0u64;
+        for stream in &mut streams {
+            stream.uncompressed_offset = uncompressed_offset;
+            for block in &mut stream.blocks {
+                block.compressed_offset += stream.compressed_offset + STREAM_HEADER_SIZE as u64;
+                block.uncompressed_offset += uncompressed_offset;
+            }
+            uncompressed_offset = uncompressed_offset
+                .checked_add(stream.uncompressed_size)
+                .ok_or_else(|| invalid(XzError::CorruptedDataInBlockIndex))?;
+        }
+
+        Ok(Self { streams })
+    }
+
+    /// Size of the file including all stream padding.
+    #[must_use]
+    pub fn compressed_size(&self) -> u64 {
+        self.streams
+            .iter()
+            .map(|stream| stream.compressed_size + stream.stream_padding)
+            .sum()
+    }
+
+    /// Amount of uncompressed bytes of all streams.
+    #[must_use]
+    pub fn uncompressed_size(&self) -> u64 {
+        self.streams
+            .iter()
+            .map(|stream| stream.uncompressed_size)
+            .sum()
+    }
+
+    /// Amount of blocks of all streams.
+    #[must_use]
+    pub fn block_count(&self) -> usize {
+        self.streams.iter().map(|stream| stream.blocks.len()).sum()
+    }
+
+    /// Amount of stream padding bytes of all streams.
+    #[must_use]
+    pub fn stream_padding(&self) -> u64 {
+        self.streams
+            .iter()
+            .map(|stream| stream.stream_padding)
+            .sum()
+    }
+
+    /// Compressed size divided by the uncompressed size, like the ratio shown by "xz --list".
+    /// Returns None if there is no uncompressed data.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)] //The ratio is only an approximation.
+    pub fn ratio(&self) -> Option<f64> {
+        let uncompressed = self.uncompressed_size();
+        if uncompressed == 0 {
+            return None;
+        }
+
+        Some(self.compressed_size() as f64 / uncompressed as f64)
+    }
+}
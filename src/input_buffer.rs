@@ -0,0 +1,125 @@
+extern crate std;
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::num::NonZeroUsize;
+use std::io::Read;
+
+#[cfg(feature = "futures-io")]
+use core::pin::Pin;
+#[cfg(feature = "futures-io")]
+use core::task::{ready, Context, Poll};
+#[cfg(feature = "futures-io")]
+use futures_io::AsyncRead;
+
+/// Buffer for compressed data that was read from the underlying stream but not decoded yet.
+#[derive(Debug)]
+pub struct InputBuffer {
+    /// A buffer
+    buffer: Vec<u8>,
+    /// Amount of bytes in the buffer we have consumed.
+    consumed: usize,
+    /// Amount of bytes in the buffer available for consumption.
+    fill_count: usize,
+}
+
+impl InputBuffer {
+    /// Creates an empty buffer that reads up to `size` bytes at once.
+    pub fn new(size: NonZeroUsize) -> Self {
+        Self {
+            buffer: vec![0; size.into()],
+            consumed: 0,
+            fill_count: 0,
+        }
+    }
+
+    /// Creates a buffer that already contains `initial`, it grows if `initial` is larger than `size`.
+    pub fn with_data(size: NonZeroUsize, initial: &[u8]) -> Self {
+        let mut buffer =
+            Self::new(size.max(NonZeroUsize::new(initial.len()).unwrap_or(NonZeroUsize::MIN)));
+        buffer.buffer[..initial.len()].copy_from_slice(initial);
+        buffer.fill_count = initial.len();
+        buffer
+    }
+
+    /// Returns the data that was not consumed yet.
+    pub fn data(&self) -> &[u8] {
+        debug_assert!(self.fill_count >= self.consumed);
+        &self.buffer[self.consumed..self.fill_count]
+    }
+
+    /// Marks `amount` bytes of `data` as consumed.
+    pub fn consume(&mut self, amount: usize) {
+        self.consumed += amount;
+        debug_assert!(self.fill_count >= self.consumed);
+    }
+
+    /// Returns true if all data in the buffer was consumed.
+    pub const fn is_empty(&self) -> bool {
+        self.consumed == self.fill_count
+    }
+
+    /// Reads from the underlying stream if the buffer is empty.
+    /// Returns false if the buffer is empty and the underlying stream is at eof.
+    pub fn try_fill(&mut self, reader: &mut impl Read) -> std::io::Result<bool> {
+        self.try_fill_max(reader, self.buffer.len())
+    }
+
+    /// Same as `try_fill` but reads at most `max` bytes from the underlying stream.
+    pub fn try_fill_max(&mut self, reader: &mut impl Read, max: usize) -> std::io::Result<bool> {
+        if self.is_empty() {
+            let size = max.min(self.buffer.len());
+            let count = reader.read(&mut self.buffer[..size])?;
+            self.filled(count);
+        }
+
+        Ok(!self.is_empty())
+    }
+
+    /// Same as `try_fill` but for an asynchronous stream.
+    /// The buffer is only modified once the underlying stream is ready.
+    #[cfg(feature = "futures-io")]
+    pub fn poll_try_fill(
+        &mut self,
+        reader: Pin<&mut impl AsyncRead>,
+        cx: &mut Context<'_>,
+    ) -> Poll<std::io::Result<bool>> {
+        if self.is_empty() {
+            let count = ready!(reader.poll_read(cx, &mut self.buffer))?;
+            self.filled(count);
+        }
+
+        Poll::Ready(Ok(!self.is_empty()))
+    }
+
+    /// Discards all data that was not consumed yet.
+    pub const fn clear(&mut self) {
+        self.filled(0);
+    }
+
+    /// Replaces the content of the buffer with the first `count` bytes that were just read into it.
+    const fn filled(&mut self, count: usize) {
+        self.fill_count = count;
+        self.consumed = 0;
+    }
+
+    /// Copies data that was not consumed yet to `buf`, or reads from the underlying stream if the buffer is empty.
+    pub fn read(&mut self, reader: &mut impl Read, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.is_empty() {
+            return reader.read(buf);
+        }
+
+        let available = self.data();
+        let to_copy = available.len().min(buf.len());
+        buf[..to_copy].copy_from_slice(&available[..to_copy]);
+        self.consume(to_copy);
+        Ok(to_copy)
+    }
+
+    /// Returns the (possibly empty) data that was not consumed yet.
+    pub fn into_vec(mut self) -> Vec<u8> {
+        self.buffer.copy_within(self.consumed..self.fill_count, 0);
+        self.buffer.truncate(self.fill_count - self.consumed);
+        self.buffer
+    }
+}
@@ -29,9 +29,15 @@ extern crate alloc;
 #[cfg(feature = "bcj")]
 mod bcj;
 
+/// Parsing of block headers.
+mod block_info;
+
 /// Crc32 validation
 mod crc32;
 
+/// Encoding and parsing of stream headers, block headers, checks, the index and stream footers.
+mod container;
+
 /// Crc64 validation
 #[cfg(feature = "crc64")]
 mod crc64xz;
@@ -39,14 +45,61 @@ mod crc64xz;
 /// LZMA and XZ stream decoder
 mod decoder;
 
+/// Raw LZMA2 decoder without the xz container.
+mod lzma2;
+
+/// Decoder for legacy .lzma files.
+mod lzma;
+
+/// Decoder for lzip files.
+mod lzip;
+
+/// Raw LZMA2 encoder.
+#[cfg(feature = "alloc")]
+mod lzma2_encoder;
+
+/// Summary of .xz files read from their stream headers, stream footers and indices.
+#[cfg(feature = "alloc")]
+mod index;
+
+/// Encoder for .xz streams that stores the data uncompressed.
+mod stored;
+
+/// Identification of .xz streams from their first bytes.
+mod probe;
+
 /// SHA256 validation. Mostly wraps the sha2 crate.
 #[cfg(feature = "sha256")]
 mod sha256;
 
+/// Buffering of compressed data read from an underlying stream.
+#[cfg(feature = "std")]
+mod input_buffer;
+
 /// Features for the Rust Standard Library. (`io::Read` support)
 #[cfg(feature = "std")]
 mod stl;
 
+/// Random access to .xz files using the index. (`io::Seek` support)
+#[cfg(feature = "std")]
+mod seekable;
+
+/// Decoding of the blocks of a file on multiple threads.
+#[cfg(feature = "std")]
+mod parallel;
+
+/// .xz encoder for the Rust Standard Library. (`io::Write` support)
+#[cfg(feature = "std")]
+mod writer;
+
+/// Decoding to a `std::io::Write`. (`io::Write` support)
+#[cfg(feature = "std")]
+mod decompress_writer;
+
+/// Decoding from a `futures_io::AsyncRead`.
+#[cfg(feature = "futures-io")]
+mod async_reader;
+
 /// utility for clamping integers.
 mod clamp;
 
@@ -58,11 +111,36 @@ mod vli;
 mod delta;
 
 // These are all types that are needed to use this crate to decode some xz files.
+#[cfg(feature = "futures-io")]
+pub use async_reader::AsyncXzReader;
+#[cfg(feature = "bcj")]
+pub use bcj::{BcjDecoder, BcjEncoder, BcjFilter};
+pub use block_info::{XzBlockInfo, XzFilterInfo};
+#[cfg(feature = "std")]
+pub use decompress_writer::XzDecompressWriter;
+#[cfg(feature = "delta")]
+pub use delta::{DeltaDecoder, DeltaEncoder};
+#[cfg(feature = "alloc")]
+pub use index::{XzFileInfo, XzIndexRecord, XzStreamInfo};
+pub use lzip::LzipDecoder;
+pub use lzma::{LzmaAloneDecoder, LzmaDecoder, LzmaProperties};
+pub use lzma2::Lzma2Decoder;
+#[cfg(feature = "alloc")]
+pub use lzma2_encoder::{Lzma2Encoder, Lzma2EncoderOptions};
+#[cfg(feature = "std")]
+pub use parallel::ParallelXzReader;
+pub use probe::{probe, XzProbe};
+#[cfg(feature = "std")]
+pub use seekable::XzSeekableReader;
+#[cfg(feature = "std")]
+pub use stl::{LzipReader, LzmaAloneReader, XzReader};
+pub use stored::XzStoredEncoder;
 #[cfg(feature = "std")]
-pub use stl::XzReader;
+pub use writer::XzWriter;
 pub use {
-    decoder::XzCheckType, decoder::XzDecoder, decoder::XzError, decoder::XzNextBlockResult,
-    decoder::XzStaticDecoder,
+    decoder::XzBlockEnd, decoder::XzCheckResult, decoder::XzCheckType, decoder::XzDamagedBlock,
+    decoder::XzDecoder, decoder::XzDecoderStats, decoder::XzError, decoder::XzErrorContext,
+    decoder::XzNextBlockResult, decoder::XzStaticDecoder,
 };
 
 /// Minimum possible dictionary size.
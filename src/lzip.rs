@@ -0,0 +1,361 @@
+use crate::crc32::crc32;
+use crate::{LzmaDecoder, LzmaProperties, XzError, XzNextBlockResult, DICT_SIZE_MIN};
+#[cfg(feature = "alloc")]
+use alloc::vec;
+
+/// Magic number at the start of each lzip member.
+const LZIP_MAGIC: [u8; 4] = *b"LZIP";
+
+/// Size of the lzip member header. (magic, version, coded dictionary size)
+const LZIP_HEADER_SIZE: usize = 6;
+
+/// Size of the lzip member trailer. (crc32, data size, member size)
+const LZIP_TRAILER_SIZE: usize = 20;
+
+/// Properties of the lzma1 data in lzip members, the dictionary size is replaced with the one from the header.
+const LZIP_LZMA_PROPERTIES: LzmaProperties = LzmaProperties {
+    lc: 3,
+    lp: 0,
+    pb: 2,
+    dict_size: DICT_SIZE_MIN,
+    uncompressed_size: None,
+    end_marker: true,
+};
+
+/// State of the lzip decoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LzipState {
+    /// Decoding the header of a member
+    Header,
+    /// Decoding the lzma1 data of a member
+    Lzma,
+    /// Decoding the trailer of a member
+    Trailer,
+}
+
+/// Decoder for lzip (.lz) files.
+///
+/// lzip files consist of one or more members, each member contains lzma1 data with a header and a trailer.
+/// The decoder decodes all members and verifies the crc32, data size and member size of each trailer.
+/// Data after the last member that does not start with the lzip magic number is ignored, just like lzip does.
+///
+/// Note: This decoder is not a small data structure (about 30k),
+/// consider placing it in heap or static memory if stack space is limited.
+#[derive(Debug)]
+pub struct LzipDecoder<'a> {
+    /// The raw lzma1 decoder
+    decoder: LzmaDecoder<'a>,
+    /// What do we decode next?
+    state: LzipState,
+    /// Buffer for the header and the trailer
+    buf: [u8; LZIP_TRAILER_SIZE],
+    /// Amount of bytes in `buf`
+    buf_pos: usize,
+    /// Input bytes that the lzma1 decoder buffered past the end of the lzma1 data.
+    pending: [u8; 64],
+    /// Amount of bytes in `pending`
+    pending_len: usize,
+    /// Amount of bytes in `pending` that were already processed
+    pending_pos: usize,
+    /// crc32 of the uncompressed data of the current member
+    crc32: u32,
+    /// Amount of uncompressed data of the current member
+    data_size: u64,
+    /// Size of the current member
+    member_size: u64,
+    /// Amount of members that were fully decoded
+    member_count: u64,
+    /// Did we error or finish and want to be reset?
+    needs_reset: bool,
+}
+
+impl<'a> LzipDecoder<'a> {
+    /// Creates a new lzip decoder that uses a fixed size dictionary.
+    /// The content in the dict slice is irrelevant and will be overwritten.
+    ///
+    /// Decoding fails with `XzError::DictionaryTooLarge` if a member requires a larger dictionary.
+    pub fn with_fixed_size_dict(dict: &'a mut [u8]) -> Self {
+        Self::new(LzmaDecoder::with_fixed_size_dict(
+            LZIP_LZMA_PROPERTIES,
+            dict,
+        ))
+    }
+
+    /// Creates a new lzip decoder that allocates its dictionary on the heap once a member header was decoded.
+    ///
+    /// Decoding fails with `XzError::DictionaryTooLarge` if a member requires a dictionary larger than `max_dict`.
+    #[cfg(feature = "alloc")]
+    #[must_use]
+    pub fn with_alloc_dict_size(max_dict: usize) -> LzipDecoder<'static> {
+        Self::with_alloc_dict(vec::Vec::new(), max_dict)
+    }
+
+    /// Creates a new lzip decoder that uses the given vec as its initial dictionary.
+    /// The vec is replaced by a larger allocation if a member requires it.
+    #[cfg(feature = "alloc")]
+    #[must_use]
+    pub fn with_alloc_dict(initial_dict: vec::Vec<u8>, max_dict: usize) -> LzipDecoder<'static> {
+        LzipDecoder::new(LzmaDecoder::with_alloc_dict(
+            LZIP_LZMA_PROPERTIES,
+            initial_dict,
+            max_dict,
+        ))
+    }
+
+    /// Constructor
+    const fn new(decoder: LzmaDecoder<'a>) -> Self {
+        Self {
+            decoder,
+            state: LzipState::Header,
+            buf: [0; LZIP_TRAILER_SIZE],
+            buf_pos: 0,
+            pending: [0; 64],
+            pending_len: 0,
+            pending_pos: 0,
+            crc32: 0,
+            data_size: 0,
+            member_size: 0,
+            member_count: 0,
+            needs_reset: false,
+        }
+    }
+
+    /// Returns the amount of members that were fully decoded and verified.
+    #[must_use]
+    pub const fn member_count(&self) -> u64 {
+        self.member_count
+    }
+
+    /// Decodes the dictionary size from the coded dictionary size of the member header.
+    fn dict_size(coded: u8) -> Result<usize, XzError> {
+        let exponent = coded & 0x1F;
+        if !(12..=29).contains(&exponent) {
+            return Err(XzError::LzipInvalidDictionarySize(coded));
+        }
+
+        let base = 1usize << exponent;
+        let size = base - (base / 16) * usize::from(coded >> 5);
+        if size < 1 << 12 {
+            //lzip requires at least 4 KiB, only the fraction can push the size below that.
+            return Err(XzError::CorruptedData);
+        }
+
+        Ok(size)
+    }
+
+    /// Reads the next input byte, buffered bytes take precedence.
+    fn next_byte(&mut self, input_data: &[u8], in_pos: &mut usize) -> Option<u8> {
+        if self.pending_pos < self.pending_len {
+            let byte = self.pending[self.pending_pos];
+            self.pending_pos += 1;
+            return Some(byte);
+        }
+
+        let byte = *input_data.get(*in_pos)?;
+        *in_pos += 1;
+        Some(byte)
+    }
+
+    /// Reads bytes into `buf` until it contains `size` bytes. Returns false if more input is needed.
+    fn fill_buf(&mut self, input_data: &[u8], in_pos: &mut usize, size: usize) -> bool {
+        while self.buf_pos < size {
+            let Some(byte) = self.next_byte(input_data, in_pos) else {
+                return false;
+            };
+            self.buf[self.buf_pos] = byte;
+            self.buf_pos += 1;
+        }
+
+        true
+    }
+
+    /// Processes the next block of input data and possibly produces output.
+    ///
+    /// Once all input has been passed to the decoder, call this fn with an empty input slice
+    /// until it returns `XzNextBlockResult::EndOfStream`.
+    /// An empty input slice tells the decoder that the end of the input was reached.
+    /// `XzNextBlockResult::EndOfStream` may also be returned before that if data that is not
+    /// a lzip member follows the last member. That data is not consumed.
+    /// The decoder must be reset before it can decode the next file.
+    ///
+    /// # Errors
+    /// All errors returned by this fn are fatal, and the decoder must be reset afterward.
+    /// `XzError::UnexpectedEndOfInput` is returned if the input ended inside a member.
+    /// If decode is called again when the decoder had a fatal error, then it will cause an Err with `XzError::NeedsReset`.
+    pub fn decode(
+        &mut self,
+        input_data: &[u8],
+        output_data: &mut [u8],
+    ) -> Result<XzNextBlockResult, XzError> {
+        if self.needs_reset {
+            return Err(XzError::NeedsReset);
+        }
+
+        let result = self.decode_inner(input_data, output_data);
+        if !matches!(result, Ok(XzNextBlockResult::NeedMoreData(_, _))) {
+            self.needs_reset = true;
+        }
+
+        result
+    }
+
+    /// Decode without error handling.
+    fn decode_inner(
+        &mut self,
+        input_data: &[u8],
+        output_data: &mut [u8],
+    ) -> Result<XzNextBlockResult, XzError> {
+        let input_finished = input_data.is_empty();
+        let mut in_pos = 0;
+        let mut out_pos = 0;
+
+        loop {
+            match self.state {
+                LzipState::Header => {
+                    if self.buf_pos == 0 && self.member_count > 0 {
+                        let next = if self.pending_pos < self.pending_len {
+                            Some(self.pending[self.pending_pos])
+                        } else {
+                            input_data.get(in_pos).copied()
+                        };
+
+                        match next {
+                            None if input_finished => {
+                                return Ok(XzNextBlockResult::EndOfStream(in_pos, out_pos));
+                            }
+                            Some(byte) if byte != LZIP_MAGIC[0] => {
+                                // Trailing data after the last member.
+                                return Ok(XzNextBlockResult::EndOfStream(in_pos, out_pos));
+                            }
+                            _ => {}
+                        }
+                    }
+
+                    let complete = self.fill_buf(input_data, &mut in_pos, LZIP_HEADER_SIZE);
+                    let magic_len = self.buf_pos.min(LZIP_MAGIC.len());
+                    if self.buf[..magic_len] != LZIP_MAGIC[..magic_len] {
+                        return Err(XzError::StreamHeaderMagicNumberMismatch);
+                    }
+
+                    if !complete {
+                        if input_finished {
+                            return Err(XzError::UnexpectedEndOfInput);
+                        }
+                        return Ok(XzNextBlockResult::NeedMoreData(in_pos, out_pos));
+                    }
+
+                    if self.buf[4] != 1 {
+                        return Err(XzError::LzipUnsupportedVersion(self.buf[4]));
+                    }
+
+                    let mut properties = LZIP_LZMA_PROPERTIES;
+                    properties.dict_size = Self::dict_size(self.buf[5])?;
+                    self.decoder.reset_with_properties(properties);
+                    self.crc32 = 0;
+                    self.data_size = 0;
+                    self.member_size = LZIP_HEADER_SIZE as u64;
+                    self.buf_pos = 0;
+                    self.state = LzipState::Lzma;
+                }
+                LzipState::Lzma => {
+                    let from_pending = self.pending_pos < self.pending_len;
+                    let input = if from_pending {
+                        &self.pending[self.pending_pos..self.pending_len]
+                    } else {
+                        &input_data[in_pos..]
+                    };
+
+                    if input.is_empty() && !input_finished {
+                        return Ok(XzNextBlockResult::NeedMoreData(in_pos, out_pos));
+                    }
+
+                    let result = self.decoder.decode(input, &mut output_data[out_pos..])?;
+                    let produced = &output_data[out_pos..][..result.output_produced()];
+                    self.crc32 = crc32(self.crc32, produced);
+                    self.data_size += produced.len() as u64;
+                    self.member_size += result.input_consumed() as u64;
+                    out_pos += result.output_produced();
+                    if from_pending {
+                        self.pending_pos += result.input_consumed();
+                    } else {
+                        in_pos += result.input_consumed();
+                    }
+
+                    let XzNextBlockResult::EndOfStream(_, _) = result else {
+                        if from_pending && result.made_progress() {
+                            continue;
+                        }
+                        return Ok(XzNextBlockResult::NeedMoreData(in_pos, out_pos));
+                    };
+
+                    // Bytes after the lzma1 data that the lzma1 decoder buffered belong to the trailer.
+                    let trailing = self.decoder.trailing_input();
+                    self.member_size -= trailing.len() as u64;
+                    let remaining = self.pending_len - self.pending_pos;
+                    let mut pending = [0u8; 64];
+                    pending[..trailing.len()].copy_from_slice(trailing);
+                    pending[trailing.len()..][..remaining]
+                        .copy_from_slice(&self.pending[self.pending_pos..self.pending_len]);
+                    self.pending = pending;
+                    self.pending_len = trailing.len() + remaining;
+                    self.pending_pos = 0;
+                    self.state = LzipState::Trailer;
+                }
+                LzipState::Trailer => {
+                    if !self.fill_buf(input_data, &mut in_pos, LZIP_TRAILER_SIZE) {
+                        if input_finished {
+                            return Err(XzError::UnexpectedEndOfInput);
+                        }
+                        return Ok(XzNextBlockResult::NeedMoreData(in_pos, out_pos));
+                    }
+
+                    self.verify_trailer()?;
+                    self.member_count += 1;
+                    self.buf_pos = 0;
+                    self.state = LzipState::Header;
+                }
+            }
+        }
+    }
+
+    /// Verifies the trailer of the current member.
+    fn verify_trailer(&mut self) -> Result<(), XzError> {
+        let mut crc = [0u8; 4];
+        crc.copy_from_slice(&self.buf[0..4]);
+        let crc = u32::from_le_bytes(crc);
+        if crc != self.crc32 {
+            return Err(XzError::LzipCrc32Mismatch(self.crc32, crc));
+        }
+
+        let mut data_size = [0u8; 8];
+        data_size.copy_from_slice(&self.buf[4..12]);
+        let data_size = u64::from_le_bytes(data_size);
+        if data_size != self.data_size {
+            return Err(XzError::LzipDataSizeMismatch(self.data_size, data_size));
+        }
+
+        self.member_size += LZIP_TRAILER_SIZE as u64;
+        let mut member_size = [0u8; 8];
+        member_size.copy_from_slice(&self.buf[12..20]);
+        let member_size = u64::from_le_bytes(member_size);
+        if member_size != self.member_size {
+            return Err(XzError::LzipMemberSizeMismatch(
+                self.member_size,
+                member_size,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Reset the decoder to decode a new lzip file.
+    pub const fn reset(&mut self) {
+        self.state = LzipState::Header;
+        self.buf_pos = 0;
+        self.pending_len = 0;
+        self.pending_pos = 0;
+        self.member_count = 0;
+        self.needs_reset = false;
+        self.decoder.reset();
+    }
+}
@@ -0,0 +1,428 @@
+use crate::decoder::{
+    DecodeResult, XzDictBuffer, XzDictBufferAllocation, XzInOutBuffer, XzLzma2Decoder,
+};
+use crate::{XzError, XzNextBlockResult, DICT_SIZE_MAX, DICT_SIZE_MIN};
+#[cfg(feature = "alloc")]
+use alloc::vec;
+
+/// Size of the header of .lzma files.
+const LZMA_ALONE_HEADER_SIZE: usize = 13;
+
+/// Parameters of a raw lzma1 stream.
+///
+/// Formats that embed raw lzma1 streams (for example ZIP method 14 or 7z) store these
+/// parameters outside the lzma1 data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LzmaProperties {
+    /// Number of literal context bits.
+    pub lc: u8,
+    /// Number of literal position bits.
+    pub lp: u8,
+    /// Number of position bits.
+    pub pb: u8,
+    /// Dictionary size in bytes. Sizes below 4096 are rounded up to 4096.
+    pub dict_size: usize,
+    /// Uncompressed size of the stream. None if unknown,
+    /// in this case the stream must end with the end of payload marker.
+    pub uncompressed_size: Option<u64>,
+    /// May the stream end with the end of payload marker even if the uncompressed size is known?
+    pub end_marker: bool,
+}
+
+impl LzmaProperties {
+    /// Creates the properties from the lc/lp/pb properties byte that is used by most formats
+    /// to store these 3 values. The stream is assumed to be of unknown size and to end with the end of payload marker.
+    ///
+    /// # Errors
+    /// `XzError::LzmaPropertiesTooLarge` if the byte is not a valid lc/lp/pb properties byte (larger than 224).
+    pub const fn from_props_byte(props: u8, dict_size: usize) -> Result<Self, XzError> {
+        if props > 224 {
+            return Err(XzError::LzmaPropertiesTooLarge);
+        }
+
+        Ok(Self {
+            lc: props % 9,
+            lp: (props / 9) % 5,
+            pb: props / 45,
+            dict_size,
+            uncompressed_size: None,
+            end_marker: true,
+        })
+    }
+
+    /// Returns the lc/lp/pb properties byte.
+    ///
+    /// # Errors
+    /// `XzError::LzmaPropertiesTooLarge` if lc is larger than 8 or lp or pb are larger than 4.
+    pub const fn props_byte(&self) -> Result<u8, XzError> {
+        if self.lc > 8 || self.lp > 4 || self.pb > 4 {
+            return Err(XzError::LzmaPropertiesTooLarge);
+        }
+
+        Ok((self.pb * 5 + self.lp) * 9 + self.lc)
+    }
+}
+
+/// Decoder for raw lzma1 streams.
+///
+/// Only lzma properties with lc+lp <= 4 are supported, this is true for the default (lc=3 lp=0 pb=2)
+/// and for all streams created by xz and lzma utils.
+///
+/// Note: This decoder is not a small data structure (about 30k),
+/// consider placing it in heap or static memory if stack space is limited.
+#[derive(Debug)]
+pub struct LzmaDecoder<'a> {
+    /// Dictionary buffer
+    dictionary_buffer: XzDictBuffer<'a>,
+    /// The lzma decoder state
+    lzma: XzLzma2Decoder,
+    /// Parameters of the lzma1 stream.
+    properties: LzmaProperties,
+    /// Was the lzma decoder initialized for the current stream?
+    initialized: bool,
+    /// Did we error or finish and want to be reset?
+    needs_reset: bool,
+}
+
+impl<'a> LzmaDecoder<'a> {
+    /// Creates a new raw lzma1 decoder that uses a fixed size dictionary.
+    /// The content in the dict slice is irrelevant and will be overwritten.
+    ///
+    /// Decoding fails with `XzError::DictionaryTooLarge` if the dict slice is smaller than the dictionary size.
+    pub fn with_fixed_size_dict(properties: LzmaProperties, mut dict: &'a mut [u8]) -> Self {
+        if dict.len() > DICT_SIZE_MAX {
+            dict = &mut dict[..DICT_SIZE_MAX];
+        }
+
+        Self::new(
+            properties,
+            XzDictBuffer::new(XzDictBufferAllocation::Fixed(dict)),
+        )
+    }
+
+    /// Creates a new raw lzma1 decoder that allocates its dictionary on the heap once decoding starts.
+    ///
+    /// Decoding fails with `XzError::DictionaryTooLarge` if the dictionary size is larger than `max_dict`.
+    #[cfg(feature = "alloc")]
+    #[must_use]
+    pub fn with_alloc_dict_size(
+        properties: LzmaProperties,
+        max_dict: usize,
+    ) -> LzmaDecoder<'static> {
+        Self::with_alloc_dict(properties, vec::Vec::new(), max_dict)
+    }
+
+    /// Creates a new raw lzma1 decoder that uses the given vec as its initial dictionary.
+    /// The vec is replaced by a larger allocation if the dictionary size requires it.
+    #[cfg(feature = "alloc")]
+    #[must_use]
+    pub fn with_alloc_dict(
+        properties: LzmaProperties,
+        mut initial_dict: vec::Vec<u8>,
+        max_dict: usize,
+    ) -> LzmaDecoder<'static> {
+        initial_dict.truncate(DICT_SIZE_MAX);
+
+        LzmaDecoder::new(
+            properties,
+            XzDictBuffer::new(XzDictBufferAllocation::Alloc(
+                initial_dict,
+                max_dict.min(DICT_SIZE_MAX),
+            )),
+        )
+    }
+
+    /// Constructor
+    const fn new(properties: LzmaProperties, dictionary_buffer: XzDictBuffer<'a>) -> Self {
+        Self {
+            dictionary_buffer,
+            lzma: XzLzma2Decoder::new(),
+            properties,
+            initialized: false,
+            needs_reset: false,
+        }
+    }
+
+    /// Returns the parameters of the lzma1 stream.
+    #[must_use]
+    pub const fn properties(&self) -> &LzmaProperties {
+        &self.properties
+    }
+
+    /// Sets up the lzma decoder for the current properties.
+    fn initialize(&mut self) -> Result<(), XzError> {
+        let mut dict_size = self.properties.dict_size;
+        let uncompressed = self.properties.uncompressed_size.unwrap_or(u64::MAX);
+        if let Ok(size) = usize::try_from(uncompressed) {
+            // There is no point in allocating more dictionary than there is data.
+            dict_size = dict_size.min(size);
+        }
+
+        self.lzma.reset_lzma1(
+            self.properties.props_byte()?,
+            dict_size.max(DICT_SIZE_MIN),
+            uncompressed,
+            self.properties.end_marker,
+            &mut self.dictionary_buffer,
+        )
+    }
+
+    /// Processes the next block of input data and possibly produces output.
+    ///
+    /// Once all input has been passed to the decoder, call this fn with an empty input slice
+    /// until it returns `XzNextBlockResult::EndOfStream`.
+    /// An empty input slice tells the decoder that the end of the input was reached.
+    /// Streams of known size without end of payload marker may end before that.
+    /// The decoder must be reset before it can decode the next stream.
+    ///
+    /// # Errors
+    /// All errors returned by this fn are fatal, and the decoder must be reset afterward.
+    /// `XzError::UnexpectedEndOfInput` is returned if the input ended before the end of the lzma data.
+    /// If decode is called again when the decoder had a fatal error, then it will cause an Err with `XzError::NeedsReset`.
+    pub fn decode(
+        &mut self,
+        input_data: &[u8],
+        output_data: &mut [u8],
+    ) -> Result<XzNextBlockResult, XzError> {
+        if self.needs_reset {
+            return Err(XzError::NeedsReset);
+        }
+
+        self.decode_inner(input_data, output_data)
+            .inspect_err(|_| self.needs_reset = true)
+    }
+
+    /// Decode without error handling.
+    fn decode_inner(
+        &mut self,
+        input_data: &[u8],
+        output_data: &mut [u8],
+    ) -> Result<XzNextBlockResult, XzError> {
+        if !self.initialized {
+            self.initialize()?;
+            self.initialized = true;
+        }
+
+        let input_finished = input_data.is_empty();
+        let mut buf = XzInOutBuffer::new(input_data, output_data);
+        match self
+            .lzma
+            .xz_dec_lzma1_run(&mut buf, &mut self.dictionary_buffer, input_finished)?
+        {
+            DecodeResult::NeedMoreData => {
+                if input_finished && buf.output_position() == 0 && buf.output_len() > 0 {
+                    return Err(XzError::UnexpectedEndOfInput);
+                }
+
+                Ok(XzNextBlockResult::NeedMoreData(
+                    buf.input_position(),
+                    buf.output_position(),
+                ))
+            }
+            DecodeResult::EndOfDataStructure => {
+                self.needs_reset = true;
+                Ok(XzNextBlockResult::EndOfStream(
+                    buf.input_position(),
+                    buf.output_position(),
+                ))
+            }
+        }
+    }
+
+    /// Returns input bytes that were reported as consumed by `decode` but are located after the end of the lzma1 stream.
+    ///
+    /// The decoder buffers a few bytes of input internally. If the lzma1 stream is followed by other data
+    /// (for example a trailer of the surrounding format), then the start of that data may be returned here
+    /// once decode returned `XzNextBlockResult::EndOfStream`.
+    #[must_use]
+    pub fn trailing_input(&self) -> &[u8] {
+        if !self.needs_reset {
+            return &[];
+        }
+
+        self.lzma.lzma1_trailing_input()
+    }
+
+    /// Reset the decoder to decode a new lzma1 stream with the same properties.
+    pub const fn reset(&mut self) {
+        self.initialized = false;
+        self.needs_reset = false;
+    }
+
+    /// Reset the decoder to decode a new lzma1 stream with different properties.
+    pub const fn reset_with_properties(&mut self, properties: LzmaProperties) {
+        self.properties = properties;
+        self.reset();
+    }
+}
+
+/// Decoder for legacy .lzma files. (`LZMA_Alone` format)
+///
+/// These files consist of a 13-byte header followed by lzma1 compressed data.
+/// The header contains the lc/lp/pb properties, the dictionary size and the uncompressed size.
+/// If the uncompressed size is unknown then the data must end with the end of payload marker,
+/// otherwise the end of payload marker is optional.
+///
+/// Only lzma properties with lc+lp <= 4 are supported, this is true for all files created by xz and lzma utils.
+///
+/// Note: This decoder is not a small data structure (about 30k),
+/// consider placing it in heap or static memory if stack space is limited.
+#[derive(Debug)]
+pub struct LzmaAloneDecoder<'a> {
+    /// The raw lzma1 decoder
+    decoder: LzmaDecoder<'a>,
+    /// Buffer for the header
+    header: [u8; LZMA_ALONE_HEADER_SIZE],
+    /// Amount of bytes in the header buffer
+    header_pos: usize,
+    /// Did we error and want to be reset?
+    needs_reset: bool,
+}
+
+impl<'a> LzmaAloneDecoder<'a> {
+    /// Creates a new .lzma decoder that uses a fixed size dictionary.
+    /// The content in the dict slice is irrelevant and will be overwritten.
+    ///
+    /// Decoding fails with `XzError::DictionaryTooLarge` if the file requires a larger dictionary.
+    pub fn with_fixed_size_dict(dict: &'a mut [u8]) -> Self {
+        Self::new(LzmaDecoder::with_fixed_size_dict(
+            Self::PLACEHOLDER_PROPERTIES,
+            dict,
+        ))
+    }
+
+    /// Creates a new .lzma decoder that allocates its dictionary on the heap once the header was decoded.
+    ///
+    /// Decoding fails with `XzError::DictionaryTooLarge` if the file requires a dictionary larger than `max_dict`.
+    #[cfg(feature = "alloc")]
+    #[must_use]
+    pub fn with_alloc_dict_size(max_dict: usize) -> LzmaAloneDecoder<'static> {
+        Self::with_alloc_dict(vec::Vec::new(), max_dict)
+    }
+
+    /// Creates a new .lzma decoder that uses the given vec as its initial dictionary.
+    /// The vec is replaced by a larger allocation if the file requires it.
+    #[cfg(feature = "alloc")]
+    #[must_use]
+    pub fn with_alloc_dict(
+        initial_dict: vec::Vec<u8>,
+        max_dict: usize,
+    ) -> LzmaAloneDecoder<'static> {
+        LzmaAloneDecoder::new(LzmaDecoder::with_alloc_dict(
+            Self::PLACEHOLDER_PROPERTIES,
+            initial_dict,
+            max_dict,
+        ))
+    }
+
+    /// Properties of the inner decoder until the header was decoded.
+    const PLACEHOLDER_PROPERTIES: LzmaProperties = LzmaProperties {
+        lc: 3,
+        lp: 0,
+        pb: 2,
+        dict_size: DICT_SIZE_MIN,
+        uncompressed_size: None,
+        end_marker: true,
+    };
+
+    /// Constructor
+    const fn new(decoder: LzmaDecoder<'a>) -> Self {
+        Self {
+            decoder,
+            header: [0; LZMA_ALONE_HEADER_SIZE],
+            header_pos: 0,
+            needs_reset: false,
+        }
+    }
+
+    /// Returns the uncompressed size stored in the header.
+    /// Returns None if the header was not decoded yet or if the size is unknown.
+    #[must_use]
+    pub const fn uncompressed_size(&self) -> Option<u64> {
+        if self.header_pos < LZMA_ALONE_HEADER_SIZE {
+            return None;
+        }
+
+        self.decoder.properties().uncompressed_size
+    }
+
+    /// Parses the header and sets up the lzma decoder.
+    fn decode_header(&mut self) -> Result<(), XzError> {
+        let mut dict_size = [0u8; 4];
+        dict_size.copy_from_slice(&self.header[1..5]);
+        let dict_size = u32::from_le_bytes(dict_size) as usize;
+
+        let mut uncompressed = [0u8; 8];
+        uncompressed.copy_from_slice(&self.header[5..13]);
+        let uncompressed = u64::from_le_bytes(uncompressed);
+
+        let mut properties = LzmaProperties::from_props_byte(self.header[0], dict_size)?;
+        if uncompressed != u64::MAX {
+            properties.uncompressed_size = Some(uncompressed);
+        }
+
+        self.decoder.reset_with_properties(properties);
+        Ok(())
+    }
+
+    /// Processes the next block of input data and possibly produces output.
+    ///
+    /// Once all input has been passed to the decoder, call this fn with an empty input slice
+    /// until it returns `XzNextBlockResult::EndOfStream`.
+    /// An empty input slice tells the decoder that the end of the input was reached.
+    /// The decoder must be reset before it can decode the next file.
+    ///
+    /// # Errors
+    /// All errors returned by this fn are fatal, and the decoder must be reset afterward.
+    /// `XzError::UnexpectedEndOfInput` is returned if the input ended before the end of the lzma data.
+    /// If decode is called again when the decoder had a fatal error, then it will cause an Err with `XzError::NeedsReset`.
+    pub fn decode(
+        &mut self,
+        input_data: &[u8],
+        output_data: &mut [u8],
+    ) -> Result<XzNextBlockResult, XzError> {
+        if self.needs_reset {
+            return Err(XzError::NeedsReset);
+        }
+
+        if self.header_pos < LZMA_ALONE_HEADER_SIZE {
+            let count = (LZMA_ALONE_HEADER_SIZE - self.header_pos).min(input_data.len());
+            self.header[self.header_pos..][..count].copy_from_slice(&input_data[..count]);
+            self.header_pos += count;
+            if self.header_pos < LZMA_ALONE_HEADER_SIZE {
+                if input_data.is_empty() {
+                    self.needs_reset = true;
+                    return Err(XzError::UnexpectedEndOfInput);
+                }
+                return Ok(XzNextBlockResult::NeedMoreData(count, 0));
+            }
+
+            self.decode_header()
+                .inspect_err(|_| self.needs_reset = true)?;
+
+            if count == input_data.len() {
+                return Ok(XzNextBlockResult::NeedMoreData(count, 0));
+            }
+
+            return Ok(
+                match self.decoder.decode(&input_data[count..], output_data)? {
+                    XzNextBlockResult::NeedMoreData(input, output) => {
+                        XzNextBlockResult::NeedMoreData(input + count, output)
+                    }
+                    XzNextBlockResult::EndOfStream(input, output) => {
+                        XzNextBlockResult::EndOfStream(input + count, output)
+                    }
+                },
+            );
+        }
+
+        self.decoder.decode(input_data, output_data)
+    }
+
+    /// Reset the decoder to decode a new .lzma file.
+    pub const fn reset(&mut self) {
+        self.header_pos = 0;
+        self.needs_reset = false;
+        self.decoder.reset();
+    }
+}
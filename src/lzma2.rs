@@ -0,0 +1,165 @@
+use crate::decoder::{
+    DecodeResult, XzDictBuffer, XzDictBufferAllocation, XzInOutBuffer, XzLzma2Decoder,
+};
+use crate::{XzError, XzNextBlockResult, DICT_SIZE_MAX, DICT_SIZE_MIN};
+#[cfg(feature = "alloc")]
+use alloc::vec;
+
+/// Decoder for raw LZMA2 streams that are not wrapped in the .xz container.
+///
+/// Raw LZMA2 streams are embedded in some other formats (for example 7z).
+/// Those formats store the dictionary size of the LZMA2 stream themselves,
+/// either as size in bytes or as the LZMA2 dictionary properties byte.
+/// Use `dict_size_from_props` to convert the properties byte.
+///
+/// Note: This decoder is not a small data structure (about 30k),
+/// consider placing it in heap or static memory if stack space is limited.
+#[derive(Debug)]
+pub struct Lzma2Decoder<'a> {
+    /// Dictionary buffer
+    dictionary_buffer: XzDictBuffer<'a>,
+    /// The lzma2 decoder state
+    lzma2: XzLzma2Decoder,
+    /// Dictionary size of the lzma2 stream.
+    dict_size: usize,
+    /// Was the lzma2 decoder initialized for the current stream?
+    initialized: bool,
+    /// Did we error or finish and want to be reset?
+    needs_reset: bool,
+}
+
+impl<'a> Lzma2Decoder<'a> {
+    /// Creates a new raw lzma2 decoder that uses a fixed size dictionary.
+    /// The content in the dict slice is irrelevant and will be overwritten.
+    ///
+    /// `dict_size` is the dictionary size of the lzma2 stream, decoding will fail with
+    /// `XzError::DictionaryTooLarge` if the dict slice is smaller than that.
+    pub fn with_fixed_size_dict(dict_size: usize, mut dict: &'a mut [u8]) -> Self {
+        if dict.len() > DICT_SIZE_MAX {
+            dict = &mut dict[..DICT_SIZE_MAX];
+        }
+
+        Self::new(
+            dict_size,
+            XzDictBuffer::new(XzDictBufferAllocation::Fixed(dict)),
+        )
+    }
+
+    /// Creates a new raw lzma2 decoder that allocates its dictionary on the heap once decoding starts.
+    ///
+    /// `dict_size` is the dictionary size of the lzma2 stream, decoding will fail with
+    /// `XzError::DictionaryTooLarge` if it is larger than `max_dict`.
+    #[cfg(feature = "alloc")]
+    #[must_use]
+    pub fn with_alloc_dict_size(dict_size: usize, max_dict: usize) -> Lzma2Decoder<'static> {
+        Self::with_alloc_dict(vec::Vec::new(), dict_size, max_dict)
+    }
+
+    /// Creates a new raw lzma2 decoder that uses the given vec as its initial dictionary.
+    /// The vec is replaced by a larger allocation if `dict_size` requires it.
+    #[cfg(feature = "alloc")]
+    #[must_use]
+    pub fn with_alloc_dict(
+        mut initial_dict: vec::Vec<u8>,
+        dict_size: usize,
+        max_dict: usize,
+    ) -> Lzma2Decoder<'static> {
+        initial_dict.truncate(DICT_SIZE_MAX);
+
+        Lzma2Decoder::new(
+            dict_size,
+            XzDictBuffer::new(XzDictBufferAllocation::Alloc(
+                initial_dict,
+                max_dict.min(DICT_SIZE_MAX),
+            )),
+        )
+    }
+
+    /// Constructor
+    const fn new(dict_size: usize, dictionary_buffer: XzDictBuffer<'a>) -> Self {
+        Self {
+            dictionary_buffer,
+            lzma2: XzLzma2Decoder::new(),
+            dict_size: Self::effective_dict_size(dict_size),
+            initialized: false,
+            needs_reset: false,
+        }
+    }
+
+    /// Decodes the dictionary size from the lzma2 dictionary properties byte.
+    /// This is the same byte that is stored in the filter properties of .xz files.
+    ///
+    /// # Errors
+    /// `XzError::UnsupportedLzmaProperties` if the byte is not a valid dictionary size (larger than 39).
+    pub fn dict_size_from_props(props: u8) -> Result<usize, XzError> {
+        XzLzma2Decoder::dict_size_from_props(props)
+    }
+
+    /// Dictionaries smaller than 4096 bytes are rounded up just like the reference implementation does.
+    const fn effective_dict_size(dict_size: usize) -> usize {
+        if dict_size < DICT_SIZE_MIN {
+            return DICT_SIZE_MIN;
+        }
+
+        dict_size
+    }
+
+    /// Processes the next block of input data and possibly produces output.
+    ///
+    /// Returns `XzNextBlockResult::EndOfStream` once the lzma2 end marker was decoded.
+    /// The decoder must be reset before it can decode the next lzma2 stream.
+    ///
+    /// If neither input was consumed nor output was produced, then the decoder needs more input
+    /// or more space in the output buffer to make progress.
+    ///
+    /// # Errors
+    /// All errors returned by this fn are fatal, and the decoder must be reset afterward.
+    /// If decode is called again when the decoder had a fatal error, then it will cause an Err with `XzError::NeedsReset`.
+    pub fn decode(
+        &mut self,
+        input_data: &[u8],
+        output_data: &mut [u8],
+    ) -> Result<XzNextBlockResult, XzError> {
+        if self.needs_reset {
+            return Err(XzError::NeedsReset);
+        }
+
+        if !self.initialized {
+            self.lzma2
+                .reset_dict_size(self.dict_size, &mut self.dictionary_buffer)
+                .inspect_err(|_| self.needs_reset = true)?;
+            self.initialized = true;
+        }
+
+        let mut buf = XzInOutBuffer::new(input_data, output_data);
+        match self
+            .lzma2
+            .xz_dec_lzma2_run(&mut buf, &mut self.dictionary_buffer)
+            .inspect_err(|_| self.needs_reset = true)?
+        {
+            DecodeResult::NeedMoreData => Ok(XzNextBlockResult::NeedMoreData(
+                buf.input_position(),
+                buf.output_position(),
+            )),
+            DecodeResult::EndOfDataStructure => {
+                self.needs_reset = true;
+                Ok(XzNextBlockResult::EndOfStream(
+                    buf.input_position(),
+                    buf.output_position(),
+                ))
+            }
+        }
+    }
+
+    /// Reset the decoder to decode a new lzma2 stream with the same dictionary size.
+    pub const fn reset(&mut self) {
+        self.initialized = false;
+        self.needs_reset = false;
+    }
+
+    /// Reset the decoder to decode a new lzma2 stream with a different dictionary size.
+    pub const fn reset_with_dict_size(&mut self, dict_size: usize) {
+        self.dict_size = Self::effective_dict_size(dict_size);
+        self.reset();
+    }
+}
@@ -0,0 +1,920 @@
+use crate::clamp::{clamp_u32_to_u16, clamp_u32_to_u8, clamp_u64_to_u32, clamp_us_to_u32};
+use crate::decoder::LzmaState;
+use crate::{
+    XzError, DICT_SIZE_MIN, DICT_SIZE_PROFILE_0, DICT_SIZE_PROFILE_1, DICT_SIZE_PROFILE_2,
+    DICT_SIZE_PROFILE_3, DICT_SIZE_PROFILE_4, DICT_SIZE_PROFILE_5, DICT_SIZE_PROFILE_6,
+    DICT_SIZE_PROFILE_7, DICT_SIZE_PROFILE_8, DICT_SIZE_PROFILE_9,
+};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Shortest match that can be encoded.
+const MATCH_LEN_MIN: usize = 2;
+
+/// Longest match that can be encoded.
+const MATCH_LEN_MAX: usize = 273;
+
+/// Maximum amount of uncompressed bytes in a compressed lzma2 chunk.
+const CHUNK_UNCOMPRESSED_MAX: usize = 1 << 21;
+
+/// Maximum amount of compressed bytes in a compressed lzma2 chunk.
+const CHUNK_COMPRESSED_MAX: usize = 1 << 16;
+
+/// Maximum amount of bytes in an uncompressed lzma2 chunk.
+const UNCOMPRESSED_CHUNK_MAX: usize = 1 << 16;
+
+/// Upper bound of the bytes the range encoder emits for a single symbol.
+const SYMBOL_SIZE_MAX: usize = 64;
+
+/// Amount of bytes that must be buffered in front of the current position before a chunk is encoded,
+/// unless the encoder is finishing.
+const LOOKAHEAD: usize = CHUNK_UNCOMPRESSED_MAX + MATCH_LEN_MAX;
+
+/// Largest dictionary the encoder supports. All positions in the window must fit into an u32.
+const ENCODER_DICT_SIZE_MAX: usize = 1536 * 1024 * 1024;
+
+/// Amount of matches the match finder remembers for a single position.
+const MATCHES_MAX: usize = 16;
+
+/// Initial value of all probabilities.
+const PROB_INIT: u16 = 1024;
+
+/// Options of the lzma2 encoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Lzma2EncoderOptions {
+    /// Dictionary size in bytes. Decoders need a dictionary of at least this size.
+    /// Sizes below 4096 are rounded up to 4096, at most 1.5GiB is supported.
+    pub dict_size: usize,
+    /// Number of literal context bits.
+    pub lc: u8,
+    /// Number of literal position bits. lc + lp must not be larger than 4.
+    pub lp: u8,
+    /// Number of position bits.
+    pub pb: u8,
+    /// Matches of at least this length are taken without looking for longer ones. (2-273)
+    pub nice_len: usize,
+    /// Maximum amount of earlier positions the match finder checks for each position.
+    pub depth: u32,
+}
+
+impl Lzma2EncoderOptions {
+    /// Returns the options of the given preset level.
+    /// The dictionary size of each level is the corresponding `DICT_SIZE_PROFILE_*` constant.
+    /// Levels above 9 are treated as level 9.
+    #[must_use]
+    pub const fn preset(level: u8) -> Self {
+        let (dict_size, nice_len, depth) = match level {
+            0 => (DICT_SIZE_PROFILE_0, 32, 4),
+            1 => (DICT_SIZE_PROFILE_1, 32, 8),
+            2 => (DICT_SIZE_PROFILE_2, 48, 16),
+            3 => (DICT_SIZE_PROFILE_3, 64, 24),
+            4 => (DICT_SIZE_PROFILE_4, 64, 32),
+            5 => (DICT_SIZE_PROFILE_5, 96, 48),
+            6 => (DICT_SIZE_PROFILE_6, 128, 64),
+            7 => (DICT_SIZE_PROFILE_7, 128, 96),
+            8 => (DICT_SIZE_PROFILE_8, 192, 128),
+            _ => (DICT_SIZE_PROFILE_9, 273, 192),
+        };
+
+        Self {
+            dict_size,
+            lc: 3,
+            lp: 0,
+            pb: 2,
+            nice_len,
+            depth,
+        }
+    }
+}
+
+impl Default for Lzma2EncoderOptions {
+    fn default() -> Self {
+        Self::preset(6)
+    }
+}
+
+/// Range encoder.
+#[derive(Debug, Clone)]
+struct RcEncoder {
+    /// Low end of the range. Bit 32 is the carry.
+    low: u64,
+    /// Size of the range.
+    range: u32,
+    /// Byte that is held back because a carry may still change it.
+    cache: u8,
+    /// Amount of held back bytes, the cache followed by 0xFF bytes.
+    cache_size: usize,
+    /// Bytes that were emitted.
+    out: Vec<u8>,
+}
+
+impl RcEncoder {
+    /// Constructor
+    const fn new() -> Self {
+        Self {
+            low: 0,
+            range: u32::MAX,
+            cache: 0,
+            cache_size: 1,
+            out: Vec::new(),
+        }
+    }
+
+    /// Reset the encoder to start a new range coded chunk.
+    fn reset(&mut self) {
+        self.low = 0;
+        self.range = u32::MAX;
+        self.cache = 0;
+        self.cache_size = 1;
+        self.out.clear();
+    }
+
+    /// Emit the highest byte of low once it can no longer be changed by a carry.
+    fn shift_low(&mut self) {
+        if self.low < 0xFF00_0000 || self.low >= 1 << 32 {
+            let carry = clamp_u32_to_u8(clamp_u64_to_u32(self.low >> 32));
+            let mut byte = self.cache;
+            while self.cache_size > 0 {
+                self.out.push(byte.wrapping_add(carry));
+                byte = 0xFF;
+                self.cache_size -= 1;
+            }
+            self.cache = clamp_u32_to_u8(clamp_u64_to_u32(self.low) >> 24);
+        }
+
+        self.cache_size += 1;
+        self.low = (self.low & 0x00FF_FFFF) << 8;
+    }
+
+    /// Keep the range large enough.
+    fn normalize(&mut self) {
+        while self.range < 1 << 24 {
+            self.range <<= 8;
+            self.shift_low();
+        }
+    }
+
+    /// Encode one bit.
+    fn bit(&mut self, prob: &mut u16, bit: u32) {
+        let p = u32::from(*prob);
+        let bound = (self.range >> 11) * p;
+        if bit == 0 {
+            self.range = bound;
+            *prob = clamp_u32_to_u16(p + (((1 << 11) - p) >> 5));
+        } else {
+            self.low += u64::from(bound);
+            self.range -= bound;
+            *prob = clamp_u32_to_u16(p - (p >> 5));
+        }
+        self.normalize();
+    }
+
+    /// Encode a bittree starting from the most significant bit.
+    fn bittree(&mut self, probs: &mut [u16], bits: u32, value: u32) {
+        let mut symbol = 1;
+        for i in (0..bits).rev() {
+            let bit = (value >> i) & 1;
+            self.bit(&mut probs[symbol], bit);
+            symbol = (symbol << 1) | bit as usize;
+        }
+    }
+
+    /// Encode a bittree starting from the least significant bit.
+    fn bittree_reverse(&mut self, probs: &mut [u16], bits: u32, value: u32) {
+        let mut symbol = 1;
+        for i in 0..bits {
+            let bit = (value >> i) & 1;
+            self.bit(&mut probs[symbol], bit);
+            symbol = (symbol << 1) | bit as usize;
+        }
+    }
+
+    /// Encode direct bits (fixed fifty-fifty probability)
+    fn direct(&mut self, value: u32, bits: u32) {
+        for i in (0..bits).rev() {
+            self.range >>= 1;
+            if (value >> i) & 1 != 0 {
+                self.low += u64::from(self.range);
+            }
+            self.normalize();
+        }
+    }
+
+    /// Amount of bytes the chunk has once it is flushed.
+    fn pending(&self) -> usize {
+        self.out.len() + self.cache_size + 4
+    }
+
+    /// Emit all bytes that are still held back.
+    fn flush(&mut self) {
+        for _ in 0..5 {
+            self.shift_low();
+        }
+    }
+}
+
+/// LZMA Length encoder
+#[derive(Debug, Clone)]
+struct LzmaLenEncoder {
+    /// Probability of match length being at least 10
+    choice: u16,
+    /// Probability of match length being at least 18
+    choice2: u16,
+    /// Probabilities for match lengths 2-9
+    low: [[u16; 8]; 16],
+    /// Probabilities for match lengths 10-17
+    mid: [[u16; 8]; 16],
+    /// Probabilities for match lengths 18-273
+    high: [u16; 256],
+}
+
+impl LzmaLenEncoder {
+    /// Constructor
+    const fn new() -> Self {
+        Self {
+            choice: PROB_INIT,
+            choice2: PROB_INIT,
+            low: [[PROB_INIT; 8]; 16],
+            mid: [[PROB_INIT; 8]; 16],
+            high: [PROB_INIT; 256],
+        }
+    }
+
+    /// Encode the length of a match.
+    fn encode(&mut self, rc: &mut RcEncoder, len: usize, pos_state: usize) {
+        let len = clamp_us_to_u32(len - MATCH_LEN_MIN);
+        if len < 8 {
+            rc.bit(&mut self.choice, 0);
+            rc.bittree(&mut self.low[pos_state], 3, len);
+        } else if len < 16 {
+            rc.bit(&mut self.choice, 1);
+            rc.bit(&mut self.choice2, 0);
+            rc.bittree(&mut self.mid[pos_state], 3, len - 8);
+        } else {
+            rc.bit(&mut self.choice, 1);
+            rc.bit(&mut self.choice2, 1);
+            rc.bittree(&mut self.high, 8, len - 16);
+        }
+    }
+}
+
+/// Probabilities and recent distances, the same model the decoder uses.
+#[derive(Debug, Clone)]
+struct LzmaEncoderState {
+    /// The 4 most recent match distances minus one, latest first.
+    reps: [usize; 4],
+    /// Types of the most recently encoded symbols
+    state: LzmaState,
+    /// If 1, it's a match. Otherwise, it's a single 8-bit literal.
+    is_match: [u16; 192],
+    /// Same layout as in the decoder.
+    is_rep: [u16; 48],
+    /// If 1, the repeated match has length of one byte.
+    is_rep0_long: [u16; 192],
+    /// Same layout as in the decoder.
+    dist_slot: [u16; 386],
+    /// length of a normal match
+    match_len: LzmaLenEncoder,
+    /// length of a repeated match
+    rep_len: LzmaLenEncoder,
+    /// probabilities of literals, 0x300 for each literal coder.
+    literal: Vec<u16>,
+}
+
+impl LzmaEncoderState {
+    /// Constructor
+    fn new(literal_coders: usize) -> Self {
+        Self {
+            reps: [0; 4],
+            state: LzmaState::LitLit,
+            is_match: [PROB_INIT; 192],
+            is_rep: [PROB_INIT; 48],
+            is_rep0_long: [PROB_INIT; 192],
+            dist_slot: [PROB_INIT; 386],
+            match_len: LzmaLenEncoder::new(),
+            rep_len: LzmaLenEncoder::new(),
+            literal: vec![PROB_INIT; 0x300 * literal_coders],
+        }
+    }
+
+    /// Resets the state, the decoder does the same for chunks that reset the state.
+    fn reset(&mut self) {
+        self.reps = [0; 4];
+        self.state = LzmaState::LitLit;
+        self.is_match = [PROB_INIT; 192];
+        self.is_rep = [PROB_INIT; 48];
+        self.is_rep0_long = [PROB_INIT; 192];
+        self.dist_slot = [PROB_INIT; 386];
+        self.match_len = LzmaLenEncoder::new();
+        self.rep_len = LzmaLenEncoder::new();
+        self.literal.fill(PROB_INIT);
+    }
+}
+
+/// A match found by the match finder.
+#[derive(Debug, Clone, Copy, Default)]
+struct Match {
+    /// Length of the match
+    len: usize,
+    /// Distance of the match minus one.
+    dist: usize,
+}
+
+/// Matches of a position, sorted by length.
+#[derive(Debug, Clone, Copy)]
+struct Matches {
+    /// Amount of valid entries in matches.
+    count: usize,
+    /// Longer matches are further back.
+    matches: [Match; MATCHES_MAX],
+}
+
+impl Matches {
+    /// Constructor
+    const fn new() -> Self {
+        Self {
+            count: 0,
+            matches: [Match { len: 0, dist: 0 }; MATCHES_MAX],
+        }
+    }
+
+    /// Add a match that is longer than all previous ones, dropping the shortest one if needed.
+    fn push(&mut self, m: Match) {
+        if self.count == MATCHES_MAX {
+            self.matches.copy_within(1.., 0);
+            self.count -= 1;
+        }
+        self.matches[self.count] = m;
+        self.count += 1;
+    }
+
+    /// The longest match, or a match of length 0.
+    fn longest(&self) -> Match {
+        self.count
+            .checked_sub(1)
+            .map_or_else(Match::default, |i| self.matches[i])
+    }
+}
+
+/// A symbol chosen by the parser.
+#[derive(Debug, Clone, Copy)]
+enum Symbol {
+    /// A single byte.
+    Literal,
+    /// Repeat a match with the distance of one of the 4 most recent matches.
+    Rep(usize, usize), //Index, Length
+    /// A match with a new distance.
+    Match(usize, usize), //Distance minus one, Length
+}
+
+/// Is the distance `big` so much larger than `small` that a match that is one byte shorter is preferable?
+const fn change_pair(small: usize, big: usize) -> bool {
+    (big >> 7) > small
+}
+
+/// Returns the distance slot of a distance minus one.
+const fn dist_slot(dist: u32) -> u32 {
+    if dist < 4 {
+        return dist;
+    }
+
+    let bits = dist.ilog2();
+    (bits << 1) | ((dist >> (bits - 1)) & 1)
+}
+
+/// Encoder for raw LZMA2 streams.
+///
+/// The output is a sequence of lzma2 chunks followed by the end marker.
+/// It can be decoded by `Lzma2Decoder` with a dictionary of `dict_size` bytes
+/// or embedded in an .xz block with `dict_size_props` as the filter properties.
+///
+/// Note: This encoder allocates a window of up to 2 times the dictionary size
+/// and a match finder that needs about 4 bytes per byte of the window.
+#[derive(Debug, Clone)]
+pub struct Lzma2Encoder {
+    /// The options after validation.
+    options: Lzma2EncoderOptions,
+    /// The most recent input. Everything before pos was already encoded.
+    window: Vec<u8>,
+    /// Amount of bytes that were dropped from the front of the window since the stream started.
+    window_offset: u64,
+    /// Position in the window of the next byte to encode.
+    pos: usize,
+    /// Hash of 4 bytes -> most recent position in the window with that hash plus one. 0 is empty.
+    head: Vec<u32>,
+    /// Position in the window -> previous position with the same hash plus one. 0 is empty.
+    chain: Vec<u32>,
+    /// Amount of bits the hash is shifted right by.
+    hash_shift: u32,
+    /// Matches of the position after pos, if they were already searched.
+    next: Option<Matches>,
+    /// The lzma probability model.
+    lzma: LzmaEncoderState,
+    /// The range encoder of the current chunk.
+    rc: RcEncoder,
+    /// Must the next chunk reset the dictionary?
+    need_dict_reset: bool,
+    /// Must the next compressed chunk contain the properties?
+    need_props: bool,
+    /// Must the next compressed chunk reset the state?
+    need_state_reset: bool,
+}
+
+impl Lzma2Encoder {
+    /// Creates a new lzma2 encoder.
+    ///
+    /// `nice_len` is clamped to 2-273 and a `depth` of 0 is treated as 1.
+    ///
+    /// # Errors
+    /// `XzError::LzmaPropertiesInvalid` if lc, lp or pb are larger than 4 or lc + lp is larger than 4.
+    /// `XzError::DictionaryTooLarge` if the dictionary size is larger than 1.5GiB.
+    pub fn new(options: &Lzma2EncoderOptions) -> Result<Self, XzError> {
+        if options.lc > 4 || options.lp > 4 || options.pb > 4 || options.lc + options.lp > 4 {
+            return Err(XzError::LzmaPropertiesInvalid);
+        }
+        if options.dict_size > ENCODER_DICT_SIZE_MAX {
+            return Err(XzError::DictionaryTooLarge(options.dict_size as u64));
+        }
+
+        let options = Lzma2EncoderOptions {
+            dict_size: options.dict_size.max(DICT_SIZE_MIN),
+            nice_len: options.nice_len.clamp(MATCH_LEN_MIN, MATCH_LEN_MAX),
+            depth: options.depth.max(1),
+            ..*options
+        };
+        let hash_bits = options.dict_size.ilog2().clamp(16, 24);
+
+        Ok(Self {
+            options,
+            window: Vec::new(),
+            window_offset: 0,
+            pos: 0,
+            head: vec![0; 1 << hash_bits],
+            chain: Vec::new(),
+            hash_shift: 32 - hash_bits,
+            next: None,
+            lzma: LzmaEncoderState::new(1 << (options.lc + options.lp)),
+            rc: RcEncoder::new(),
+            need_dict_reset: true,
+            need_props: true,
+            need_state_reset: true,
+        })
+    }
+
+    /// Returns the lzma2 dictionary properties byte.
+    /// This is the byte that is stored in the filter properties of .xz files.
+    #[must_use]
+    pub fn dict_size_props(&self) -> u8 {
+        let mut props = 0u8;
+        while (2 + usize::from(props & 1)) << ((props >> 1) + 11) < self.options.dict_size {
+            props += 1;
+        }
+        props
+    }
+
+    /// Returns the lc/lp/pb properties byte.
+    const fn props(&self) -> u8 {
+        (self.options.pb * 5 + self.options.lp) * 9 + self.options.lc
+    }
+
+    /// Compresses the input and appends the produced lzma2 chunks to output.
+    ///
+    /// The encoder buffers up to about 2MiB of input before it produces a chunk,
+    /// call `finish` to encode the buffered input.
+    pub fn encode(&mut self, mut input: &[u8], output: &mut Vec<u8>) {
+        while !input.is_empty() {
+            let wanted = (self.pos + LOOKAHEAD).saturating_sub(self.window.len());
+            let size = wanted.min(input.len());
+            self.window.extend_from_slice(&input[..size]);
+            input = &input[size..];
+
+            if self.window.len() - self.pos >= LOOKAHEAD {
+                self.encode_chunk(false, output);
+                self.slide();
+            }
+        }
+    }
+
+    /// Encodes all buffered input and appends the lzma2 end marker to output.
+    /// The encoder is reset afterward and can be used for the next stream.
+    pub fn finish(&mut self, output: &mut Vec<u8>) {
+        while self.pos < self.window.len() {
+            self.encode_chunk(true, output);
+            self.slide();
+        }
+
+        output.push(0);
+        self.reset();
+    }
+
+    /// Resets the encoder to its initial state, discarding all buffered input.
+    pub fn reset(&mut self) {
+        self.window.clear();
+        self.window_offset = 0;
+        self.pos = 0;
+        self.head.fill(0);
+        self.chain.clear();
+        self.next = None;
+        self.lzma.reset();
+        self.need_dict_reset = true;
+        self.need_props = true;
+        self.need_state_reset = true;
+    }
+
+    /// Drops the part of the window that is further back than the dictionary size.
+    fn slide(&mut self) {
+        let keep = self.options.dict_size;
+        if self.pos < keep + keep.max(CHUNK_UNCOMPRESSED_MAX) {
+            return;
+        }
+
+        self.insert_until(self.pos);
+        let shift = self.pos - keep;
+        self.window.drain(..shift);
+        self.chain.drain(..shift);
+        let shift_u32 = clamp_us_to_u32(shift);
+        for entry in self.head.iter_mut().chain(self.chain.iter_mut()) {
+            *entry = entry.saturating_sub(shift_u32);
+        }
+        self.pos -= shift;
+        self.window_offset += shift as u64;
+    }
+
+    /// Hash of the 4 bytes at pos.
+    fn hash(&self, pos: usize) -> usize {
+        let bytes = [
+            self.window[pos],
+            self.window[pos + 1],
+            self.window[pos + 2],
+            self.window[pos + 3],
+        ];
+        (u32::from_le_bytes(bytes).wrapping_mul(0x9E37_79B1) >> self.hash_shift) as usize
+    }
+
+    /// Inserts all positions before pos into the match finder.
+    fn insert_until(&mut self, pos: usize) {
+        while self.chain.len() < pos {
+            let next = self.chain.len();
+            if next + 4 > self.window.len() {
+                self.chain.push(0);
+                continue;
+            }
+
+            let hash = self.hash(next);
+            self.chain.push(self.head[hash]);
+            self.head[hash] = clamp_us_to_u32(next + 1);
+        }
+    }
+
+    /// Length of the common prefix of the data at a and b, at most limit.
+    fn common_len(&self, a: usize, b: usize, limit: usize) -> usize {
+        self.window[a..a + limit]
+            .iter()
+            .zip(&self.window[b..b + limit])
+            .take_while(|(x, y)| x == y)
+            .count()
+    }
+
+    /// Inserts pos into the match finder and returns the matches that were found for it.
+    fn find_matches(&mut self, pos: usize) -> Matches {
+        self.insert_until(pos + 1);
+
+        let mut matches = Matches::new();
+        let avail = (self.window.len() - pos).min(MATCH_LEN_MAX);
+        let nice_len = self.options.nice_len.min(avail);
+        let mut best = MATCH_LEN_MIN - 1;
+        let mut candidate = self.chain[pos];
+        for _ in 0..self.options.depth {
+            if candidate == 0 || best >= avail {
+                break;
+            }
+
+            let start = candidate as usize - 1;
+            let dist = pos - start;
+            if dist > self.options.dict_size {
+                break;
+            }
+
+            if self.window[start + best] == self.window[pos + best] {
+                let len = self.common_len(start, pos, avail);
+                if len > best {
+                    best = len;
+                    matches.push(Match {
+                        len,
+                        dist: dist - 1,
+                    });
+                    if len >= nice_len {
+                        break;
+                    }
+                }
+            }
+
+            candidate = self.chain[start];
+        }
+
+        matches
+    }
+
+    /// Length of the repeated match with the given index at pos, 0 if it is not possible.
+    fn rep_len(&self, index: usize, pos: usize, limit: usize) -> usize {
+        let dist = self.lzma.reps[index] + 1;
+        if dist > pos {
+            return 0;
+        }
+
+        self.common_len(pos - dist, pos, limit)
+    }
+
+    /// Chooses the next symbol to encode, modelled after the fast mode of liblzma.
+    fn parse(&mut self) -> Symbol {
+        let pos = self.pos;
+        let avail = (self.window.len() - pos).min(MATCH_LEN_MAX);
+        let matches = self.next.take().unwrap_or_else(|| self.find_matches(pos));
+        if avail < MATCH_LEN_MIN {
+            return Symbol::Literal;
+        }
+
+        let nice_len = self.options.nice_len;
+        let mut rep_len = 0;
+        let mut rep_index = 0;
+        for index in 0..4 {
+            let len = self.rep_len(index, pos, avail);
+            if len >= nice_len {
+                return Symbol::Rep(index, len);
+            }
+            if len > rep_len {
+                rep_len = len;
+                rep_index = index;
+            }
+        }
+
+        let mut count = matches.count;
+        let Match {
+            len: mut len_main,
+            dist: mut dist_main,
+        } = matches.longest();
+        if len_main >= nice_len {
+            return Symbol::Match(dist_main, len_main);
+        }
+
+        while count > 1 {
+            let shorter = matches.matches[count - 2];
+            if len_main != shorter.len + 1 || !change_pair(shorter.dist, dist_main) {
+                break;
+            }
+            count -= 1;
+            len_main = shorter.len;
+            dist_main = shorter.dist;
+        }
+
+        if len_main == 2 && dist_main >= 0x80 {
+            len_main = 1;
+        }
+
+        if rep_len >= MATCH_LEN_MIN
+            && (rep_len + 1 >= len_main
+                || (rep_len + 2 >= len_main && dist_main > 1 << 9)
+                || (rep_len + 3 >= len_main && dist_main > 1 << 15))
+        {
+            return Symbol::Rep(rep_index, rep_len);
+        }
+
+        if len_main < MATCH_LEN_MIN || avail <= 2 {
+            return Symbol::Literal;
+        }
+
+        //Take a literal if the next position has a better match.
+        let next = self.find_matches(pos + 1);
+        self.next = Some(next);
+        let next = next.longest();
+        if next.len >= MATCH_LEN_MIN
+            && ((next.len >= len_main && next.dist < dist_main)
+                || (next.len == len_main + 1 && !change_pair(dist_main, next.dist))
+                || next.len > len_main + 1
+                || (next.len + 1 >= len_main && len_main >= 3 && change_pair(next.dist, dist_main)))
+        {
+            return Symbol::Literal;
+        }
+
+        let limit = (len_main - 1).max(MATCH_LEN_MIN);
+        for index in 0..4 {
+            if self.rep_len(index, pos + 1, limit) == limit {
+                return Symbol::Literal;
+            }
+        }
+
+        Symbol::Match(dist_main, len_main)
+    }
+
+    /// Position of the next byte to encode since the stream started.
+    const fn abs_pos(&self) -> u64 {
+        self.window_offset + self.pos as u64
+    }
+
+    /// Returns the state index of the current position for `is_match` and `is_rep0_long`.
+    const fn pos_state(&self) -> usize {
+        let pos_mask = (1 << self.options.pb) - 1;
+        clamp_u64_to_u32(self.abs_pos()) as usize & pos_mask
+    }
+
+    /// Encodes the byte at pos as a literal.
+    fn encode_literal(&mut self) {
+        let pos_state = self.pos_state();
+        let state = self.lzma.state.num();
+        self.rc
+            .bit(&mut self.lzma.is_match[16 * state + pos_state], 0);
+
+        let byte = u32::from(self.window[self.pos]);
+        let prev_byte = if self.pos > 0 {
+            u32::from(self.window[self.pos - 1])
+        } else {
+            0
+        };
+        let literal_pos_mask = (1 << self.options.lp) - 1;
+        let lc = u32::from(self.options.lc);
+        let coder = ((clamp_u64_to_u32(self.abs_pos()) as usize & literal_pos_mask) << lc)
+            + (prev_byte >> (8 - lc)) as usize;
+        let probs = &mut self.lzma.literal[0x300 * coder..0x300 * (coder + 1)];
+
+        if self.lzma.state.u32_is_literal() {
+            self.rc.bittree(probs, 8, byte);
+        } else {
+            let mut match_byte = u32::from(self.window[self.pos - self.lzma.reps[0] - 1]);
+            let mut offset = 0x100;
+            let mut symbol = byte | 0x100;
+            while symbol < 0x10000 {
+                match_byte <<= 1;
+                let match_bit = match_byte & offset;
+                let index = offset + match_bit + (symbol >> 8);
+                self.rc.bit(&mut probs[index as usize], (symbol >> 7) & 1);
+                symbol <<= 1;
+                offset &= !(match_byte ^ symbol);
+            }
+        }
+
+        self.lzma.state = self.lzma.state.u32_literal();
+    }
+
+    /// Encodes a match with a new distance.
+    fn encode_match(&mut self, dist: usize, len: usize) {
+        let pos_state = self.pos_state();
+        let state = self.lzma.state.num();
+        self.rc
+            .bit(&mut self.lzma.is_match[16 * state + pos_state], 1);
+        self.rc.bit(&mut self.lzma.is_rep[state], 0);
+        self.lzma.match_len.encode(&mut self.rc, len, pos_state);
+
+        let dist_u32 = clamp_us_to_u32(dist);
+        let slot = dist_slot(dist_u32);
+        let len_state = (len - MATCH_LEN_MIN).min(3);
+        self.rc.bittree(
+            &mut self.lzma.dist_slot[64 * len_state..64 * (len_state + 1)],
+            6,
+            slot,
+        );
+
+        if slot >= 4 {
+            let footer_bits = (slot >> 1) - 1;
+            let base = (2 | (slot & 1)) << footer_bits;
+            let reduced = dist_u32 - base;
+            if slot < 14 {
+                let offset = (256 + base - slot - 1) as usize;
+                self.rc
+                    .bittree_reverse(&mut self.lzma.dist_slot[offset..], footer_bits, reduced);
+            } else {
+                self.rc.direct(reduced >> 4, footer_bits - 4);
+                self.rc
+                    .bittree_reverse(&mut self.lzma.dist_slot[370..], 4, reduced & 0xF);
+            }
+        }
+
+        self.lzma.reps = [
+            dist,
+            self.lzma.reps[0],
+            self.lzma.reps[1],
+            self.lzma.reps[2],
+        ];
+        self.lzma.state = self.lzma.state.u32_match();
+    }
+
+    /// Encodes a match with the distance of one of the 4 most recent matches.
+    fn encode_rep(&mut self, index: usize, len: usize) {
+        let pos_state = self.pos_state();
+        let state = self.lzma.state.num();
+        self.rc
+            .bit(&mut self.lzma.is_match[16 * state + pos_state], 1);
+        self.rc.bit(&mut self.lzma.is_rep[state], 1);
+
+        if index == 0 {
+            self.rc.bit(&mut self.lzma.is_rep[state + 12], 0);
+            self.rc
+                .bit(&mut self.lzma.is_rep0_long[16 * state + pos_state], 1);
+        } else {
+            self.rc.bit(&mut self.lzma.is_rep[state + 12], 1);
+            if index == 1 {
+                self.rc.bit(&mut self.lzma.is_rep[state + 24], 0);
+            } else {
+                self.rc.bit(&mut self.lzma.is_rep[state + 24], 1);
+                self.rc.bit(
+                    &mut self.lzma.is_rep[state + 36],
+                    clamp_us_to_u32(index - 2),
+                );
+            }
+
+            let dist = self.lzma.reps[index];
+            self.lzma.reps.copy_within(..index, 1);
+            self.lzma.reps[0] = dist;
+        }
+
+        self.lzma.rep_len.encode(&mut self.rc, len, pos_state);
+        self.lzma.state = self.lzma.state.u32_long_rep();
+    }
+
+    /// Encodes the next chunk and appends it to output.
+    /// Unless finishing, input near the end of the window is left for the next chunk
+    /// so that matches are not cut short.
+    fn encode_chunk(&mut self, finishing: bool, output: &mut Vec<u8>) {
+        let start = self.pos;
+        self.rc.reset();
+        loop {
+            let remaining = self.window.len() - self.pos;
+            if remaining == 0 || (!finishing && remaining <= MATCH_LEN_MAX) {
+                break;
+            }
+            if self.pos - start + MATCH_LEN_MAX > CHUNK_UNCOMPRESSED_MAX
+                || self.rc.pending() + SYMBOL_SIZE_MAX > CHUNK_COMPRESSED_MAX
+            {
+                break;
+            }
+
+            match self.parse() {
+                Symbol::Literal => {
+                    self.encode_literal();
+                    self.pos += 1;
+                }
+                Symbol::Rep(index, len) => {
+                    self.encode_rep(index, len);
+                    self.pos += len;
+                    self.next = None;
+                }
+                Symbol::Match(dist, len) => {
+                    self.encode_match(dist, len);
+                    self.pos += len;
+                    self.next = None;
+                }
+            }
+        }
+        self.next = None;
+
+        let uncompressed = self.pos - start;
+        if uncompressed == 0 {
+            return;
+        }
+
+        self.rc.flush();
+        let compressed = self.rc.out.len();
+        if compressed >= uncompressed {
+            self.write_uncompressed(start, output);
+            return;
+        }
+
+        let control = if self.need_dict_reset {
+            0xE0
+        } else if self.need_props {
+            0xC0
+        } else if self.need_state_reset {
+            0xA0
+        } else {
+            0x80
+        };
+        let uncompressed = clamp_us_to_u32(uncompressed - 1);
+        let compressed = clamp_us_to_u32(compressed - 1);
+        output.push(control | clamp_u32_to_u8(uncompressed >> 16));
+        output.extend_from_slice(&clamp_u32_to_u16(uncompressed).to_be_bytes());
+        output.extend_from_slice(&clamp_u32_to_u16(compressed).to_be_bytes());
+        if self.need_props {
+            output.push(self.props());
+        }
+        output.extend_from_slice(&self.rc.out);
+
+        self.need_dict_reset = false;
+        self.need_props = false;
+        self.need_state_reset = false;
+    }
+
+    /// Stores the input from start to pos in uncompressed chunks.
+    /// The state is reset because the decoder never sees the symbols that were encoded for it.
+    fn write_uncompressed(&mut self, start: usize, output: &mut Vec<u8>) {
+        for chunk in self.window[start..self.pos].chunks(UNCOMPRESSED_CHUNK_MAX) {
+            output.push(if self.need_dict_reset { 1 } else { 2 });
+            output.extend_from_slice(
+                &clamp_u32_to_u16(clamp_us_to_u32(chunk.len() - 1)).to_be_bytes(),
+            );
+            output.extend_from_slice(chunk);
+            self.need_dict_reset = false;
+        }
+
+        self.lzma.reset();
+        self.need_state_reset = true;
+    }
+}
@@ -0,0 +1,474 @@
+extern crate std;
+
+use crate::container::{BlockTrailer, STREAM_HEADER_SIZE};
+use crate::input_buffer::InputBuffer;
+use crate::{XzCheckType, XzDecoder, XzError, XzFileInfo, XzNextBlockResult, DICT_SIZE_MAX};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::num::NonZeroUsize;
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+
+/// Size of the chunks in which the output of a block grows while it is decoded.
+const OUTPUT_CHUNK_SIZE: usize = 0x10000;
+
+/// A block that is decoded by a worker thread.
+#[derive(Debug)]
+struct Job {
+    /// Number of the block in the file.
+    block: usize,
+    /// Stream header of the stream that contains the block.
+    header: [u8; STREAM_HEADER_SIZE],
+    /// The block including its block padding.
+    input: Vec<u8>,
+    /// Index and stream footer to verify the block with.
+    trailer: BlockTrailer,
+    /// Amount of uncompressed bytes in the block according to the index.
+    uncompressed_size: u64,
+}
+
+/// Output of a worker thread for a block.
+type JobResult = (usize, Result<Vec<u8>, XzError>);
+
+/// Largest possible size of a block header.
+const BLOCK_HEADER_SIZE_MAX: usize = 1024;
+
+/// A block of a stream as described by the index.
+#[derive(Debug, Clone, Copy)]
+struct ParallelBlock {
+    /// Offset of the block header in the file.
+    compressed_offset: u64,
+    /// Size of the block including the block padding.
+    total_size: u64,
+    /// Size of the block without the block padding.
+    unpadded_size: u64,
+    /// Amount of uncompressed bytes in the block.
+    uncompressed_size: u64,
+    /// Memory needed by the decoder of the block, including its dictionary.
+    decoder_memory: u64,
+    /// Check type of the stream that contains the block.
+    check_type: XzCheckType,
+    /// Stream header of the stream that contains the block.
+    header: [u8; STREAM_HEADER_SIZE],
+}
+
+impl ParallelBlock {
+    /// Memory needed for the input, output and decoder of the block while it is in flight.
+    const fn memory(&self) -> u64 {
+        self.total_size
+            .saturating_add(self.uncompressed_size)
+            .saturating_add(self.decoder_memory)
+    }
+
+    /// Index and stream footer to verify the block with.
+    fn trailer(&self) -> BlockTrailer {
+        BlockTrailer::new(self.unpadded_size, self.uncompressed_size, self.check_type)
+    }
+}
+
+/// A block that does not fit into the memory limit, it is decoded directly from the underlying stream.
+#[derive(Debug)]
+struct StreamedBlock {
+    /// The block as described by the index.
+    block: ParallelBlock,
+    /// the decoder, on the heap.
+    decoder: Box<XzDecoder<'static>>,
+    /// Compressed data of the block that was not decoded yet.
+    input: InputBuffer,
+    /// Amount of compressed bytes of the block that were not read yet.
+    input_remaining: u64,
+    /// Amount of uncompressed bytes that were already returned.
+    produced: u64,
+}
+
+/// Reader that decodes the blocks of an .xz file on multiple threads.
+///
+/// The blocks are located using the index of each stream, so this only helps for files with many blocks,
+/// for example those created with "xz -T0". All streams of the file are decoded, as if multi stream mode was enabled.
+/// Every block is decoded with its own decoder and dictionary.
+/// The output is returned in order.
+///
+/// The memory limit bounds the compressed and uncompressed size of all blocks that are in flight
+/// as well as the memory of their decoders, see `XzDecoder::memory_usage`.
+/// A block that is larger than the limit is not buffered. Once all blocks before it were returned,
+/// it is decoded on the calling thread directly from the underlying stream, like "xz -T" falls back to a single thread.
+#[derive(Debug)]
+pub struct ParallelXzReader<R: Read + Seek> {
+    /// the underlying stream
+    reader: R,
+    /// Summary of all streams in the file.
+    info: XzFileInfo,
+    /// All blocks of all streams in order.
+    blocks: Vec<ParallelBlock>,
+    /// Sender for blocks to decode. Only None while dropping.
+    jobs: Option<Sender<Job>>,
+    /// Receiver of the output of the workers.
+    results: Receiver<JobResult>,
+    /// The worker threads.
+    workers: Vec<JoinHandle<()>>,
+    /// Output or error of blocks that finished out of order.
+    finished: BTreeMap<usize, Result<Vec<u8>, XzError>>,
+    /// Output of the current block.
+    current: Vec<u8>,
+    /// Amount of bytes of the current block that were already returned.
+    current_consumed: usize,
+    /// The current block if it is decoded directly from the underlying stream.
+    streamed: Option<StreamedBlock>,
+    /// Next block to send to the workers.
+    next_job: usize,
+    /// Next block to return output of.
+    next_output: usize,
+    /// Memory used by blocks in flight.
+    memory_used: u64,
+    /// Maximum memory used by blocks in flight.
+    memory_limit: u64,
+    /// The error that stopped decoding. Returned by every subsequent read.
+    error: Option<XzError>,
+}
+
+impl<R: Read + Seek> ParallelXzReader<R> {
+    /// Creates a new instance of `ParallelXzReader`
+    /// This uses one worker thread per available cpu and a memory limit of 256MB.
+    ///
+    /// # Errors
+    /// propagated from the underlying stream.
+    /// `InvalidData` if the stream headers, stream footers or indices are not valid.
+    pub fn new(r: R) -> std::io::Result<Self> {
+        Self::new_with_threads_and_memory_limit(
+            r,
+            std::thread::available_parallelism().unwrap_or(NonZeroUsize::MIN),
+            256 * 1024 * 1024,
+        )
+    }
+
+    /// Creates a new instance of `ParallelXzReader` with the given amount of worker threads
+    /// and the given limit for the memory used by blocks in flight.
+    /// The header of every block is read to determine the size of its dictionary.
+    ///
+    /// # Errors
+    /// propagated from the underlying stream.
+    /// `InvalidData` if the stream headers, stream footers or indices are not valid.
+    pub fn new_with_threads_and_memory_limit(
+        mut r: R,
+        threads: NonZeroUsize,
+        memory_limit: u64,
+    ) -> std::io::Result<Self> {
+        let info = XzFileInfo::from_reader(&mut r)?;
+        let mut blocks = Vec::with_capacity(info.block_count());
+        for stream in &info.streams {
+            let mut header = [0u8; STREAM_HEADER_SIZE];
+            r.seek(SeekFrom::Start(stream.compressed_offset))?;
+            r.read_exact(&mut header)?;
+            for block in &stream.blocks {
+                blocks.push(ParallelBlock {
+                    compressed_offset: block.compressed_offset,
+                    total_size: block.total_size(),
+                    unpadded_size: block.unpadded_size,
+                    uncompressed_size: block.uncompressed_size,
+                    decoder_memory: decoder_memory(&mut r, &header, block.compressed_offset)?,
+                    check_type: stream.check_type,
+                    header,
+                });
+            }
+        }
+
+        let (jobs, job_receiver) = channel::<Job>();
+        let (result_sender, results) = channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let mut workers = Vec::with_capacity(threads.get());
+        for _ in 0..threads.get() {
+            let job_receiver = Arc::clone(&job_receiver);
+            let result_sender = result_sender.clone();
+            workers.push(std::thread::spawn(move || {
+                worker(&job_receiver, &result_sender);
+            }));
+        }
+
+        Ok(Self {
+            reader: r,
+            info,
+            blocks,
+            jobs: Some(jobs),
+            results,
+            workers,
+            finished: BTreeMap::new(),
+            current: Vec::new(),
+            current_consumed: 0,
+            streamed: None,
+            next_job: 0,
+            next_output: 0,
+            memory_used: 0,
+            memory_limit,
+            error: None,
+        })
+    }
+
+    /// Returns the summary of all streams that was read from the stream footers and indices.
+    #[must_use]
+    pub const fn info(&self) -> &XzFileInfo {
+        &self.info
+    }
+
+    /// Sends blocks to the workers until the memory limit or a block that does not fit into it is reached.
+    fn dispatch(&mut self) -> std::io::Result<()> {
+        while self.next_job < self.blocks.len() {
+            let block = self.blocks[self.next_job];
+            if self.memory_used.saturating_add(block.memory()) > self.memory_limit {
+                return Ok(());
+            }
+
+            let mut input = vec![
+                0u8;
+                usize::try_from(block.total_size).map_err(|_| {
+                    std::io::Error::new(std::io::ErrorKind::InvalidData, XzError::CorruptedData)
+                })?
+            ];
+            self.reader.seek(SeekFrom::Start(block.compressed_offset))?;
+            self.reader.read_exact(&mut input)?;
+
+            let job = Job {
+                block: self.next_job,
+                header: block.header,
+                input,
+                trailer: block.trailer(),
+                uncompressed_size: block.uncompressed_size,
+            };
+            self.jobs
+                .as_ref()
+                .and_then(|jobs| jobs.send(job).ok())
+                .ok_or_else(|| std::io::Error::other("xz worker thread panicked"))?;
+            self.memory_used = self.memory_used.saturating_add(block.memory());
+            self.next_job += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Starts decoding the next block directly from the underlying stream.
+    /// Only called while no block is in flight.
+    fn start_streamed(&mut self) -> std::io::Result<()> {
+        let block = self.blocks[self.next_output];
+        let mut decoder = XzDecoder::in_heap_with_alloc_dict_size(0, DICT_SIZE_MAX);
+        decoder
+            .decode(&block.header, &mut [])
+            .map_err(|err| self.block_error(err))?;
+        self.reader.seek(SeekFrom::Start(block.compressed_offset))?;
+        self.streamed = Some(StreamedBlock {
+            block,
+            decoder,
+            input: InputBuffer::new(NonZeroUsize::new(8192).expect("Impossible to fail")),
+            input_remaining: block.total_size,
+            produced: 0,
+        });
+        self.next_job += 1;
+        Ok(())
+    }
+
+    /// Decodes the next output of the block that is decoded directly from the underlying stream.
+    /// Returns 0 once the block was verified against its index record.
+    /// After an io error of the underlying stream the next call continues where this one stopped.
+    fn read_streamed(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let Some(streamed) = &mut self.streamed else {
+            return Ok(0);
+        };
+        let mut block_error = |err: XzError| {
+            self.error = Some(err.clone());
+            std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+        };
+
+        loop {
+            if streamed.input.is_empty() {
+                if streamed.input_remaining == 0 {
+                    match streamed
+                        .decoder
+                        .decode(streamed.block.trailer().as_slice(), &mut [])
+                    {
+                        Ok(XzNextBlockResult::EndOfStream(_, _)) => (),
+                        Ok(XzNextBlockResult::NeedMoreData(_, _)) => {
+                            return Err(block_error(XzError::CorruptedData))
+                        }
+                        Err(err) => return Err(block_error(err)),
+                    }
+
+                    self.streamed = None;
+                    self.next_output += 1;
+                    return Ok(0);
+                }
+
+                let max = usize::try_from(streamed.input_remaining).unwrap_or(usize::MAX);
+                if !streamed.input.try_fill_max(&mut self.reader, max)? {
+                    return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof));
+                }
+                streamed.input_remaining -= streamed.input.data().len() as u64;
+            }
+
+            let produced = match streamed.decoder.decode(streamed.input.data(), buf) {
+                Ok(XzNextBlockResult::NeedMoreData(in_count, out_count)) => {
+                    streamed.input.consume(in_count);
+                    out_count
+                }
+                //The decoder only sees the index once the block is finished.
+                Ok(XzNextBlockResult::EndOfStream(_, _)) => {
+                    return Err(block_error(XzError::CorruptedData))
+                }
+                Err(err) => return Err(block_error(err)),
+            };
+
+            streamed.produced += produced as u64;
+            if streamed.produced > streamed.block.uncompressed_size {
+                return Err(block_error(XzError::CorruptedData));
+            }
+
+            if produced != 0 {
+                return Ok(produced);
+            }
+        }
+    }
+
+    /// Records the error of a block so every subsequent read returns it.
+    fn block_error(&mut self, err: XzError) -> std::io::Error {
+        self.error = Some(err.clone());
+        std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+    }
+
+    /// Waits for the output of the next block and makes it the current block.
+    /// Errors of later blocks are only returned once all blocks before them were returned.
+    fn next_block(&mut self) -> std::io::Result<()> {
+        loop {
+            if let Some(result) = self.finished.remove(&self.next_output) {
+                let output = result.map_err(|err| self.block_error(err))?;
+                let block = self.blocks[self.next_output];
+                self.memory_used = self.memory_used.saturating_sub(block.memory());
+                self.current = output;
+                self.current_consumed = 0;
+                self.next_output += 1;
+                return Ok(());
+            }
+
+            let (block, result) = self
+                .results
+                .recv()
+                .map_err(|_| std::io::Error::other("xz worker thread panicked"))?;
+            self.finished.insert(block, result);
+        }
+    }
+}
+
+impl<R: Read + Seek> Read for ParallelXzReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        if let Some(err) = &self.error {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                err.clone(),
+            ));
+        }
+
+        while self.current_consumed == self.current.len() {
+            if self.streamed.is_some() {
+                let size = self.read_streamed(buf)?;
+                if size != 0 {
+                    return Ok(size);
+                }
+                continue;
+            }
+
+            if self.next_output == self.blocks.len() {
+                return Ok(0);
+            }
+
+            self.dispatch()?;
+            if self.next_job == self.next_output {
+                //Nothing is in flight, the next block does not fit into the memory limit.
+                self.start_streamed()?;
+                continue;
+            }
+            self.next_block()?;
+        }
+
+        let available = &self.current[self.current_consumed..];
+        let size = available.len().min(buf.len());
+        buf[..size].copy_from_slice(&available[..size]);
+        self.current_consumed += size;
+        Ok(size)
+    }
+}
+
+impl<R: Read + Seek> Drop for ParallelXzReader<R> {
+    fn drop(&mut self) {
+        //Workers stop once there are no more jobs.
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            _ = worker.join();
+        }
+    }
+}
+
+/// Returns the memory needed to decode the block that starts at `offset`, see `XzDecoder::memory_usage`.
+fn decoder_memory(
+    r: &mut (impl Read + Seek),
+    header: &[u8; STREAM_HEADER_SIZE],
+    offset: u64,
+) -> std::io::Result<u64> {
+    let mut block_header = [0u8; BLOCK_HEADER_SIZE_MAX];
+    r.seek(SeekFrom::Start(offset))?;
+    r.read_exact(&mut block_header[..1])?;
+    let size = (usize::from(block_header[0]) + 1) * 4;
+    r.read_exact(&mut block_header[1..size])?;
+    XzDecoder::memory_usage(header, &block_header[..size])
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+}
+
+/// Decodes blocks until the sender of the jobs is dropped.
+fn worker(jobs: &Mutex<Receiver<Job>>, results: &Sender<JobResult>) {
+    loop {
+        let job = match jobs.lock() {
+            Ok(jobs) => jobs.recv(),
+            Err(_) => return,
+        };
+        let Ok(job) = job else {
+            return;
+        };
+
+        //A new decoder for every block frees the dictionary once the block is done.
+        let mut decoder = XzDecoder::in_heap_with_alloc_dict_size(0, DICT_SIZE_MAX);
+        let result = decode_block(&mut decoder, &job);
+        if results.send((job.block, result)).is_err() {
+            return;
+        }
+    }
+}
+
+/// Decodes a single block and verifies it against its index record.
+fn decode_block(decoder: &mut XzDecoder, job: &Job) -> Result<Vec<u8>, XzError> {
+    decoder.decode(&job.header, &mut [])?;
+
+    let capacity = usize::try_from(job.uncompressed_size).unwrap_or(usize::MAX);
+    let mut output = Vec::with_capacity(capacity.min(OUTPUT_CHUNK_SIZE));
+    let mut input = job.input.as_slice();
+    while !input.is_empty() {
+        let produced = output.len();
+        let remaining = capacity.saturating_sub(produced);
+        //One more byte than the index allows detects blocks that are too large.
+        output.resize(produced + remaining.clamp(1, OUTPUT_CHUNK_SIZE), 0);
+        let result = decoder.decode(input, &mut output[produced..])?;
+        input = &input[result.input_consumed()..];
+        output.truncate(produced + result.output_produced());
+        if output.len() > capacity {
+            return Err(XzError::CorruptedData);
+        }
+    }
+
+    match decoder.decode(job.trailer.as_slice(), &mut [])? {
+        XzNextBlockResult::EndOfStream(_, _) => Ok(output),
+        XzNextBlockResult::NeedMoreData(_, _) => Err(XzError::CorruptedData),
+    }
+}
@@ -0,0 +1,86 @@
+use crate::block_info::XzBlockInfo;
+use crate::container::parse_stream_header;
+use crate::container::{STREAM_HEADER_MAGIC, STREAM_HEADER_SIZE};
+use crate::decoder::memory_usage_of_dict;
+use crate::{XzCheckType, XzError};
+
+/// Result of `probe`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum XzProbe {
+    /// The data is a prefix of an .xz stream but too short, at least this many bytes are needed in total.
+    NeedMoreData(usize),
+    /// The stream contains no blocks.
+    Empty(XzCheckType),
+    /// The check type of the stream and the header of its first block.
+    Block(XzCheckType, XzBlockInfo),
+}
+
+impl XzProbe {
+    /// Returns the check type of the stream, None if more data is needed.
+    #[must_use]
+    pub const fn check_type(&self) -> Option<XzCheckType> {
+        match self {
+            Self::NeedMoreData(_) => None,
+            Self::Empty(check_type) | Self::Block(check_type, _) => Some(*check_type),
+        }
+    }
+
+    /// Returns the header of the first block, None if more data is needed or the stream is empty.
+    #[must_use]
+    pub const fn block_info(&self) -> Option<&XzBlockInfo> {
+        match self {
+            Self::Block(_, block_info) => Some(block_info),
+            _ => None,
+        }
+    }
+
+    /// Returns the amount of memory in bytes a `XzDecoder` needs to decode the first block,
+    /// see `XzDecoder::memory_usage`. None if more data is needed or the stream is empty.
+    #[must_use]
+    pub const fn memory_usage(&self) -> Option<u64> {
+        match self {
+            Self::Block(_, block_info) => Some(memory_usage_of_dict(block_info.dict_size())),
+            _ => None,
+        }
+    }
+}
+
+/// Identifies an .xz stream from its first bytes without decoding any data.
+///
+/// Parses the stream header and the header of the first block. If `data` is too short then
+/// `XzProbe::NeedMoreData` tells how many bytes are needed, as soon as possible data that is
+/// not an .xz stream fails with `XzError::StreamHeaderMagicNumberMismatch`.
+///
+/// # Errors
+/// - `XzError::StreamHeaderMagicNumberMismatch` if the data is not an .xz stream.
+/// - `XzError::UnsupportedCheckType` and the other errors of `XzDecoder::decode` for invalid or unsupported headers.
+pub fn probe(data: &[u8]) -> Result<XzProbe, XzError> {
+    if data.len() < STREAM_HEADER_SIZE {
+        let prefix = data.len().min(STREAM_HEADER_MAGIC.len());
+        if data[..prefix] != STREAM_HEADER_MAGIC[..prefix] {
+            return Err(XzError::StreamHeaderMagicNumberMismatch);
+        }
+        return Ok(XzProbe::NeedMoreData(STREAM_HEADER_SIZE));
+    }
+
+    let check_type = parse_stream_header(data)?;
+    let Some(&size_byte) = data.get(STREAM_HEADER_SIZE) else {
+        return Ok(XzProbe::NeedMoreData(STREAM_HEADER_SIZE + 1));
+    };
+
+    // A 0 byte instead of a block header is the start of the index.
+    if size_byte == 0 {
+        return Ok(XzProbe::Empty(check_type));
+    }
+
+    let block_header_end = STREAM_HEADER_SIZE + (usize::from(size_byte) + 1) * 4;
+    if data.len() < block_header_end {
+        return Ok(XzProbe::NeedMoreData(block_header_end));
+    }
+
+    Ok(XzProbe::Block(
+        check_type,
+        XzBlockInfo::parse(&data[STREAM_HEADER_SIZE..])?,
+    ))
+}
@@ -0,0 +1,318 @@
+extern crate std;
+
+use crate::container::{BlockTrailer, STREAM_HEADER_SIZE};
+use crate::input_buffer::InputBuffer;
+use crate::{XzDecoder, XzError, XzFileInfo, XzNextBlockResult};
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::num::NonZeroUsize;
+use std::io::{Read, Seek, SeekFrom};
+
+/// A block of a stream as described by the index.
+#[derive(Debug, Clone, Copy)]
+struct SeekableBlock {
+    /// Offset of the block header in the file.
+    compressed_offset: u64,
+    /// Size of the block without the block padding.
+    unpadded_size: u64,
+    /// Offset of the first byte of the block in the uncompressed data.
+    uncompressed_offset: u64,
+    /// Amount of uncompressed bytes in the block.
+    uncompressed_size: u64,
+    /// Index of the stream that contains the block.
+    stream: usize,
+}
+
+/// Reader that supports random access in .xz files by using the index of each stream.
+///
+/// All streams of the file are decoded, as if multi stream mode was enabled.
+/// Seeking is cheap, it only records the new position.
+/// The next read then decodes from the start of the block that contains the position.
+/// Every block is verified against its check and its record in the index once it was fully decoded.
+/// Files made of a single block (the default of "xz" without threads) cannot be accessed randomly
+/// and are decoded from the beginning on every backward seek.
+#[derive(Debug)]
+pub struct XzSeekableReader<R: Read + Seek + 'static> {
+    /// the inner decoder, on the heap.
+    decoder: Box<XzDecoder<'static>>,
+    /// the underlying stream
+    reader: R,
+    /// Compressed data of the current block that was not decoded yet.
+    buffer: InputBuffer,
+    /// Buffer for output that is decoded only to reach the current position.
+    discard: Vec<u8>,
+    /// All blocks of all streams, ordered by their offsets.
+    blocks: Vec<SeekableBlock>,
+    /// Summary of all streams in the file.
+    info: XzFileInfo,
+    /// Total amount of uncompressed bytes in the file.
+    uncompressed_size: u64,
+    /// Current position in the uncompressed data.
+    position: u64,
+    /// Is the decoder inside a block?
+    active: bool,
+    /// Block the decoder is currently in.
+    block: usize,
+    /// Position in the uncompressed data the decoder has reached.
+    decoded: u64,
+    /// Offset in the file of the next byte to read into the buffer.
+    input_offset: u64,
+    /// Offset in the file where the current block ends.
+    input_end: u64,
+}
+
+impl<R: Read + Seek> XzSeekableReader<R> {
+    /// Creates a new instance of `XzSeekableReader`
+    /// This reads the stream footers and indices of all streams from the end of the file.
+    /// This reader will heap allocate two internal 8k io buffers.
+    /// This reader will allocate up to about 3GB of additional memory in heap for the lzma dictionary
+    /// depending on the input file.
+    ///
+    /// # Errors
+    /// propagated from the underlying stream.
+    /// `InvalidData` if the stream footers or indices are not valid.
+    #[allow(clippy::missing_panics_doc)] //We never actually panic.
+    pub fn new(r: R) -> std::io::Result<Self> {
+        Self::new_with_buffer_size_and_decoder(
+            r,
+            NonZeroUsize::new(8192).expect("Impossible to fail"),
+            XzDecoder::in_heap(),
+        )
+    }
+
+    /// Creates a new instance of `XzSeekableReader` with the given buffer size and decoder.
+    /// The multi stream mode of the decoder is ignored.
+    ///
+    /// # Errors
+    /// propagated from the underlying stream.
+    /// `InvalidData` if the stream footers or indices are not valid.
+    pub fn new_with_buffer_size_and_decoder(
+        mut r: R,
+        buffer_size: NonZeroUsize,
+        mut decoder: Box<XzDecoder<'static>>,
+    ) -> std::io::Result<Self> {
+        let info = XzFileInfo::from_reader(&mut r)?;
+        let mut blocks = Vec::with_capacity(info.block_count());
+        for (index, stream) in info.streams.iter().enumerate() {
+            blocks.extend(stream.blocks.iter().map(|block| SeekableBlock {
+                compressed_offset: block.compressed_offset,
+                unpadded_size: block.unpadded_size,
+                uncompressed_offset: block.uncompressed_offset,
+                uncompressed_size: block.uncompressed_size,
+                stream: index,
+            }));
+        }
+        decoder.set_multi_stream(false);
+        Ok(Self {
+            decoder,
+            reader: r,
+            buffer: InputBuffer::new(buffer_size),
+            discard: vec![0; buffer_size.into()],
+            uncompressed_size: info.uncompressed_size(),
+            blocks,
+            info,
+            position: 0,
+            active: false,
+            block: 0,
+            decoded: 0,
+            input_offset: 0,
+            input_end: 0,
+        })
+    }
+
+    /// Returns the total amount of uncompressed bytes of all streams.
+    #[must_use]
+    pub const fn uncompressed_size(&self) -> u64 {
+        self.uncompressed_size
+    }
+
+    /// Returns the amount of streams in the file.
+    #[must_use]
+    pub fn stream_count(&self) -> usize {
+        self.info.streams.len()
+    }
+
+    /// Returns the amount of blocks of all streams in the file.
+    #[must_use]
+    pub fn block_count(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Returns the summary of all streams that was read from the stream footers and indices.
+    #[must_use]
+    pub const fn info(&self) -> &XzFileInfo {
+        &self.info
+    }
+
+    /// Returns the underlying reader.
+    /// The position of the underlying reader is unspecified.
+    #[must_use]
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Prepares the decoder to decode the given block.
+    fn start_block(&mut self, index: usize) -> std::io::Result<()> {
+        let block = self.blocks[index];
+        let header_offset = self.info.streams[block.stream].compressed_offset;
+
+        let mut header = [0u8; STREAM_HEADER_SIZE];
+        self.reader.seek(SeekFrom::Start(header_offset))?;
+        self.reader.read_exact(&mut header)?;
+        self.decoder.reset();
+        self.decoder
+            .decode(&header, &mut [])
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+
+        self.reader.seek(SeekFrom::Start(block.compressed_offset))?;
+        self.input_offset = block.compressed_offset;
+        self.input_end = block.compressed_offset + ((block.unpadded_size + 3) & !3);
+        self.buffer.clear();
+        self.block = index;
+        self.decoded = block.uncompressed_offset;
+        self.active = true;
+        Ok(())
+    }
+
+    /// Verifies the current block after all of its input was decoded and continues with the next block.
+    fn finish_block(&mut self) -> std::io::Result<()> {
+        let block = self.blocks[self.block];
+        let trailer = BlockTrailer::new(
+            block.unpadded_size,
+            block.uncompressed_size,
+            self.info.streams[block.stream].check_type,
+        );
+        match self.decoder.decode(trailer.as_slice(), &mut []) {
+            Ok(XzNextBlockResult::EndOfStream(_, _)) => (),
+            Ok(XzNextBlockResult::NeedMoreData(_, _)) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    XzError::CorruptedData,
+                ))
+            }
+            Err(err) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, err)),
+        }
+
+        if self.block + 1 < self.blocks.len() {
+            return self.start_block(self.block + 1);
+        }
+
+        self.active = false;
+        Ok(())
+    }
+
+    /// Ensure that the buffer has at least 1 more readable byte of the current block.
+    /// Returns false if the entire block was read.
+    fn try_fill_buffer(&mut self) -> std::io::Result<bool> {
+        if self.buffer.is_empty() {
+            let remaining = self.input_end - self.input_offset;
+            if remaining == 0 {
+                return Ok(false);
+            }
+
+            let max = usize::try_from(remaining).unwrap_or(usize::MAX);
+            if !self.buffer.try_fill_max(&mut self.reader, max)? {
+                return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof));
+            }
+            self.input_offset += self.buffer.data().len() as u64;
+        }
+
+        Ok(true)
+    }
+
+    /// Decodes until some output at the current position was produced or the end of the data is reached.
+    fn decode_next(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            if self.position >= self.uncompressed_size {
+                return Ok(0);
+            }
+
+            let target = self.blocks.partition_point(|block| {
+                block.uncompressed_offset + block.uncompressed_size <= self.position
+            });
+            //Skipping entire blocks is cheaper than decoding them.
+            if !self.active
+                || self.position < self.decoded
+                || self.blocks[target].uncompressed_offset > self.decoded
+            {
+                self.start_block(target)?;
+            }
+
+            if !self.try_fill_buffer()? {
+                self.finish_block()?;
+                continue;
+            }
+
+            let skip = self.position - self.decoded;
+            let output = if skip == 0 {
+                &mut *buf
+            } else {
+                let size = usize::try_from(skip)
+                    .map_or(self.discard.len(), |skip| skip.min(self.discard.len()));
+                &mut self.discard[..size]
+            };
+
+            let produced = match self.decoder.decode(self.buffer.data(), output) {
+                Ok(XzNextBlockResult::NeedMoreData(in_count, out_count)) => {
+                    self.buffer.consume(in_count);
+                    out_count
+                }
+                //The decoder only sees the index once the block is finished.
+                Ok(XzNextBlockResult::EndOfStream(_, _)) => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        XzError::CorruptedData,
+                    ))
+                }
+                Err(err) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, err)),
+            };
+
+            self.decoded += produced as u64;
+            let block = self.blocks[self.block];
+            if self.decoded > block.uncompressed_offset + block.uncompressed_size {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    XzError::CorruptedData,
+                ));
+            }
+
+            if skip == 0 && produced != 0 {
+                self.position += produced as u64;
+                return Ok(produced);
+            }
+        }
+    }
+}
+
+impl<R: Read + Seek> Read for XzSeekableReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        //After an error the next read starts over at the block of the current position.
+        self.decode_next(buf).inspect_err(|_| self.active = false)
+    }
+}
+
+impl<R: Read + Seek> Seek for XzSeekableReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(position) => {
+                self.position = position;
+                return Ok(position);
+            }
+            SeekFrom::End(offset) => (self.uncompressed_size, offset),
+            SeekFrom::Current(offset) => (self.position, offset),
+        };
+
+        self.position = base.checked_add_signed(offset).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
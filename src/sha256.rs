@@ -40,6 +40,11 @@ impl XzSha256 {
         Digest::update(self.delegate(), buf);
     }
 
+    /// Finalizes the digest and returns it. The state is reset afterward.
+    pub fn finish(&mut self) -> [u8; 32] {
+        self.take_delegate().finalize().into()
+    }
+
     /// Validate the digest.
     pub fn validate(&mut self, buf: &[u8]) -> Result<(), XzError> {
         let state = self.take_delegate();
@@ -1,11 +1,20 @@
 extern crate std;
 
-use crate::{XzDecoder, XzError, XzNextBlockResult};
+use crate::input_buffer::InputBuffer;
+use crate::{
+    LzipDecoder, LzmaAloneDecoder, XzDamagedBlock, XzDecoder, XzDecoderStats, XzError,
+    XzErrorContext, XzNextBlockResult, DICT_SIZE_MAX,
+};
 use alloc::boxed::Box;
+use alloc::collections::VecDeque;
 use alloc::vec;
 use alloc::vec::Vec;
+use core::mem;
 use core::num::NonZeroUsize;
-use std::io::Read;
+use std::io::{BufRead, Read};
+
+/// Size of the buffer for decoded data, it is only allocated once `XzReader` is used as `BufRead`.
+const OUTPUT_BUFFER_SIZE: usize = 8192;
 
 impl std::error::Error for XzError {}
 #[derive(Debug)]
@@ -14,14 +23,18 @@ pub struct XzReader<R: Read + 'static> {
     decoder: Box<XzDecoder<'static>>,
     /// the underlying stream
     reader: R,
-    /// A buffer
-    buffer: Vec<u8>,
-    /// Amount of bytes in the buffer we have consumed.
-    buffer_consumed: usize,
-    /// Amount of bytes in the buffer available for consumption.
-    buffer_fill_count: usize,
+    /// Data read from the underlying stream that was not decoded yet.
+    input: InputBuffer,
     /// Are we at the end of a valid xz stream and should return eof?
     eos: bool,
+    /// Decoded data for `BufRead`, empty until `fill_buf` is called.
+    output: Vec<u8>,
+    /// Amount of bytes in the output buffer we have consumed.
+    output_consumed: usize,
+    /// Amount of bytes in the output buffer available for consumption.
+    output_fill_count: usize,
+    /// Blocks that were skipped in recovery mode and were not taken yet.
+    damaged_blocks: VecDeque<XzDamagedBlock>,
 }
 
 impl<R: Read> XzReader<R> {
@@ -53,10 +66,12 @@ impl<R: Read> XzReader<R> {
         Self {
             decoder,
             reader: r,
-            buffer: vec![0; buffer_size.into()],
-            buffer_consumed: 0,
-            buffer_fill_count: 0,
+            input: InputBuffer::new(buffer_size),
             eos: false,
+            output: Vec::new(),
+            output_consumed: 0,
+            output_fill_count: 0,
+            damaged_blocks: VecDeque::new(),
         }
     }
 
@@ -81,44 +96,87 @@ impl<R: Read> XzReader<R> {
         initial_data_in_buffer: impl AsRef<[u8]>,
         decoder: Box<XzDecoder<'static>>,
     ) -> Self {
-        let initial = initial_data_in_buffer.as_ref();
-        let mut reader = Self::new_with_buffer_size_and_decoder(
-            r,
-            buffer_size.max(NonZeroUsize::new(initial.len()).unwrap_or(NonZeroUsize::MIN)),
-            decoder,
-        );
-        reader.buffer.as_mut_slice()[0..initial.len()].copy_from_slice(initial);
-        reader.buffer_fill_count = initial.len();
+        let mut reader = Self::new_with_buffer_size_and_decoder(r, buffer_size, decoder);
+        reader.input = InputBuffer::with_data(buffer_size, initial_data_in_buffer.as_ref());
         reader
     }
 
+    /// Enables or disables multi stream mode of the decoder.
+    ///
+    /// In multi stream mode the reader decodes all concatenated streams (for example files created
+    /// with `cat a.xz b.xz`) and skips the stream padding between them.
+    /// The reader only returns eof once the underlying reader is exhausted.
+    /// This means that data following the last stream must be stream padding or another stream.
+    pub const fn set_multi_stream(&mut self, multi_stream: bool) {
+        self.decoder.set_multi_stream(multi_stream);
+    }
+
+    /// Sets the memory limit of the decoder, see `XzDecoder::set_memlimit`.
+    ///
+    /// If a block exceeds the limit then `read` fails with `InvalidData` and a `XzError::MemoryLimitExceeded`.
+    /// Reading can continue after the limit was raised.
+    pub const fn set_memlimit(&mut self, memlimit: u64) {
+        self.decoder.set_memlimit(memlimit);
+    }
+
+    /// Enables or disables ignoring the checks of blocks, see `XzDecoder::set_ignore_check`.
+    pub const fn set_ignore_check(&mut self, ignore_check: bool) {
+        self.decoder.set_ignore_check(ignore_check);
+    }
+
     /// Reset the decoder to possibly decode the next fresh stream.
     pub fn reset(&mut self) {
         self.eos = false;
+        self.damaged_blocks.clear();
         self.decoder.reset();
     }
 
+    /// Enables or disables recovery mode of the decoder, see `XzDecoder::set_recovery`.
+    /// `take_damaged_block` reports the damaged blocks that were skipped.
+    pub const fn set_recovery(&mut self, recovery: bool) {
+        self.decoder.set_recovery(recovery);
+    }
+
+    /// Returns the next block that was damaged in recovery mode, in the order the blocks were skipped.
+    /// Every damaged block is returned once, the reader keeps them until they are taken.
+    pub fn take_damaged_block(&mut self) -> Option<XzDamagedBlock> {
+        self.damaged_blocks.pop_front()
+    }
+
     /// Returns true if the xz stream is end of a valid xz stream.
     #[must_use]
     pub const fn is_eos(&self) -> bool {
         self.eos
     }
 
+    /// Returns a snapshot of the progress of the decoder, see `XzDecoder::stats`.
+    /// Data in the internal buffers that was not decoded yet is not counted.
+    #[must_use]
+    pub const fn stats(&self) -> XzDecoderStats {
+        self.decoder.stats()
+    }
+
+    /// Returns the position of the decoder when decoding failed with `InvalidData`, see `XzDecoder::error_context`.
+    /// The `output_produced` bytes of the failed call were not returned by the reader.
+    #[must_use]
+    pub const fn error_context(&self) -> Option<XzErrorContext> {
+        self.decoder.error_context()
+    }
+
     /// Ensure that the buffer has at least 1 more readable byte. Otherwise, fill the inner buffer.
     fn fill_buffer(&mut self) -> std::io::Result<()> {
-        debug_assert!(self.buffer_fill_count >= self.buffer_consumed);
-
-        if self.buffer_consumed == self.buffer_fill_count {
-            self.buffer_fill_count = self.reader.read(&mut self.buffer)?;
-            if self.buffer_fill_count == 0 {
-                return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof));
-            }
-            self.buffer_consumed = 0;
+        if !self.try_fill_buffer()? {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof));
         }
 
         Ok(())
     }
 
+    /// Same as `fill_buffer` but returns false instead of an error if the underlying stream is at eof.
+    fn try_fill_buffer(&mut self) -> std::io::Result<bool> {
+        self.input.try_fill(&mut self.reader)
+    }
+
     /// Take a peek at raw data without consuming it.
     /// The param fn is guaranteed to be called with at least 1 byte of data.
     /// # Errors
@@ -126,9 +184,7 @@ impl<R: Read> XzReader<R> {
     /// This fn fails with `UnexpectedEof` if no data is available and no data can be read from the stream.
     pub fn peek_inner<T>(&mut self, peeker: impl FnOnce(&[u8]) -> T) -> std::io::Result<T> {
         self.fill_buffer()?;
-        Ok(peeker(
-            &self.buffer[self.buffer_consumed..self.buffer_fill_count],
-        ))
+        Ok(peeker(self.input.data()))
     }
 
     /// Read raw bytes, bypassing the decoder.
@@ -137,17 +193,7 @@ impl<R: Read> XzReader<R> {
 
         impl<R: Read> Read for ReadInner<'_, R> {
             fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-                debug_assert!(self.0.buffer_fill_count >= self.0.buffer_consumed);
-                if self.0.buffer_fill_count != self.0.buffer_consumed {
-                    let available = self.0.buffer_fill_count - self.0.buffer_consumed;
-                    let to_copy = std::cmp::min(available, buf.len());
-                    let source = &self.0.buffer[self.0.buffer_consumed..][..to_copy];
-                    buf[..to_copy].copy_from_slice(source);
-                    self.0.buffer_consumed += to_copy;
-                    return Ok(to_copy);
-                }
-
-                self.0.reader.read(buf)
+                self.0.input.read(&mut self.0.reader, buf)
             }
         }
 
@@ -156,21 +202,66 @@ impl<R: Read> XzReader<R> {
 
     /// Returns the underlying reader as well as the (possibly empty)
     /// buffer that may contain some unprocessed data.
+    /// Decoded data that was buffered by `fill_buf` and not consumed yet is discarded.
     #[must_use]
-    pub fn into_inner(mut self) -> (R, Vec<u8>) {
-        debug_assert!(self.buffer_fill_count >= self.buffer_consumed);
-        if self.buffer_consumed != 0 {
-            self.buffer
-                .copy_within(self.buffer_consumed..self.buffer_fill_count, 0);
-            self.buffer_fill_count -= self.buffer_consumed;
+    pub fn into_inner(self) -> (R, Vec<u8>) {
+        (self.reader, self.input.into_vec())
+    }
+
+    /// Decodes the remaining data without returning it and verifies all checks, the index and the stream footer.
+    /// In multi stream mode all following streams are verified as well.
+    ///
+    /// Returns the stats of the decoder, which include the total amount of decompressed data.
+    /// Decoded data that is still buffered for `BufRead` is discarded.
+    ///
+    /// # Errors
+    /// `UnexpectedEof` if the data ends in the middle of a stream.
+    /// `InvalidData` if the data is corrupted or unsupported.
+    /// propagated from the underlying stream.
+    pub fn verify(&mut self) -> std::io::Result<XzDecoderStats> {
+        self.output_consumed = self.output_fill_count;
+        while !self.eos {
+            if !self.try_fill_buffer()? {
+                self.finish_input()?;
+                break;
+            }
+
+            let result = self.decoder.verify(self.input.data());
+            // The decoder returns after every damaged block.
+            self.damaged_blocks
+                .extend(self.decoder.take_damaged_block());
+            let result =
+                result.map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+            self.input.consume(result.input_consumed());
+            if result.is_end_of_stream() && !self.decoder.is_multi_stream() {
+                self.eos = true;
+            }
         }
 
-        self.buffer.truncate(self.buffer_fill_count);
-        (self.reader, self.buffer)
+        Ok(self.stats())
     }
-}
-impl<R: Read> Read for XzReader<R> {
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+
+    /// Called once the underlying stream is at eof, sets eos if the data ended after a complete stream.
+    fn finish_input(&mut self) -> std::io::Result<()> {
+        if !self.decoder.is_multi_stream() {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof));
+        }
+
+        match self.decoder.finish() {
+            Ok(()) => {
+                self.eos = true;
+                Ok(())
+            }
+            Err(XzError::UnexpectedEndOfInput) => Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                XzError::UnexpectedEndOfInput,
+            )),
+            Err(err) => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, err)),
+        }
+    }
+
+    /// Decodes directly into buf, bypassing the output buffer.
+    fn decode_into(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         if buf.is_empty() {
             return Ok(0);
         }
@@ -180,21 +271,34 @@ impl<R: Read> Read for XzReader<R> {
         }
 
         loop {
-            debug_assert!(self.buffer_fill_count >= self.buffer_consumed);
-            self.fill_buffer()?;
+            if !self.try_fill_buffer()? {
+                self.finish_input()?;
+                return Ok(0);
+            }
 
-            return match self.decoder.decode(
-                &self.buffer.as_slice()[self.buffer_consumed..self.buffer_fill_count],
-                buf,
-            ) {
+            let result = self.decoder.decode(self.input.data(), buf);
+            // The decoder returns after every damaged block.
+            self.damaged_blocks
+                .extend(self.decoder.take_damaged_block());
+            return match result {
                 Ok(XzNextBlockResult::NeedMoreData(in_count, outcount)) => {
-                    self.buffer_consumed += in_count;
+                    self.input.consume(in_count);
+                    if outcount == 0 {
+                        continue;
+                    }
+                    Ok(outcount)
+                }
+                Ok(XzNextBlockResult::EndOfStream(in_count, outcount))
+                    if self.decoder.is_multi_stream() =>
+                {
+                    self.input.consume(in_count);
                     if outcount == 0 {
                         continue;
                     }
                     Ok(outcount)
                 }
-                Ok(XzNextBlockResult::EndOfStream(_, outcount)) => {
+                Ok(XzNextBlockResult::EndOfStream(in_count, outcount)) => {
+                    self.input.consume(in_count);
                     self.eos = true;
                     Ok(outcount)
                 }
@@ -203,3 +307,247 @@ impl<R: Read> Read for XzReader<R> {
         }
     }
 }
+
+impl<R: Read> Read for XzReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        debug_assert!(self.output_fill_count >= self.output_consumed);
+        if self.output_consumed == self.output_fill_count {
+            return self.decode_into(buf);
+        }
+
+        let available = &self.output[self.output_consumed..self.output_fill_count];
+        let to_copy = available.len().min(buf.len());
+        buf[..to_copy].copy_from_slice(&available[..to_copy]);
+        self.output_consumed += to_copy;
+        Ok(to_copy)
+    }
+}
+
+impl<R: Read> BufRead for XzReader<R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        debug_assert!(self.output_fill_count >= self.output_consumed);
+        if self.output_consumed == self.output_fill_count {
+            if self.output.is_empty() {
+                self.output = vec![0; OUTPUT_BUFFER_SIZE];
+            }
+
+            // The decoder writes straight into the output buffer, it is only moved out to satisfy the borrow checker.
+            let mut output = mem::take(&mut self.output);
+            let result = self.decode_into(&mut output);
+            self.output = output;
+            self.output_fill_count = result?;
+            self.output_consumed = 0;
+        }
+
+        Ok(&self.output[self.output_consumed..self.output_fill_count])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.output_consumed = (self.output_consumed + amt).min(self.output_fill_count);
+    }
+}
+
+/// Decodes the next data into buf for readers whose decoder returns `EndOfStream` only once at the very end.
+/// An empty input is passed to the decoder once the underlying stream is at eof.
+fn read_until_eos<R: Read>(
+    reader: &mut R,
+    input: &mut InputBuffer,
+    eos: &mut bool,
+    buf: &mut [u8],
+    mut decode: impl FnMut(&[u8], &mut [u8]) -> Result<XzNextBlockResult, XzError>,
+) -> std::io::Result<usize> {
+    if buf.is_empty() {
+        return Ok(0);
+    }
+
+    if *eos {
+        return Ok(0);
+    }
+
+    loop {
+        let data = if input.try_fill(reader)? {
+            input.data()
+        } else {
+            &[]
+        };
+
+        return match decode(data, buf) {
+            Ok(XzNextBlockResult::NeedMoreData(in_count, outcount)) => {
+                input.consume(in_count);
+                if outcount == 0 {
+                    continue;
+                }
+                Ok(outcount)
+            }
+            Ok(XzNextBlockResult::EndOfStream(in_count, outcount)) => {
+                input.consume(in_count);
+                *eos = true;
+                Ok(outcount)
+            }
+            Err(XzError::UnexpectedEndOfInput) => Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                XzError::UnexpectedEndOfInput,
+            )),
+            Err(err) => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, err)),
+        };
+    }
+}
+
+/// Reader for legacy .lzma files. (`LZMA_Alone` format)
+#[derive(Debug)]
+pub struct LzmaAloneReader<R: Read + 'static> {
+    /// the inner decoder, on the heap.
+    decoder: Box<LzmaAloneDecoder<'static>>,
+    /// the underlying stream
+    reader: R,
+    /// Data read from the underlying stream that was not decoded yet.
+    input: InputBuffer,
+    /// Are we at the end of the lzma data and should return eof?
+    eos: bool,
+}
+
+impl<R: Read> LzmaAloneReader<R> {
+    /// Creates a new instance of `LzmaAloneReader`
+    /// This reader will heap allocate an internal 8k io buffer to read from R.
+    /// This reader will allocate up to about 3GB of additional memory in heap for the lzma dictionary
+    /// depending on the input file.
+    #[allow(clippy::missing_panics_doc)] //We never actually panic.
+    #[must_use]
+    pub fn new(r: R) -> Self {
+        Self::new_with_buffer_size(r, NonZeroUsize::new(8192).expect("Impossible to fail"))
+    }
+
+    #[must_use]
+    pub fn new_with_buffer_size(r: R, buffer_size: NonZeroUsize) -> Self {
+        Self::new_with_buffer_size_and_decoder(
+            r,
+            buffer_size,
+            Box::new(LzmaAloneDecoder::with_alloc_dict_size(DICT_SIZE_MAX)),
+        )
+    }
+
+    #[must_use]
+    pub fn new_with_buffer_size_and_decoder(
+        r: R,
+        buffer_size: NonZeroUsize,
+        decoder: Box<LzmaAloneDecoder<'static>>,
+    ) -> Self {
+        Self {
+            decoder,
+            reader: r,
+            input: InputBuffer::new(buffer_size),
+            eos: false,
+        }
+    }
+
+    /// Reset the decoder to possibly decode the next fresh .lzma file.
+    pub fn reset(&mut self) {
+        self.eos = false;
+        self.decoder.reset();
+    }
+
+    /// Returns true if the reader reached the end of the lzma data.
+    #[must_use]
+    pub const fn is_eos(&self) -> bool {
+        self.eos
+    }
+
+    /// Returns the underlying reader as well as the (possibly empty)
+    /// buffer that may contain some unprocessed data.
+    #[must_use]
+    pub fn into_inner(self) -> (R, Vec<u8>) {
+        (self.reader, self.input.into_vec())
+    }
+}
+
+impl<R: Read> Read for LzmaAloneReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        read_until_eos(
+            &mut self.reader,
+            &mut self.input,
+            &mut self.eos,
+            buf,
+            |input, buf| self.decoder.decode(input, buf),
+        )
+    }
+}
+
+/// Reader for lzip (.lz) files.
+/// All members are decoded, data after the last member that is not a lzip member is ignored.
+#[derive(Debug)]
+pub struct LzipReader<R: Read + 'static> {
+    /// the inner decoder, on the heap.
+    decoder: Box<LzipDecoder<'static>>,
+    /// the underlying stream
+    reader: R,
+    /// Data read from the underlying stream that was not decoded yet.
+    input: InputBuffer,
+    /// Are we at the end of the last member and should return eof?
+    eos: bool,
+}
+
+impl<R: Read> LzipReader<R> {
+    /// Creates a new instance of `LzipReader`
+    /// This reader will heap allocate an internal 8k io buffer to read from R.
+    /// This reader will allocate up to about 3GB of additional memory in heap for the lzma dictionary
+    /// depending on the input file.
+    #[allow(clippy::missing_panics_doc)] //We never actually panic.
+    #[must_use]
+    pub fn new(r: R) -> Self {
+        Self::new_with_buffer_size(r, NonZeroUsize::new(8192).expect("Impossible to fail"))
+    }
+
+    #[must_use]
+    pub fn new_with_buffer_size(r: R, buffer_size: NonZeroUsize) -> Self {
+        Self::new_with_buffer_size_and_decoder(
+            r,
+            buffer_size,
+            Box::new(LzipDecoder::with_alloc_dict_size(DICT_SIZE_MAX)),
+        )
+    }
+
+    #[must_use]
+    pub fn new_with_buffer_size_and_decoder(
+        r: R,
+        buffer_size: NonZeroUsize,
+        decoder: Box<LzipDecoder<'static>>,
+    ) -> Self {
+        Self {
+            decoder,
+            reader: r,
+            input: InputBuffer::new(buffer_size),
+            eos: false,
+        }
+    }
+
+    /// Reset the decoder to possibly decode the next fresh lzip file.
+    pub fn reset(&mut self) {
+        self.eos = false;
+        self.decoder.reset();
+    }
+
+    /// Returns true if the reader reached the end of the last member.
+    #[must_use]
+    pub const fn is_eos(&self) -> bool {
+        self.eos
+    }
+
+    /// Returns the underlying reader as well as the (possibly empty)
+    /// buffer that may contain some unprocessed data.
+    #[must_use]
+    pub fn into_inner(self) -> (R, Vec<u8>) {
+        (self.reader, self.input.into_vec())
+    }
+}
+
+impl<R: Read> Read for LzipReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        read_until_eos(
+            &mut self.reader,
+            &mut self.input,
+            &mut self.eos,
+            buf,
+            |input, buf| self.decoder.decode(input, buf),
+        )
+    }
+}
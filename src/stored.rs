@@ -0,0 +1,244 @@
+use crate::container::{
+    enc_lzma2_block_header, enc_stream_header, BlockTrailer, XzCheckState, BLOCK_HEADER_SIZE,
+};
+use crate::{XzCheckType, XzError, XzNextBlockResult};
+
+/// Maximum amount of bytes in an uncompressed lzma2 chunk.
+const CHUNK_SIZE_MAX: usize = 1 << 16;
+
+/// What the encoder does next once all pending bytes are written.
+#[derive(Clone, Debug, Copy, Default, Ord, PartialEq, Eq, PartialOrd, Hash)]
+enum XzStoredEncoderState {
+    /// Accepting data.
+    #[default]
+    Data,
+    /// Finishing, the end of the block is written next.
+    BlockEnd,
+    /// Finishing, the index and stream footer are written next.
+    Trailer,
+    /// The stream is finished once the pending bytes are written.
+    Done,
+    /// The stream is finished, the encoder must be reset.
+    NeedsReset,
+}
+
+/// Encoder that writes .xz streams without compressing the data.
+///
+/// The data is stored in a single block with a lzma2 filter that only consists of uncompressed chunks.
+/// This is useful to wrap data that is already compressed, or where speed matters more than size.
+/// The output can be decoded by every .xz decoder with the smallest possible dictionary (4096 bytes).
+///
+/// The encoder does not need an allocator, output is written incrementally to a buffer of any size.
+#[derive(Debug, Clone)]
+pub struct XzStoredEncoder {
+    /// Check type of the stream.
+    check_type: XzCheckType,
+    /// Check of the uncompressed data.
+    check: XzCheckState,
+    /// Bytes that were produced but not yet written to the output.
+    pending: [u8; 64],
+    /// Amount of bytes of pending that were already written to the output.
+    pending_pos: usize,
+    /// Amount of valid bytes in pending.
+    pending_size: usize,
+    /// Was the stream header already produced?
+    header_written: bool,
+    /// Was the block header already produced?
+    block_started: bool,
+    /// Amount of data bytes that follow the current chunk header.
+    chunk_remaining: usize,
+    /// Amount of lzma2 bytes of the block.
+    compressed_size: u64,
+    /// Amount of uncompressed bytes of the block.
+    uncompressed_size: u64,
+    /// What to do next.
+    state: XzStoredEncoderState,
+}
+
+impl XzStoredEncoder {
+    /// Creates a new stored encoder that writes a stream with the given check type.
+    #[must_use]
+    pub const fn new(check_type: XzCheckType) -> Self {
+        Self {
+            check_type,
+            check: XzCheckState::new(check_type),
+            pending: [0; 64],
+            pending_pos: 0,
+            pending_size: 0,
+            header_written: false,
+            block_started: false,
+            chunk_remaining: 0,
+            compressed_size: 0,
+            uncompressed_size: 0,
+            state: XzStoredEncoderState::Data,
+        }
+    }
+
+    /// Resets the encoder to write a new stream with the same check type.
+    pub const fn reset(&mut self) {
+        *self = Self::new(self.check_type);
+    }
+
+    /// Returns the check type of the stream.
+    #[must_use]
+    pub const fn check_type(&self) -> XzCheckType {
+        self.check_type
+    }
+
+    /// Adds bytes to the pending buffer.
+    fn push(&mut self, bytes: &[u8]) {
+        self.pending[self.pending_size..self.pending_size + bytes.len()].copy_from_slice(bytes);
+        self.pending_size += bytes.len();
+    }
+
+    /// Writes as many pending bytes as possible to output. Returns the amount of bytes written.
+    fn drain(&mut self, output: &mut [u8]) -> usize {
+        let size = (self.pending_size - self.pending_pos).min(output.len());
+        output[..size].copy_from_slice(&self.pending[self.pending_pos..self.pending_pos + size]);
+        self.pending_pos += size;
+        if self.pending_pos == self.pending_size {
+            self.pending_pos = 0;
+            self.pending_size = 0;
+        }
+        size
+    }
+
+    /// Produces the stream header if it was not produced yet.
+    fn push_stream_header(&mut self) {
+        if !self.header_written {
+            self.push(&enc_stream_header(self.check_type));
+            self.header_written = true;
+        }
+    }
+
+    /// Stores the next block of input data and writes it to the output.
+    ///
+    /// Returns `XzNextBlockResult::NeedMoreData` with the amount of input consumed and output produced.
+    /// Input that was not consumed must be passed again at the start of the input of the next call.
+    ///
+    /// # Errors
+    /// `XzError::NeedsReset` if the stream was already finished.
+    pub fn encode(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> Result<XzNextBlockResult, XzError> {
+        if self.state != XzStoredEncoderState::Data {
+            return Err(XzError::NeedsReset);
+        }
+
+        let mut consumed = 0;
+        let mut produced = 0;
+        loop {
+            produced += self.drain(&mut output[produced..]);
+            if self.pending_size != 0 {
+                break;
+            }
+
+            if self.chunk_remaining > 0 {
+                let size = self
+                    .chunk_remaining
+                    .min(input.len() - consumed)
+                    .min(output.len() - produced);
+                let data = &input[consumed..consumed + size];
+                output[produced..produced + size].copy_from_slice(data);
+                self.check.update(data);
+                self.chunk_remaining -= size;
+                self.compressed_size += size as u64;
+                self.uncompressed_size += size as u64;
+                consumed += size;
+                produced += size;
+                if self.chunk_remaining > 0 {
+                    break;
+                }
+                continue;
+            }
+
+            if consumed == input.len() {
+                break;
+            }
+
+            self.push_stream_header();
+            if !self.block_started {
+                self.push(&enc_lzma2_block_header(0));
+                self.block_started = true;
+            }
+
+            // Uncompressed chunk with (0x01) or without (0x02) dictionary reset, size - 1 in big endian
+            let size = (input.len() - consumed).min(CHUNK_SIZE_MAX);
+            let control = if self.compressed_size == 0 { 1 } else { 2 };
+            #[allow(clippy::cast_possible_truncation)] //size is at most 65536
+            let size_bytes = ((size - 1) as u16).to_be_bytes();
+            self.push(&[control, size_bytes[0], size_bytes[1]]);
+            self.compressed_size += 3;
+            self.chunk_remaining = size;
+        }
+
+        Ok(XzNextBlockResult::NeedMoreData(consumed, produced))
+    }
+
+    /// Writes the end of the block, the index and the stream footer to the output.
+    ///
+    /// Returns `XzNextBlockResult::NeedMoreData` with the amount of output produced if the output
+    /// was too small, call finish again with more output space in that case.
+    /// Returns `XzNextBlockResult::EndOfStream` once the stream is complete.
+    /// The encoder must be reset before it can write the next stream.
+    ///
+    /// # Errors
+    /// `XzError::UnexpectedEndOfInput` if an uncompressed chunk still needs input from a previous call of encode.
+    /// `XzError::NeedsReset` if the stream was already finished.
+    pub fn finish(&mut self, output: &mut [u8]) -> Result<XzNextBlockResult, XzError> {
+        if self.chunk_remaining > 0 {
+            return Err(XzError::UnexpectedEndOfInput);
+        }
+
+        let mut produced = 0;
+        loop {
+            produced += self.drain(&mut output[produced..]);
+            if self.pending_size != 0 {
+                return Ok(XzNextBlockResult::NeedMoreData(0, produced));
+            }
+
+            match self.state {
+                XzStoredEncoderState::Data => {
+                    self.push_stream_header();
+                    self.state = XzStoredEncoderState::BlockEnd;
+                }
+                XzStoredEncoderState::BlockEnd => {
+                    if self.block_started {
+                        // End marker, block padding and check
+                        self.push(&[0]);
+                        self.compressed_size += 1;
+                        let padding = [0u8; 3];
+                        let padding_size = (4 - (self.compressed_size % 4) as usize) % 4;
+                        self.push(&padding[..padding_size]);
+                        let mut check = [0u8; 32];
+                        let check_size = self.check.finish(&mut check);
+                        self.push(&check[..check_size]);
+                    }
+                    self.state = XzStoredEncoderState::Trailer;
+                }
+                XzStoredEncoderState::Trailer => {
+                    let trailer = if self.block_started {
+                        BlockTrailer::new(
+                            BLOCK_HEADER_SIZE as u64
+                                + self.compressed_size
+                                + self.check_type.check_size() as u64,
+                            self.uncompressed_size,
+                            self.check_type,
+                        )
+                    } else {
+                        BlockTrailer::empty(self.check_type)
+                    };
+                    self.push(trailer.as_slice());
+                    self.state = XzStoredEncoderState::Done;
+                }
+                XzStoredEncoderState::Done => {
+                    self.state = XzStoredEncoderState::NeedsReset;
+                    return Ok(XzNextBlockResult::EndOfStream(0, produced));
+                }
+                XzStoredEncoderState::NeedsReset => return Err(XzError::NeedsReset),
+            }
+        }
+    }
+}
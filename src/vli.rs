@@ -18,12 +18,6 @@ impl VliDecoder {
         }
     }
 
-    /// Reset back to initial state
-    pub const fn reset(&mut self) {
-        self.vli_bits = 0;
-        self.vli = 0;
-    }
-
     /// Decodes a vli with a buffer that is known to hold the full vli.
     /// Returns (decoded vli, amount of bytes consumed)
     ///
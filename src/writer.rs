@@ -0,0 +1,224 @@
+extern crate std;
+
+use crate::container::{
+    enc_lzma2_block_header, enc_stream_header, BlockTrailer, XzCheckState, BLOCK_HEADER_SIZE,
+};
+use crate::{Lzma2Encoder, Lzma2EncoderOptions, XzCheckType, XzError};
+use alloc::vec::Vec;
+use std::io::Write;
+
+/// Writer that compresses everything written to it into a single .xz stream.
+///
+/// The data is stored in a single lzma2 block, or no block at all if nothing was written.
+/// Call `finish` to write the end of the stream and get the inner writer back.
+/// If the writer is dropped instead, then the stream is finished and all errors are ignored.
+#[derive(Debug)]
+pub struct XzWriter<W: Write> {
+    /// The underlying stream. Only None after finishing.
+    writer: Option<W>,
+    /// The lzma2 encoder
+    encoder: Lzma2Encoder,
+    /// Check type of the stream.
+    check_type: XzCheckType,
+    /// Check of the uncompressed data of the current block.
+    check: XzCheckState,
+    /// Compressed data that was not yet written to the underlying stream.
+    buffer: Vec<u8>,
+    /// Was the stream header already written?
+    header_written: bool,
+    /// Was the block header of the current block already written?
+    block_started: bool,
+    /// Amount of lzma2 bytes of the current block.
+    compressed_size: u64,
+    /// Amount of uncompressed bytes of the current block.
+    uncompressed_size: u64,
+    /// Unpadded size and uncompressed size of the block, once it is finished.
+    record: Option<(u64, u64)>,
+    /// Was the end of the stream written or attempted to be written?
+    finished: bool,
+}
+
+impl<W: Write> XzWriter<W> {
+    /// Creates a new instance of `XzWriter` that compresses with preset level 6.
+    /// The check type is crc64, or crc32 if the crc64 feature is disabled.
+    #[must_use]
+    pub fn new(w: W) -> Self {
+        Self::new_with_preset(w, 6)
+    }
+
+    /// Creates a new instance of `XzWriter` that compresses with the given preset level (0-9).
+    /// The check type is crc64, or crc32 if the crc64 feature is disabled.
+    #[allow(clippy::missing_panics_doc)] //We never actually panic.
+    #[must_use]
+    pub fn new_with_preset(w: W, level: u8) -> Self {
+        #[cfg(feature = "crc64")]
+        let check_type = XzCheckType::Crc64;
+        #[cfg(not(feature = "crc64"))]
+        let check_type = XzCheckType::Crc32;
+
+        Self::new_with_options(w, &Lzma2EncoderOptions::preset(level), check_type)
+            .expect("presets are always valid")
+    }
+
+    /// Creates a new instance of `XzWriter` with the given encoder options and check type.
+    ///
+    /// # Errors
+    /// propagated from `Lzma2Encoder::new` if the options are not valid.
+    pub fn new_with_options(
+        w: W,
+        options: &Lzma2EncoderOptions,
+        check_type: XzCheckType,
+    ) -> Result<Self, XzError> {
+        Ok(Self {
+            writer: Some(w),
+            encoder: Lzma2Encoder::new(options)?,
+            check_type,
+            check: XzCheckState::new(check_type),
+            buffer: Vec::new(),
+            header_written: false,
+            block_started: false,
+            compressed_size: 0,
+            uncompressed_size: 0,
+            record: None,
+            finished: false,
+        })
+    }
+
+    /// Returns a reference to the underlying stream.
+    #[allow(clippy::missing_panics_doc)] //We never actually panic.
+    #[must_use]
+    pub const fn get_ref(&self) -> &W {
+        self.writer.as_ref().expect("only None after finishing")
+    }
+
+    /// Returns a mutable reference to the underlying stream.
+    /// Writing to it directly corrupts the .xz stream.
+    #[allow(clippy::missing_panics_doc)] //We never actually panic.
+    pub const fn get_mut(&mut self) -> &mut W {
+        self.writer.as_mut().expect("only None after finishing")
+    }
+
+    /// Writes the end of the stream and returns the underlying stream.
+    ///
+    /// # Errors
+    /// propagated from the underlying stream.
+    #[allow(clippy::missing_panics_doc)] //We never actually panic.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.finish_stream()?;
+        Ok(self.writer.take().expect("only None after finishing"))
+    }
+
+    /// Writes the buffered compressed data to the underlying stream.
+    fn write_buffer(&mut self) -> std::io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        self.writer
+            .as_mut()
+            .expect("only None after finishing")
+            .write_all(&self.buffer)?;
+        self.compressed_size += self.buffer.len() as u64;
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Writes the stream header if it was not written yet.
+    fn write_stream_header(&mut self) -> std::io::Result<()> {
+        if self.header_written {
+            return Ok(());
+        }
+
+        let header = enc_stream_header(self.check_type);
+        self.get_mut().write_all(&header)?;
+        self.header_written = true;
+        Ok(())
+    }
+
+    /// Writes the block header if the block was not started yet.
+    fn start_block(&mut self) -> std::io::Result<()> {
+        self.write_stream_header()?;
+        if self.block_started {
+            return Ok(());
+        }
+
+        let header = enc_lzma2_block_header(self.encoder.dict_size_props());
+        self.get_mut().write_all(&header)?;
+        self.block_started = true;
+        self.compressed_size = 0;
+        self.uncompressed_size = 0;
+        Ok(())
+    }
+
+    /// Writes the end of the current block, if there is one.
+    fn finish_block(&mut self) -> std::io::Result<()> {
+        if !self.block_started {
+            return Ok(());
+        }
+
+        self.encoder.finish(&mut self.buffer);
+        self.write_buffer()?;
+
+        let padding = [0u8; 3];
+        let padding_size = (4 - (self.compressed_size % 4) as usize) % 4;
+        let mut check = [0u8; 32];
+        let check_size = self.check.finish(&mut check);
+        self.get_mut().write_all(&padding[..padding_size])?;
+        self.get_mut().write_all(&check[..check_size])?;
+
+        self.record = Some((
+            BLOCK_HEADER_SIZE as u64 + self.compressed_size + check_size as u64,
+            self.uncompressed_size,
+        ));
+        self.block_started = false;
+        Ok(())
+    }
+
+    /// Writes the end of the stream. Does nothing if the stream was already finished.
+    fn finish_stream(&mut self) -> std::io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+
+        self.write_stream_header()?;
+        self.finish_block()?;
+
+        let trailer = match self.record {
+            Some((unpadded_size, uncompressed_size)) => {
+                BlockTrailer::new(unpadded_size, uncompressed_size, self.check_type)
+            }
+            None => BlockTrailer::empty(self.check_type),
+        };
+
+        let writer = self.get_mut();
+        writer.write_all(trailer.as_slice())?;
+        writer.flush()
+    }
+}
+
+impl<W: Write> Write for XzWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        self.start_block()?;
+        self.encoder.encode(buf, &mut self.buffer);
+        self.check.update(buf);
+        self.uncompressed_size += buf.len() as u64;
+        self.write_buffer()?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.write_buffer()?;
+        self.get_mut().flush()
+    }
+}
+
+impl<W: Write> Drop for XzWriter<W> {
+    fn drop(&mut self) {
+        _ = self.finish_stream();
+    }
+}
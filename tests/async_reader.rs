@@ -0,0 +1,154 @@
+#![cfg(feature = "futures-io")]
+
+use futures_io::AsyncRead;
+use std::future::{poll_fn, Future};
+use std::io::ErrorKind;
+use std::pin::{pin, Pin};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use xz4rust::{AsyncXzReader, XzError};
+
+/// Counts how often it was woken.
+struct CountingWaker(AtomicUsize);
+
+impl Wake for CountingWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Polls the future until it is ready, the future must wake the waker every time it is pending.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+    let waker = Waker::from(counter.clone());
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        let woken = counter.0.load(Ordering::Relaxed);
+        if let Poll::Ready(result) = future.as_mut().poll(&mut cx) {
+            return result;
+        }
+        assert_ne!(counter.0.load(Ordering::Relaxed), woken);
+    }
+}
+
+/// Reader that returns pending before every read and only returns a few bytes at once.
+struct SlowReader {
+    data: Vec<u8>,
+    position: usize,
+    chunk_size: usize,
+    pending: bool,
+}
+
+impl SlowReader {
+    fn new(data: &[u8], chunk_size: usize) -> Self {
+        Self {
+            data: data.to_vec(),
+            position: 0,
+            chunk_size,
+            pending: true,
+        }
+    }
+}
+
+impl AsyncRead for SlowReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        if self.pending {
+            self.pending = false;
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+
+        self.pending = true;
+        let size = buf
+            .len()
+            .min(self.chunk_size)
+            .min(self.data.len() - self.position);
+        buf[..size].copy_from_slice(&self.data[self.position..self.position + size]);
+        self.position += size;
+        Poll::Ready(Ok(size))
+    }
+}
+
+async fn read_to_end<R: AsyncRead + Unpin>(reader: &mut R) -> std::io::Result<Vec<u8>> {
+    let mut result = Vec::new();
+    let mut buf = [0u8; 1000];
+    loop {
+        let count = poll_fn(|cx| Pin::new(&mut *reader).poll_read(cx, &mut buf)).await?;
+        if count == 0 {
+            return Ok(result);
+        }
+        result.extend_from_slice(&buf[..count]);
+    }
+}
+
+#[test]
+pub fn test_async_reader() {
+    for chunk_size in [1, 7, 4096, 1 << 20] {
+        let mut reader = AsyncXzReader::new(SlowReader::new(
+            include_bytes!("../test_files/java_native_utils_riscv64.so.xz"),
+            chunk_size,
+        ));
+        let result = block_on(read_to_end(&mut reader)).unwrap();
+        assert!(reader.is_eos());
+        assert_eq!(
+            result.as_slice(),
+            include_bytes!("../test_files/java_native_utils_riscv64.so")
+        );
+    }
+}
+
+#[test]
+pub fn test_async_reader_leftover() {
+    let mut data = include_bytes!("../test_files/good-1-block_header-1.xz").to_vec();
+    let stream_size = data.len();
+    data.extend_from_slice(b"trailing data");
+
+    let mut reader = AsyncXzReader::new(SlowReader::new(&data, 1 << 20));
+    let peeked = block_on(reader.peek_inner(|buf| buf[..6].to_vec())).unwrap();
+    assert_eq!(peeked, &data[..6]);
+    let result = block_on(read_to_end(&mut reader)).unwrap();
+    assert_eq!(result.as_slice(), b"Hello\nWorld!\n");
+    let peeked = block_on(reader.peek_inner(<[u8]>::to_vec)).unwrap();
+    assert_eq!(peeked, b"trailing data");
+
+    let (inner, leftover) = reader.into_inner();
+    assert_eq!(leftover.as_slice(), &data[stream_size..]);
+    assert_eq!(inner.position, data.len());
+}
+
+#[test]
+pub fn test_async_reader_multi_stream() {
+    let mut reader = AsyncXzReader::new(SlowReader::new(
+        include_bytes!("../test_files/good-0catpad-empty.xz"),
+        3,
+    ));
+    reader.set_multi_stream(true);
+    assert!(block_on(read_to_end(&mut reader)).unwrap().is_empty());
+    assert!(reader.is_eos());
+}
+
+#[test]
+pub fn test_async_reader_errors() {
+    let data = include_bytes!("../test_files/java_native_utils_riscv64.so.xz");
+    let mut reader = AsyncXzReader::new(SlowReader::new(&data[..data.len() / 2], 4096));
+    let err = block_on(read_to_end(&mut reader)).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+
+    let mut reader = AsyncXzReader::new(SlowReader::new(
+        include_bytes!("../test_files/bad-1-check-crc64.xz"),
+        4096,
+    ));
+    let err = block_on(read_to_end(&mut reader)).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert!(err.into_inner().unwrap().downcast::<XzError>().is_ok());
+
+    let mut reader = AsyncXzReader::new(SlowReader::new(&[], 4096));
+    let err = block_on(reader.peek_inner(|_| ())).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+}
@@ -0,0 +1,212 @@
+mod common;
+
+use common::run_xz;
+use std::io::{Cursor, Read};
+use xz4rust::{
+    BcjDecoder, BcjEncoder, BcjFilter, Lzma2Decoder, XzError, XzNextBlockResult, XzReader,
+};
+
+const AMD64: &[u8] = include_bytes!("../test_files/java_native_utils_amd64.so");
+const ARMEL: &[u8] = include_bytes!("../test_files/java_native_utils_armel.so");
+const RISCV64: &[u8] = include_bytes!("../test_files/java_native_utils_riscv64.so");
+
+const ALL_FILTERS: [BcjFilter; 8] = [
+    BcjFilter::X86,
+    BcjFilter::PowerPc,
+    BcjFilter::IntelIthanium64,
+    BcjFilter::Arm,
+    BcjFilter::ArmThumb,
+    BcjFilter::Sparc,
+    BcjFilter::Arm64,
+    BcjFilter::RiscV,
+];
+
+/// Runs the filter over data, passing at most step bytes to each call.
+fn convert(data: &[u8], step: usize, mut filter: impl FnMut(&mut [u8]) -> usize) -> Vec<u8> {
+    let mut result = data.to_vec();
+    let mut position = 0;
+    let mut end = 0;
+    while end < result.len() {
+        end = result.len().min(end.max(position) + step);
+        position += filter(&mut result[position..end]);
+    }
+    result
+}
+
+fn encode(data: &[u8], filter: BcjFilter, start_offset: u32, step: usize) -> Vec<u8> {
+    let mut encoder = BcjEncoder::new(filter, start_offset).unwrap();
+    convert(data, step, |buf| encoder.encode(buf))
+}
+
+fn decode(data: &[u8], filter: BcjFilter, start_offset: u32, step: usize) -> Vec<u8> {
+    let mut decoder = BcjDecoder::new(filter, start_offset).unwrap();
+    convert(data, step, |buf| decoder.decode(buf))
+}
+
+/// Returns the data of the single block of a .xz file before the bcj filter was applied to it.
+fn bcj_data_of_block(xz: &[u8], size: usize) -> Vec<u8> {
+    let block_header_size = (usize::from(xz[12]) + 1) * 4;
+    let mut decoder = Lzma2Decoder::with_alloc_dict_size(1 << 23, 1 << 23);
+    let mut out = vec![0u8; size];
+    let result = decoder
+        .decode(&xz[12 + block_header_size..], &mut out)
+        .unwrap();
+    assert!(matches!(result, XzNextBlockResult::EndOfStream(_, _)));
+    assert_eq!(result.output_produced(), size);
+    out
+}
+
+#[test]
+pub fn test_bcj_encoder_matches_liblzma() {
+    // These files were created by xz-utils, the lzma2 data of the block is the output of the bcj encoder of liblzma.
+    for (xz, data, filter, start_offset) in [
+        (
+            include_bytes!("../test_files/bcj-offset-x86.xz").as_slice(),
+            AMD64,
+            BcjFilter::X86,
+            4661,
+        ),
+        (
+            include_bytes!("../test_files/bcj-offset-powerpc.xz").as_slice(),
+            AMD64,
+            BcjFilter::PowerPc,
+            65536,
+        ),
+        (
+            include_bytes!("../test_files/bcj-offset-ia64.xz").as_slice(),
+            AMD64,
+            BcjFilter::IntelIthanium64,
+            4656,
+        ),
+        (
+            include_bytes!("../test_files/bcj-offset-arm.xz").as_slice(),
+            ARMEL,
+            BcjFilter::Arm,
+            32772,
+        ),
+        (
+            include_bytes!("../test_files/bcj-offset-armthumb.xz").as_slice(),
+            ARMEL,
+            BcjFilter::ArmThumb,
+            32770,
+        ),
+        (
+            include_bytes!("../test_files/bcj-offset-arm64.xz").as_slice(),
+            AMD64,
+            BcjFilter::Arm64,
+            262_144,
+        ),
+        (
+            include_bytes!("../test_files/bcj-offset-sparc.xz").as_slice(),
+            AMD64,
+            BcjFilter::Sparc,
+            4096,
+        ),
+        (
+            include_bytes!("../test_files/bcj-offset-riscv.xz").as_slice(),
+            RISCV64,
+            BcjFilter::RiscV,
+            524_290,
+        ),
+    ] {
+        let data = &data[..65536];
+        let expected = bcj_data_of_block(xz, data.len());
+        for step in [1, 3, 16, 4096, data.len()] {
+            let encoded = encode(data, filter, start_offset, step);
+            assert_eq!(encoded, expected, "{filter:?} {step}");
+            assert_eq!(decode(&encoded, filter, start_offset, step), data);
+        }
+    }
+}
+
+#[test]
+pub fn test_bcj_round_trip() {
+    for filter in ALL_FILTERS {
+        for data in [AMD64, ARMEL, RISCV64] {
+            for start_offset in [0, 16 * 4097, u32::MAX - 15] {
+                let encoded = encode(data, filter, start_offset, 100_000);
+                assert_eq!(decode(&encoded, filter, start_offset, 7), data);
+            }
+        }
+    }
+}
+
+#[test]
+pub fn test_bcj_state() {
+    let mut encoder = BcjEncoder::new(BcjFilter::X86, 5).unwrap();
+    assert_eq!(encoder.filter(), BcjFilter::X86);
+    let mut buf = [0xe8, 0, 0, 0, 0];
+    // An x86 call needs 5 bytes, the encoder cannot tell if the last 4 bytes are part of one.
+    assert_eq!(encoder.encode(&mut buf[..4]), 0);
+    assert_eq!(encoder.pos(), 5);
+    assert_eq!(encoder.encode(&mut buf), 5);
+    assert_eq!(encoder.pos(), 10);
+    assert_eq!(buf, [0xe8, 10, 0, 0, 0]);
+
+    let mut decoder = BcjDecoder::new(BcjFilter::X86, 5).unwrap();
+    assert_eq!(decoder.filter(), BcjFilter::X86);
+    assert_eq!(decoder.decode(&mut buf), 5);
+    assert_eq!(decoder.pos(), 10);
+    assert_eq!(buf, [0xe8, 0, 0, 0, 0]);
+
+    // The last instruction is converted even though the data is not a multiple of 16 bytes.
+    let mut buf = [0, 0, 0, 0x94, 0, 0, 0, 0x94];
+    let mut encoder = BcjEncoder::new(BcjFilter::Arm64, 16).unwrap();
+    assert_eq!(encoder.encode(&mut buf), 8);
+    assert_eq!(buf, [4, 0, 0, 0x94, 5, 0, 0, 0x94]);
+    let mut decoder = BcjDecoder::new(BcjFilter::Arm64, 16).unwrap();
+    assert_eq!(decoder.decode(&mut buf), 8);
+    assert_eq!(buf, [0, 0, 0, 0x94, 0, 0, 0, 0x94]);
+
+    for filter in ALL_FILTERS {
+        assert_eq!(BcjFilter::try_from(filter.id()).unwrap(), filter);
+        assert_eq!(
+            BcjEncoder::new(filter, filter.alignment()).unwrap().pos(),
+            filter.alignment()
+        );
+        if filter.alignment() > 1 {
+            assert_eq!(
+                BcjEncoder::new(filter, 1).unwrap_err(),
+                XzError::BcjFilterOffsetUnaligned(1)
+            );
+            assert_eq!(
+                BcjDecoder::new(filter, 1).unwrap_err(),
+                XzError::BcjFilterOffsetUnaligned(1)
+            );
+        }
+    }
+}
+
+#[test]
+pub fn test_bcj_xz_compat() {
+    // The data does not end on a multiple of 16 bytes, so the tail of the data must be converted as well.
+    let mut arm64_tail = vec![0u8; 16];
+    arm64_tail.extend_from_slice(&[0, 0, 0, 0x94]);
+    for (filter, option, data) in [
+        (BcjFilter::X86, "--x86", AMD64),
+        (BcjFilter::Arm64, "--arm64", arm64_tail.as_slice()),
+        (BcjFilter::Arm64, "--arm64", &AMD64[..AMD64.len() - 4]),
+        (BcjFilter::RiscV, "--riscv", &RISCV64[..RISCV64.len() - 6]),
+        (BcjFilter::ArmThumb, "--armthumb", &ARMEL[..ARMEL.len() - 2]),
+    ] {
+        let Some(expected) = xz_bcj_encode(option, data) else {
+            println!("xz is not installed, skipping test");
+            return;
+        };
+        assert_eq!(encode(data, filter, 0, 65536), expected, "{filter:?}");
+        assert_eq!(decode(&expected, filter, 0, 65536), data, "{filter:?}");
+
+        let compressed = run_xz(&[option, "--lzma2=preset=0", "-c"], data).unwrap();
+        let mut decompressed = Vec::new();
+        XzReader::new(Cursor::new(compressed))
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, data, "{filter:?}");
+    }
+}
+
+/// Runs the bcj filter of xz-utils by compressing and decompressing with only the bcj filter in the decoder.
+fn xz_bcj_encode(option: &str, data: &[u8]) -> Option<Vec<u8>> {
+    let compressed = run_xz(&["--format=raw", option, "--lzma2=preset=0", "-c"], data)?;
+    run_xz(&["--format=raw", "--lzma2=preset=0", "-dc"], &compressed)
+}
@@ -0,0 +1,15 @@
+use xz4rust::XzDecoder;
+
+#[test]
+pub fn test_arm64_unaligned_end() {
+    // The data is 108 bytes long, the last 3 instructions are not part of a multiple of 16 bytes.
+    let compressed = include_bytes!("../test_files/arm64-unaligned-end.xz");
+    let expected = include_bytes!("../test_files/arm64-unaligned-end");
+
+    let mut decoder = XzDecoder::in_heap();
+    let mut output = vec![0u8; 4096];
+    let result = decoder.decode(compressed, &mut output).unwrap();
+    assert!(result.is_end_of_stream());
+    assert_eq!(result.input_consumed(), compressed.len());
+    assert_eq!(&output[..result.output_produced()], expected.as_slice());
+}
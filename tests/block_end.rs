@@ -0,0 +1,129 @@
+use xz4rust::{XzBlockInfo, XzCheckResult, XzCheckType, XzDecoder, XzError, XzFileInfo};
+
+const MULTI: &[u8] = include_bytes!("../test_files/multi_block_streams.xz");
+
+#[test]
+pub fn test_block_end_events() {
+    let info = XzFileInfo::from_slice(MULTI).unwrap();
+    let mut decoder = XzDecoder::in_heap();
+    decoder.set_multi_stream(true);
+    decoder.set_stop_at_block_end(true);
+
+    let mut output = vec![0u8; info.uncompressed_size() as usize];
+    let mut consumed = 0;
+    let mut produced = 0;
+    let mut verified = 0;
+    let mut events = Vec::new();
+    while consumed < MULTI.len() {
+        let result = decoder
+            .decode(&MULTI[consumed..], &mut output[produced..])
+            .unwrap();
+        consumed += result.input_consumed();
+        produced += result.output_produced();
+
+        if let Some(block_end) = decoder.take_block_end() {
+            // The decoder stops right after the check, everything decoded so far is verified.
+            verified += block_end.uncompressed_size;
+            assert_eq!(verified, produced as u64);
+            events.push(block_end);
+        }
+        assert!(decoder.take_block_end().is_none());
+    }
+    decoder.finish().unwrap();
+
+    let mut expected = Vec::new();
+    for (stream_index, stream) in info.streams.iter().enumerate() {
+        for (block_index, block) in stream.blocks.iter().enumerate() {
+            let header = XzBlockInfo::parse(&MULTI[block.compressed_offset as usize..]).unwrap();
+            expected.push((
+                stream_index as u64,
+                block_index as u64,
+                block.unpadded_size,
+                block.unpadded_size
+                    - header.header_size() as u64
+                    - stream.check_type.check_size() as u64,
+                block.uncompressed_size,
+                stream.check_type,
+            ));
+        }
+    }
+    let actual: Vec<_> = events
+        .iter()
+        .map(|e| {
+            assert_eq!(e.check_result, XzCheckResult::Verified);
+            (
+                e.stream_index,
+                e.block_index,
+                e.unpadded_size,
+                e.compressed_size,
+                e.uncompressed_size,
+                e.check_type,
+            )
+        })
+        .collect();
+    assert_eq!(actual.len(), 75);
+    assert_eq!(actual, expected);
+}
+
+#[test]
+pub fn test_block_end_without_stop() {
+    let mut decoder = XzDecoder::in_heap();
+    let mut output = vec![0u8; 1 << 20];
+    let result = decoder.decode(MULTI, &mut output).unwrap();
+    assert!(result.is_end_of_stream());
+
+    // Only the last block of the stream is kept.
+    let block_end = decoder.take_block_end().unwrap();
+    assert_eq!(block_end.stream_index, 0);
+    assert_eq!(block_end.block_index, 51);
+    assert!(decoder.take_block_end().is_none());
+}
+
+#[test]
+pub fn test_block_end_check_result() {
+    let mut output = vec![0u8; 4096];
+    for (data, check_type, check_result) in [
+        (
+            include_bytes!("../test_files/good-1-check-none.xz").as_slice(),
+            XzCheckType::None,
+            XzCheckResult::None,
+        ),
+        (
+            include_bytes!("../test_files/good-1-check-crc32.xz").as_slice(),
+            XzCheckType::Crc32,
+            XzCheckResult::Verified,
+        ),
+        (
+            include_bytes!("../test_files/good-1-check-sha256.xz").as_slice(),
+            XzCheckType::Sha256,
+            XzCheckResult::Verified,
+        ),
+    ] {
+        let mut decoder = XzDecoder::in_heap();
+        decoder.set_stop_at_block_end(true);
+        let result = decoder.decode(data, &mut output).unwrap();
+        assert!(!result.is_end_of_stream());
+        let block_end = decoder.take_block_end().unwrap();
+        assert_eq!(block_end.check_type, check_type);
+        assert_eq!(block_end.check_result, check_result);
+        assert_eq!(block_end.uncompressed_size, result.output_produced() as u64);
+
+        let consumed = result.input_consumed();
+        let result = decoder.decode(&data[consumed..], &mut output).unwrap();
+        assert!(result.is_end_of_stream());
+        assert_eq!(result.input_consumed(), data.len() - consumed);
+        assert!(decoder.take_block_end().is_none());
+    }
+
+    // A block that fails its check is never reported.
+    let mut decoder = XzDecoder::in_heap();
+    decoder.set_stop_at_block_end(true);
+    let err = decoder
+        .decode(
+            include_bytes!("../test_files/bad-1-check-crc32.xz"),
+            &mut output,
+        )
+        .unwrap_err();
+    assert!(matches!(err, XzError::ContentCrc32Mismatch(_, _)));
+    assert!(decoder.take_block_end().is_none());
+}
@@ -0,0 +1,152 @@
+mod common;
+
+use common::COMPRESSED;
+use xz4rust::{BcjFilter, XzBlockInfo, XzDecoder, XzError, XzFilterInfo};
+
+/// Decodes until the first block header was decoded.
+fn first_block_info(data: &[u8]) -> XzBlockInfo {
+    let mut decoder = XzDecoder::in_heap();
+    decoder.set_stop_at_block_header(true);
+    assert!(decoder.block_info().is_none());
+    let mut output = vec![0u8; 4096];
+    let result = decoder.decode(data, &mut output).unwrap();
+    assert_eq!(result.output_produced(), 0);
+    let info = *decoder.block_info().unwrap();
+    assert_eq!(result.input_consumed(), 12 + info.header_size());
+    assert_eq!(XzBlockInfo::parse(&data[12..]).unwrap(), info);
+    info
+}
+
+#[test]
+pub fn test_block_info() {
+    // Expected values are taken from "xz --robot -lvv"
+    let info = first_block_info(COMPRESSED);
+    assert_eq!(info.header_size(), 20);
+    assert_eq!(info.compressed_size(), Some(95546));
+    assert_eq!(info.uncompressed_size(), Some(673_064));
+    assert_eq!(info.dict_size(), 8 << 20);
+    assert_eq!(
+        info.filters(),
+        &[
+            XzFilterInfo::Bcj {
+                filter: BcjFilter::RiscV,
+                start_offset: 0
+            },
+            XzFilterInfo::Lzma2 { dict_size: 8 << 20 }
+        ]
+    );
+
+    let info = first_block_info(include_bytes!("../test_files/good-1-3delta-lzma2.xz"));
+    assert_eq!(info.header_size(), 20);
+    assert_eq!(info.compressed_size(), None);
+    assert_eq!(info.uncompressed_size(), None);
+    assert_eq!(
+        info.filters(),
+        &[
+            XzFilterInfo::Delta { distance: 1 },
+            XzFilterInfo::Delta { distance: 2 },
+            XzFilterInfo::Delta { distance: 3 },
+            XzFilterInfo::Lzma2 {
+                dict_size: 64 << 10
+            }
+        ]
+    );
+
+    for (data, filter, start_offset) in [
+        (
+            include_bytes!("../test_files/bcj-offset-x86.xz").as_slice(),
+            BcjFilter::X86,
+            4661,
+        ),
+        (
+            include_bytes!("../test_files/bcj-offset-arm64.xz").as_slice(),
+            BcjFilter::Arm64,
+            262_144,
+        ),
+        (
+            include_bytes!("../test_files/bcj-offset-riscv.xz").as_slice(),
+            BcjFilter::RiscV,
+            524_290,
+        ),
+    ] {
+        assert_eq!(
+            first_block_info(data).filters()[0],
+            XzFilterInfo::Bcj {
+                filter,
+                start_offset
+            }
+        );
+    }
+}
+
+#[test]
+pub fn test_block_info_reject_filter_chain() {
+    let mut decoder = XzDecoder::in_heap();
+    decoder.set_stop_at_block_header(true);
+    let mut output = vec![0u8; 1 << 20];
+    let mut consumed = 0;
+    let mut produced = 0;
+    loop {
+        let result = decoder
+            .decode(&COMPRESSED[consumed..], &mut output[produced..])
+            .unwrap();
+        consumed += result.input_consumed();
+        produced += result.output_produced();
+        if result.is_end_of_stream() {
+            break;
+        }
+
+        let info = decoder.block_info().unwrap();
+        assert_eq!(info.filters().len(), 2);
+    }
+    assert_eq!(
+        &output[..produced],
+        include_bytes!("../test_files/java_native_utils_riscv64.so")
+    );
+    assert!(decoder.block_info().is_some());
+    decoder.reset();
+    assert!(decoder.block_info().is_none());
+}
+
+#[test]
+pub fn test_block_info_errors() {
+    let header = &COMPRESSED[12..32];
+    assert_eq!(
+        XzBlockInfo::parse(&header[..19]).unwrap_err(),
+        XzError::UnexpectedEndOfInput
+    );
+    assert_eq!(
+        XzBlockInfo::parse(&[]).unwrap_err(),
+        XzError::UnexpectedEndOfInput
+    );
+    let mut corrupted = header.to_vec();
+    corrupted[5] ^= 1;
+    assert!(matches!(
+        XzBlockInfo::parse(&corrupted).unwrap_err(),
+        XzError::BlockHeaderCrc32Mismatch(_, _)
+    ));
+
+    for data in [
+        include_bytes!("../test_files/unsupported-filter_flags-1.xz").as_slice(),
+        include_bytes!("../test_files/unsupported-filter_flags-2.xz").as_slice(),
+        include_bytes!("../test_files/unsupported-block_header.xz").as_slice(),
+    ] {
+        assert_eq!(
+            XzBlockInfo::parse(&data[12..]).unwrap_err(),
+            XzError::UnsupportedBlockHeaderOption
+        );
+    }
+
+    // Filters before lzma2 that are not delta are bcj filters, just like when decoding.
+    let data = include_bytes!("../test_files/unsupported-filter_flags-3.xz");
+    assert_eq!(
+        XzBlockInfo::parse(&data[12..]).unwrap_err(),
+        XzError::UnsupportedBcjFilter(33)
+    );
+
+    let data = include_bytes!("../test_files/bad-bcj-offset-unaligned.xz");
+    assert!(matches!(
+        XzBlockInfo::parse(&data[12..]).unwrap_err(),
+        XzError::BcjFilterOffsetUnaligned(_)
+    ));
+}
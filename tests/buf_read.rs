@@ -0,0 +1,73 @@
+use std::io::{BufRead, Cursor, Read, Write};
+use xz4rust::{XzReader, XzWriter};
+
+fn compress(data: &[u8]) -> Vec<u8> {
+    let mut writer = XzWriter::new_with_preset(Vec::new(), 1);
+    writer.write_all(data).unwrap();
+    writer.finish().unwrap()
+}
+
+#[test]
+pub fn test_buf_read_lines() {
+    let mut data = String::new();
+    for i in 0..100_000 {
+        data.push_str(&format!("line {i}\n"));
+    }
+
+    let reader = XzReader::new(Cursor::new(compress(data.as_bytes())));
+    let mut count = 0;
+    for (i, line) in reader.lines().enumerate() {
+        assert_eq!(line.unwrap(), format!("line {i}"));
+        count += 1;
+    }
+    assert_eq!(count, 100_000);
+}
+
+#[test]
+pub fn test_buf_read_mixed() {
+    let data = include_bytes!("../test_files/java_native_utils_riscv64.so");
+    let mut reader = XzReader::new(Cursor::new(
+        include_bytes!("../test_files/java_native_utils_riscv64.so.xz").to_vec(),
+    ));
+
+    let mut result = Vec::new();
+    let mut buf = [0u8; 777];
+    loop {
+        let available = reader.fill_buf().unwrap();
+        if available.is_empty() {
+            break;
+        }
+        let size = available.len().min(100);
+        result.extend_from_slice(&available[..size]);
+        reader.consume(size);
+
+        // Read also returns the buffered data first.
+        let count = reader.read(&mut buf).unwrap();
+        result.extend_from_slice(&buf[..count]);
+        reader.read_until(0, &mut result).unwrap();
+    }
+
+    assert_eq!(result.as_slice(), data);
+    assert!(reader.fill_buf().unwrap().is_empty());
+    assert_eq!(reader.read(&mut buf).unwrap(), 0);
+}
+
+#[test]
+pub fn test_buf_read_leftover() {
+    let mut data = include_bytes!("../test_files/good-1-block_header-1.xz").to_vec();
+    let stream_size = data.len();
+    data.extend_from_slice(b"trailing data");
+
+    let mut reader = XzReader::new(Cursor::new(data.clone()));
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    assert_eq!(line, "Hello\n");
+    line.clear();
+    reader.read_line(&mut line).unwrap();
+    assert_eq!(line, "World!\n");
+    assert!(reader.fill_buf().unwrap().is_empty());
+    assert!(reader.is_eos());
+
+    let (_, leftover) = reader.into_inner();
+    assert_eq!(leftover.as_slice(), &data[stream_size..]);
+}
@@ -0,0 +1,92 @@
+//! Fixtures and helpers shared by the integration tests.
+// Every test crate only uses some of them.
+#![allow(dead_code)]
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+use xz4rust::{
+    LzipDecoder, Lzma2Decoder, LzmaAloneDecoder, LzmaDecoder, XzDecoder, XzError, XzNextBlockResult,
+};
+
+/// A single block .xz file.
+pub const COMPRESSED: &[u8] = include_bytes!("../../test_files/java_native_utils_riscv64.so.xz");
+
+/// The decompressed data of `COMPRESSED`.
+pub const EXPECTED: &[u8] = include_bytes!("../../test_files/java_native_utils_riscv64.so");
+
+/// A tiny .xz file with a single block.
+pub const HELLO: &[u8] = include_bytes!("../../test_files/good-1-block_header-1.xz");
+
+/// The decoders that decode from a slice into a slice.
+pub trait SliceDecoder {
+    /// Same as the `decode` fn of the decoder.
+    fn decode(&mut self, input: &[u8], output: &mut [u8]) -> Result<XzNextBlockResult, XzError>;
+}
+
+macro_rules! impl_slice_decoder {
+    ($($decoder:ident),*) => {
+        $(
+            impl SliceDecoder for $decoder<'_> {
+                fn decode(
+                    &mut self,
+                    input: &[u8],
+                    output: &mut [u8],
+                ) -> Result<XzNextBlockResult, XzError> {
+                    $decoder::decode(self, input, output)
+                }
+            }
+        )*
+    };
+}
+
+impl_slice_decoder!(
+    XzDecoder,
+    Lzma2Decoder,
+    LzmaDecoder,
+    LzmaAloneDecoder,
+    LzipDecoder
+);
+
+/// Decodes `input` until the end of the stream, passing at most `chunk` bytes of input
+/// and `chunk` bytes of output space to each call. The stream must end with the input.
+pub fn decode_all(
+    decoder: &mut impl SliceDecoder,
+    input: &[u8],
+    chunk: usize,
+) -> Result<Vec<u8>, XzError> {
+    let mut out = Vec::new();
+    let mut buf = vec![0u8; chunk];
+    let mut data = input;
+    loop {
+        let result = decoder.decode(&data[..data.len().min(chunk)], &mut buf)?;
+        data = &data[result.input_consumed()..];
+        out.extend_from_slice(&buf[..result.output_produced()]);
+        if let XzNextBlockResult::EndOfStream(_, _) = result {
+            assert!(data.is_empty());
+            return Ok(out);
+        }
+    }
+}
+
+/// Runs "xz" with the given arguments and `data` as stdin and returns its stdout.
+/// Returns None if xz is not installed.
+pub fn run_xz(args: &[&str], data: &[u8]) -> Option<Vec<u8>> {
+    let mut child = Command::new("/usr/bin/env")
+        .arg("xz")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .ok()?;
+
+    let mut stdin = child.stdin.take().unwrap();
+    let data = data.to_vec();
+    let feeder = std::thread::spawn(move || stdin.write_all(&data).unwrap());
+    let output = child.wait_with_output().unwrap();
+    feeder.join().unwrap();
+    if output.status.code() == Some(127) {
+        return None;
+    }
+    assert!(output.status.success());
+    Some(output.stdout)
+}
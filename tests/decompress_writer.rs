@@ -0,0 +1,131 @@
+mod common;
+
+use common::{COMPRESSED, EXPECTED};
+use std::io::{ErrorKind, Write};
+use std::num::NonZeroUsize;
+use xz4rust::{XzDecoder, XzDecompressWriter, XzError};
+
+#[test]
+pub fn test_decompress_writer() {
+    for chunk_size in [1, 13, 4096, COMPRESSED.len()] {
+        let mut writer = XzDecompressWriter::new(Vec::new());
+        for chunk in COMPRESSED.chunks(chunk_size) {
+            writer.write_all(chunk).unwrap();
+        }
+        assert!(writer.is_eos());
+        let result = writer.finish().unwrap();
+        assert_eq!(result.as_slice(), EXPECTED);
+    }
+
+    // A tiny output buffer needs many decoder calls per write.
+    let mut writer = XzDecompressWriter::new_with_buffer_size_and_decoder(
+        Vec::new(),
+        NonZeroUsize::MIN,
+        XzDecoder::in_heap(),
+    );
+    writer.write_all(COMPRESSED).unwrap();
+    assert_eq!(writer.get_ref().as_slice(), EXPECTED);
+    assert_eq!(writer.finish().unwrap().as_slice(), EXPECTED);
+}
+
+#[test]
+pub fn test_decompress_writer_incomplete() {
+    let mut writer = XzDecompressWriter::new(Vec::new());
+    writer
+        .write_all(&COMPRESSED[..COMPRESSED.len() - 1])
+        .unwrap();
+    assert!(!writer.is_eos());
+    let err = writer.finish().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+
+    let mut writer = XzDecompressWriter::new(Vec::new());
+    writer.write_all(&COMPRESSED[..1000]).unwrap();
+    assert!(!writer.get_mut().is_empty());
+    let partial = writer.into_inner();
+    assert_eq!(partial.as_slice(), &EXPECTED[..partial.len()]);
+}
+
+#[test]
+pub fn test_decompress_writer_trailing_data() {
+    let mut data = COMPRESSED.to_vec();
+    data.extend_from_slice(b"trailing data");
+
+    let mut writer = XzDecompressWriter::new(Vec::new());
+    assert_eq!(writer.write(&data).unwrap(), COMPRESSED.len());
+    assert!(writer.is_eos());
+    assert_eq!(writer.write(b"more").unwrap(), 0);
+    assert_eq!(
+        writer.write_all(b"more").unwrap_err().kind(),
+        ErrorKind::WriteZero
+    );
+    assert_eq!(writer.finish().unwrap().as_slice(), EXPECTED);
+}
+
+#[test]
+pub fn test_decompress_writer_multi_stream() {
+    let mut data = COMPRESSED.to_vec();
+    data.extend_from_slice(&[0; 8]);
+    data.extend_from_slice(include_bytes!("../test_files/good-1-block_header-1.xz"));
+
+    let mut writer = XzDecompressWriter::new(Vec::new());
+    writer.set_multi_stream(true);
+    for chunk in data.chunks(100) {
+        writer.write_all(chunk).unwrap();
+    }
+    let result = writer.finish().unwrap();
+    assert_eq!(&result[..EXPECTED.len()], EXPECTED);
+    assert_eq!(&result[EXPECTED.len()..], b"Hello\nWorld!\n");
+
+    let mut writer = XzDecompressWriter::new(Vec::new());
+    writer.set_multi_stream(true);
+    writer.write_all(COMPRESSED).unwrap();
+    writer.write_all(&[0; 3]).unwrap();
+    let err = writer.finish().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert_eq!(
+        *err.into_inner().unwrap().downcast::<XzError>().unwrap(),
+        XzError::InvalidStreamPadding
+    );
+}
+
+#[test]
+pub fn test_decompress_writer_corrupted() {
+    let mut writer = XzDecompressWriter::new(Vec::new());
+    let err = writer
+        .write_all(include_bytes!("../test_files/bad-1-check-crc64.xz"))
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert!(err.into_inner().unwrap().downcast::<XzError>().is_ok());
+}
+
+#[test]
+pub fn test_decompress_writer_flush() {
+    let mut flushed_output = 0;
+    for split in (13..4096).step_by(97) {
+        // The output buffer can fill up before the written data was decoded entirely.
+        let mut writer = XzDecompressWriter::new_with_buffer_size_and_decoder(
+            Vec::new(),
+            NonZeroUsize::new(16).unwrap(),
+            XzDecoder::in_heap(),
+        );
+        writer.write_all(&COMPRESSED[..split]).unwrap();
+        let written = writer.get_ref().len();
+        writer.flush().unwrap();
+        let flushed = writer.get_ref().len();
+        assert!(flushed >= written);
+        assert_eq!(writer.get_ref().as_slice(), &EXPECTED[..flushed]);
+
+        // Nothing is pending anymore.
+        writer.flush().unwrap();
+        assert_eq!(writer.get_ref().len(), flushed);
+
+        if flushed > written {
+            flushed_output += 1;
+            if flushed_output == 1 {
+                writer.write_all(&COMPRESSED[split..]).unwrap();
+                assert_eq!(writer.finish().unwrap().as_slice(), EXPECTED);
+            }
+        }
+    }
+    assert_ne!(flushed_output, 0);
+}
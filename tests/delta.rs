@@ -0,0 +1,127 @@
+mod common;
+
+use common::run_xz;
+use xz4rust::{DeltaDecoder, DeltaEncoder, Lzma2Decoder, XzError, XzNextBlockResult};
+
+const TIFF: &[u8] = include_bytes!("../test_files/good-1-delta-lzma2.tiff");
+
+/// Returns the data of the single block of a .xz file before the delta filters were applied to it.
+fn delta_data_of_block(xz: &[u8], size: usize) -> Vec<u8> {
+    let block_header_size = (usize::from(xz[12]) + 1) * 4;
+    let mut decoder = Lzma2Decoder::with_alloc_dict_size(1 << 20, 1 << 20);
+    let mut out = vec![0u8; size];
+    let result = decoder
+        .decode(&xz[12 + block_header_size..], &mut out)
+        .unwrap();
+    assert!(matches!(result, XzNextBlockResult::EndOfStream(_, _)));
+    assert_eq!(result.output_produced(), size);
+    out
+}
+
+/// Runs the coder over data in chunks of the given size.
+fn chunked(data: &[u8], step: usize, mut coder: impl FnMut(&mut [u8])) -> Vec<u8> {
+    let mut result = data.to_vec();
+    for chunk in result.chunks_mut(step) {
+        coder(chunk);
+    }
+    result
+}
+
+#[test]
+pub fn test_delta_encoder_matches_liblzma() {
+    // These files were created by xz-utils, the lzma2 data of the block is the output of the delta encoder of liblzma.
+    let expected = delta_data_of_block(
+        include_bytes!("../test_files/good-1-delta-lzma2.tiff.xz"),
+        TIFF.len(),
+    );
+    for step in [1, 2, 3, 255, 256, 257, 100_000, TIFF.len()] {
+        let mut encoder = DeltaEncoder::with_distance(3).unwrap();
+        assert_eq!(chunked(TIFF, step, |c| encoder.encode(c)), expected);
+        let mut decoder = DeltaDecoder::with_distance(3).unwrap();
+        assert_eq!(chunked(&expected, step, |c| decoder.decode(c)), TIFF);
+    }
+
+    let data = include_bytes!("../test_files/good-1-3delta-lzma2");
+    let expected = delta_data_of_block(
+        include_bytes!("../test_files/good-1-3delta-lzma2.xz"),
+        data.len(),
+    );
+    let mut encoded = data.to_vec();
+    for distance in 1..=3 {
+        DeltaEncoder::with_distance(distance)
+            .unwrap()
+            .encode(&mut encoded);
+    }
+    assert_eq!(encoded, expected);
+}
+
+#[test]
+pub fn test_delta_round_trip() {
+    for distance in [1, 2, 7, 16, 255, 256] {
+        let mut encoder = DeltaEncoder::with_distance(distance).unwrap();
+        let mut decoder = DeltaDecoder::with_distance(distance).unwrap();
+        assert_eq!(encoder.distance(), distance);
+        assert_eq!(decoder.distance(), distance);
+        for step in [1, 5, 256, 1000] {
+            let encoded = chunked(TIFF, step, |c| encoder.encode(c));
+            assert_ne!(encoded, TIFF);
+            assert_eq!(chunked(&encoded, 777, |c| decoder.decode(c)), TIFF);
+            encoder.reset();
+            decoder.reset();
+        }
+    }
+}
+
+#[test]
+pub fn test_delta_distance() {
+    for distance in [0, 257, usize::MAX] {
+        assert_eq!(
+            DeltaEncoder::with_distance(distance).unwrap_err(),
+            XzError::UnsupportedDeltaDistance(distance as u64)
+        );
+        assert_eq!(
+            DeltaDecoder::with_distance(distance).unwrap_err(),
+            XzError::UnsupportedDeltaDistance(distance as u64)
+        );
+    }
+
+    let mut encoder = DeltaEncoder::new();
+    assert_eq!(encoder.distance(), 1);
+    let mut data = [1, 2, 4, 8];
+    encoder.encode(&mut data);
+    assert_eq!(data, [1, 1, 2, 4]);
+
+    // A failed reset keeps the previous distance.
+    assert!(encoder.reset_with_distance(300).is_err());
+    assert_eq!(encoder.distance(), 1);
+    encoder.reset_with_distance(2).unwrap();
+    let mut data = [1, 2, 4, 8];
+    encoder.encode(&mut data);
+    assert_eq!(data, [1, 2, 3, 6]);
+
+    let mut decoder = DeltaDecoder::default();
+    decoder.reset_with_distance(2).unwrap();
+    decoder.decode(&mut data);
+    assert_eq!(data, [1, 2, 4, 8]);
+}
+
+#[test]
+pub fn test_delta_xz_compat() {
+    for distance in [1, 4, 100, 256] {
+        let option = format!("--delta=dist={distance}");
+        let Some(compressed) = run_xz(
+            &["--format=raw", &option, "--lzma2=preset=0", "-c"],
+            &TIFF[..100_000],
+        ) else {
+            println!("xz is not installed, skipping test");
+            return;
+        };
+        let expected = run_xz(&["--format=raw", "--lzma2=preset=0", "-dc"], &compressed).unwrap();
+
+        let mut encoder = DeltaEncoder::with_distance(distance).unwrap();
+        assert_eq!(
+            chunked(&TIFF[..100_000], 4096, |c| encoder.encode(c)),
+            expected
+        );
+    }
+}
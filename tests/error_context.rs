@@ -0,0 +1,142 @@
+mod common;
+
+use common::{COMPRESSED, EXPECTED};
+use std::io::{Cursor, Read};
+use xz4rust::{XzDecoder, XzError, XzFileInfo, XzReader};
+
+#[test]
+pub fn test_error_context_check() {
+    let data = include_bytes!("../test_files/bad-1-check-crc32.xz");
+    let mut decoder = XzDecoder::in_heap();
+    assert!(decoder.error_context().is_none());
+
+    let mut output = vec![0u8; 4096];
+    let err = decoder.decode(data, &mut output).unwrap_err();
+    assert!(matches!(err, XzError::ContentCrc32Mismatch(_, _)));
+    let context = decoder.error_context().unwrap();
+    assert_eq!(context.stream_index, 0);
+    assert_eq!(context.block_index, 0);
+    assert_eq!(context.output_produced, 13);
+    assert_eq!(context.uncompressed_offset, 13);
+    // The check is the last part of the block, it is followed by the index and the stream footer.
+    let info =
+        XzFileInfo::from_slice(include_bytes!("../test_files/good-1-check-crc32.xz")).unwrap();
+    let block = &info.streams[0].blocks[0];
+    assert_eq!(
+        context.compressed_offset,
+        block.compressed_offset + block.unpadded_size.next_multiple_of(4)
+    );
+    assert_eq!(context.input_consumed as u64, context.compressed_offset);
+
+    // The context is kept after the decoder refuses to continue.
+    assert_eq!(
+        decoder.decode(data, &mut output).unwrap_err(),
+        XzError::NeedsReset
+    );
+    assert_eq!(decoder.error_context(), Some(context));
+
+    decoder.reset();
+    assert!(decoder.error_context().is_none());
+}
+
+#[test]
+pub fn test_error_context_corrupted_block() {
+    let info =
+        XzFileInfo::from_slice(include_bytes!("../test_files/multi_block_streams.xz")).unwrap();
+    let block = &info.streams[2].blocks[3];
+    let mut data = include_bytes!("../test_files/multi_block_streams.xz").to_vec();
+    // Corrupt the compressed data after the block header.
+    let corrupted = block.compressed_offset as usize + 100;
+    data[corrupted] ^= 0xFF;
+
+    let mut decoder = XzDecoder::in_heap();
+    decoder.set_multi_stream(true);
+    let mut output = vec![0u8; 1 << 20];
+    let mut consumed = 0;
+    let mut produced = 0;
+    let err = loop {
+        match decoder.decode(&data[consumed..], &mut output) {
+            Ok(result) => {
+                consumed += result.input_consumed();
+                produced += result.output_produced();
+            }
+            Err(err) => break err,
+        }
+    };
+    assert!(err.is_corruption());
+
+    let context = decoder.error_context().unwrap();
+    assert_eq!(context.stream_index, 2);
+    assert_eq!(context.block_index, 3);
+    assert_eq!(
+        context.compressed_offset,
+        (consumed + context.input_consumed) as u64
+    );
+    assert_eq!(
+        context.uncompressed_offset,
+        (produced + context.output_produced) as u64
+    );
+    // Input that the lzma decoder read ahead is not counted.
+    assert!(context.compressed_offset > block.compressed_offset);
+    assert!(context.compressed_offset <= corrupted as u64);
+    assert!(context.uncompressed_offset >= block.uncompressed_offset);
+    assert!(context.uncompressed_offset < block.uncompressed_offset + block.uncompressed_size);
+}
+
+#[test]
+pub fn test_error_context_reader() {
+    let mut data = COMPRESSED.to_vec();
+    let len = data.len();
+    data[len / 2] ^= 0x55;
+    let mut reader = XzReader::new(Cursor::new(data));
+    let mut result = Vec::new();
+    let err = reader.read_to_end(&mut result).unwrap_err();
+    assert!(err.downcast::<XzError>().unwrap().is_corruption());
+
+    let context = reader.error_context().unwrap();
+    assert_eq!(
+        context.uncompressed_offset,
+        (result.len() + context.output_produced) as u64
+    );
+    assert!(context.compressed_offset <= len as u64);
+    assert_eq!(&result[..], &EXPECTED[..result.len()]);
+}
+
+#[test]
+pub fn test_error_categories() {
+    let corruption = [
+        XzError::CorruptedDataInLzma,
+        XzError::ContentCrc32Mismatch(1, 2),
+        XzError::StreamHeaderMagicNumberMismatch,
+        XzError::UnexpectedEndOfInput,
+    ];
+    let unsupported = [
+        XzError::UnsupportedCheckType(5),
+        XzError::UnsupportedBlockHeaderOption,
+        XzError::UnsupportedLzmaProperties(41),
+    ];
+    let resource_limit = [
+        XzError::DictionaryTooLarge(1 << 30),
+        XzError::MemoryLimitExceeded(1 << 30),
+    ];
+    for err in corruption {
+        assert!(err.is_corruption());
+        assert!(!err.is_unsupported());
+        assert!(!err.is_resource_limit());
+    }
+    for err in unsupported {
+        assert!(!err.is_corruption());
+        assert!(err.is_unsupported());
+        assert!(!err.is_resource_limit());
+    }
+    for err in resource_limit {
+        assert!(!err.is_corruption());
+        assert!(!err.is_unsupported());
+        assert!(err.is_resource_limit());
+    }
+    for err in [XzError::NeedsReset, XzError::NeedsLargerInputBuffer] {
+        assert!(!err.is_corruption());
+        assert!(!err.is_unsupported());
+        assert!(!err.is_resource_limit());
+    }
+}
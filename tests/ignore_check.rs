@@ -0,0 +1,118 @@
+use std::io::Read;
+use xz4rust::{XzCheckResult, XzCheckType, XzDecoder, XzError, XzReader};
+
+fn decode(decoder: &mut XzDecoder, data: &[u8]) -> Result<Vec<u8>, XzError> {
+    let mut output = vec![0u8; 4096];
+    let result = decoder.decode(data, &mut output)?;
+    assert!(result.is_end_of_stream());
+    assert_eq!(result.input_consumed(), data.len());
+    output.truncate(result.output_produced());
+    Ok(output)
+}
+
+#[test]
+pub fn test_ignore_check() {
+    for (bad, good, check_type) in [
+        (
+            include_bytes!("../test_files/bad-1-check-crc32.xz").as_slice(),
+            include_bytes!("../test_files/good-1-check-crc32.xz").as_slice(),
+            XzCheckType::Crc32,
+        ),
+        (
+            include_bytes!("../test_files/bad-1-check-crc32-2.xz").as_slice(),
+            include_bytes!("../test_files/good-1-check-crc32.xz").as_slice(),
+            XzCheckType::Crc32,
+        ),
+        (
+            include_bytes!("../test_files/bad-1-check-crc64.xz").as_slice(),
+            include_bytes!("../test_files/good-1-check-crc64.xz").as_slice(),
+            XzCheckType::Crc64,
+        ),
+        (
+            include_bytes!("../test_files/bad-1-check-sha256.xz").as_slice(),
+            include_bytes!("../test_files/good-1-check-sha256.xz").as_slice(),
+            XzCheckType::Sha256,
+        ),
+    ] {
+        let mut decoder = XzDecoder::in_heap();
+        assert!(decode(&mut decoder, bad).is_err());
+        let expected = decode(&mut XzDecoder::in_heap(), good).unwrap();
+
+        decoder.reset();
+        decoder.set_ignore_check(true);
+        decoder.set_stop_at_block_end(true);
+        let mut output = vec![0u8; 4096];
+        let result = decoder.decode(bad, &mut output).unwrap();
+        let block_end = decoder.take_block_end().unwrap();
+        assert_eq!(block_end.check_type, check_type);
+        assert_eq!(block_end.check_result, XzCheckResult::Ignored);
+        assert_eq!(&output[..result.output_produced()], expected.as_slice());
+        assert!(decoder.stats().check_ignored);
+
+        let consumed = result.input_consumed();
+        let result = decoder.decode(&bad[consumed..], &mut output).unwrap();
+        assert!(result.is_end_of_stream());
+
+        // The option stays enabled after a reset.
+        decoder.reset();
+        decoder.set_stop_at_block_end(false);
+        assert_eq!(decode(&mut decoder, bad).unwrap(), expected);
+    }
+}
+
+#[test]
+pub fn test_ignore_check_none() {
+    let mut decoder = XzDecoder::in_heap();
+    decoder.set_ignore_check(true);
+    decoder.set_stop_at_block_end(true);
+    let data = include_bytes!("../test_files/good-1-check-none.xz");
+    let mut output = vec![0u8; 4096];
+    decoder.decode(data, &mut output).unwrap();
+    let block_end = decoder.take_block_end().unwrap();
+    assert_eq!(block_end.check_result, XzCheckResult::None);
+}
+
+#[test]
+pub fn test_ignore_check_still_verifies_headers() {
+    let mut decoder = XzDecoder::in_heap();
+    decoder.set_ignore_check(true);
+
+    let mut data = include_bytes!("../test_files/good-1-check-crc32.xz").to_vec();
+    data[14] ^= 1;
+    assert!(matches!(
+        decode(&mut decoder, &data).unwrap_err(),
+        XzError::BlockHeaderCrc32Mismatch(_, _)
+    ));
+
+    decoder.reset();
+    assert_eq!(
+        decode(
+            &mut decoder,
+            include_bytes!("../test_files/bad-2-index-4.xz")
+        )
+        .unwrap_err(),
+        XzError::IndexCrc32Mismatch(1575476230, 1558699014)
+    );
+}
+
+#[test]
+pub fn test_ignore_check_reader() {
+    let data = include_bytes!("../test_files/bad-1-check-sha256.xz");
+    let mut result = Vec::new();
+    assert!(XzReader::new(data.as_slice())
+        .read_to_end(&mut result)
+        .is_err());
+
+    let mut reader = XzReader::new(data.as_slice());
+    reader.set_ignore_check(true);
+    result.clear();
+    reader.read_to_end(&mut result).unwrap();
+    assert_eq!(
+        result,
+        decode(
+            &mut XzDecoder::in_heap(),
+            include_bytes!("../test_files/good-1-check-sha256.xz")
+        )
+        .unwrap()
+    );
+}
@@ -0,0 +1,112 @@
+use std::io::{Cursor, ErrorKind};
+use xz4rust::{XzCheckType, XzError, XzFileInfo, XzIndexRecord};
+
+const MULTI: &[u8] = include_bytes!("../test_files/multi_block_streams.xz");
+
+// Expected values are taken from "xz --robot -lvv multi_block_streams.xz"
+fn check_multi(info: &XzFileInfo) {
+    assert_eq!(info.streams.len(), 3);
+    assert_eq!(info.block_count(), 75);
+    assert_eq!(info.compressed_size(), 281_576);
+    assert_eq!(info.uncompressed_size(), 1_517_648);
+    assert_eq!(info.stream_padding(), 12);
+    assert_eq!(format!("{:.3}", info.ratio().unwrap()), "0.186");
+
+    let expected = [
+        (52, 0, 0, 158_008, 844_584, XzCheckType::Crc64, 8),
+        (0, 158_016, 844_584, 32, 0, XzCheckType::Crc32, 0),
+        (
+            23,
+            158_048,
+            844_584,
+            123_524,
+            673_064,
+            XzCheckType::Sha256,
+            4,
+        ),
+    ];
+    for (stream, expected) in info.streams.iter().zip(expected) {
+        assert_eq!(
+            (
+                stream.blocks.len(),
+                stream.compressed_offset,
+                stream.uncompressed_offset,
+                stream.compressed_size,
+                stream.uncompressed_size,
+                stream.check_type,
+                stream.stream_padding,
+            ),
+            expected
+        );
+    }
+
+    let blocks = &info.streams[0].blocks;
+    assert_eq!(
+        blocks[0],
+        XzIndexRecord {
+            compressed_offset: 12,
+            uncompressed_offset: 0,
+            unpadded_size: 16 + 4493 + 8,
+            uncompressed_size: 16384,
+        }
+    );
+    assert_eq!(blocks[0].total_size(), 4520);
+    assert_eq!(blocks[1].compressed_offset, 4532);
+    assert_eq!(blocks[51].uncompressed_size, 9000);
+
+    let blocks = &info.streams[2].blocks;
+    assert_eq!(blocks[0].compressed_offset, 158_060);
+    assert_eq!(blocks[0].uncompressed_offset, 844_584);
+    assert_eq!(blocks[0].total_size(), 5644);
+    assert_eq!(blocks[22].compressed_offset, 279_356);
+    assert_eq!(blocks[22].uncompressed_offset, 1_504_584);
+}
+
+#[test]
+pub fn test_info_from_slice() {
+    check_multi(&XzFileInfo::from_slice(MULTI).unwrap());
+
+    let info = XzFileInfo::from_slice(include_bytes!("../test_files/good-0-empty.xz")).unwrap();
+    assert_eq!(info.streams.len(), 1);
+    assert_eq!(info.block_count(), 0);
+    assert_eq!(info.ratio(), None);
+}
+
+#[test]
+pub fn test_info_from_reader() {
+    let mut cursor = Cursor::new(MULTI);
+    let info = XzFileInfo::from_reader(&mut cursor).unwrap();
+    check_multi(&info);
+    assert_eq!(info, XzFileInfo::from_slice(MULTI).unwrap());
+}
+
+#[test]
+pub fn test_info_invalid() {
+    assert_eq!(
+        XzFileInfo::from_slice(&[]).unwrap_err(),
+        XzError::UnexpectedEndOfInput
+    );
+    assert_eq!(
+        XzFileInfo::from_slice(&MULTI[1..]).unwrap_err(),
+        XzError::CorruptedDataInBlockIndex
+    );
+    let mut input = MULTI.to_vec();
+    input[0] = 0;
+    assert_eq!(
+        XzFileInfo::from_slice(&input).unwrap_err(),
+        XzError::StreamHeaderMagicNumberMismatch
+    );
+    assert_eq!(
+        XzFileInfo::from_slice(&MULTI[..MULTI.len() - 1]).unwrap_err(),
+        XzError::FooterMagicNumberMismatch
+    );
+    assert_eq!(
+        XzFileInfo::from_slice(&[0; 8]).unwrap_err(),
+        XzError::InvalidStreamPadding
+    );
+
+    let err = XzFileInfo::from_reader(&mut Cursor::new(&MULTI[1..])).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    let err = XzFileInfo::from_reader(&mut Cursor::new(&MULTI[MULTI.len() - 12..])).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+}
@@ -0,0 +1,148 @@
+mod common;
+
+use common::decode_all;
+use std::io::{Cursor, ErrorKind, Read};
+use xz4rust::{LzipDecoder, LzipReader, XzError};
+
+const AMD64: &[u8] = include_bytes!("../test_files/java_native_utils_amd64.so.lz");
+const RISCV64: &[u8] = include_bytes!("../test_files/java_native_utils_riscv64.so.lz");
+
+fn expected() -> Vec<u8> {
+    let mut expected = Vec::new();
+    expected.extend_from_slice(include_bytes!("../test_files/java_native_utils_amd64.so"));
+    expected.extend_from_slice(include_bytes!("../test_files/java_native_utils_riscv64.so"));
+    expected
+}
+
+#[test]
+pub fn test_lzip_single_member() {
+    let mut decoder = LzipDecoder::with_alloc_dict_size(1 << 20);
+    for chunk in [1, 7, 4096, 1 << 20] {
+        assert_eq!(
+            decode_all(&mut decoder, RISCV64, chunk).unwrap().as_slice(),
+            include_bytes!("../test_files/java_native_utils_riscv64.so")
+        );
+        assert_eq!(decoder.member_count(), 1);
+        decoder.reset();
+    }
+}
+
+#[test]
+pub fn test_lzip_multi_member() {
+    let input = [AMD64, RISCV64].concat();
+    let mut dict = vec![0u8; 1 << 20];
+    let mut decoder = LzipDecoder::with_fixed_size_dict(dict.as_mut_slice());
+    for chunk in [1, 21, 4096, 1 << 20] {
+        assert_eq!(decode_all(&mut decoder, &input, chunk).unwrap(), expected());
+        assert_eq!(decoder.member_count(), 2);
+        decoder.reset();
+    }
+}
+
+#[test]
+pub fn test_lzip_reader() {
+    let mut input = [AMD64, RISCV64].concat();
+    // Trailing data is ignored.
+    input.extend_from_slice(b"trailing garbage");
+    let mut reader = LzipReader::new(Cursor::new(input));
+    let mut out = Vec::new();
+    reader.read_to_end(&mut out).unwrap();
+    assert!(reader.is_eos());
+    assert_eq!(out, expected());
+
+    // Trailing data that looks like a member is not.
+    let mut input = RISCV64.to_vec();
+    input.extend_from_slice(b"LZIX");
+    let mut reader = LzipReader::new(Cursor::new(input));
+    let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+    let err: XzError = err.downcast().unwrap();
+    assert_eq!(err, XzError::StreamHeaderMagicNumberMismatch);
+}
+
+#[test]
+pub fn test_lzip_truncated() {
+    for len in [3, 6, 100, RISCV64.len() - 20, RISCV64.len() - 1] {
+        let mut reader = LzipReader::new(Cursor::new(RISCV64[..len].to_vec()));
+        let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::UnexpectedEof | ErrorKind::InvalidData
+        ));
+    }
+}
+
+#[test]
+pub fn test_lzip_trailer_mismatch() {
+    let trailer = RISCV64.len() - 20;
+    let mut decoder = LzipDecoder::with_alloc_dict_size(1 << 20);
+    let mut corrupt = |offset: usize| {
+        let mut input = RISCV64.to_vec();
+        input[trailer + offset] ^= 1;
+        decoder.reset();
+        decode_all(&mut decoder, &input, 4096).unwrap_err()
+    };
+
+    assert!(matches!(corrupt(0), XzError::LzipCrc32Mismatch(_, _)));
+    assert!(matches!(corrupt(4), XzError::LzipDataSizeMismatch(_, _)));
+    let size = RISCV64.len() as u64;
+    assert_eq!(corrupt(12), XzError::LzipMemberSizeMismatch(size, size ^ 1));
+}
+
+#[test]
+pub fn test_lzip_small_members() {
+    let input = include_bytes!("../test_files/small_members.lz");
+    let mut decoder = LzipDecoder::with_alloc_dict_size(1 << 12);
+    for chunk in [1, 2, 21, 4096] {
+        assert_eq!(
+            decode_all(&mut decoder, input, chunk).unwrap().as_slice(),
+            include_bytes!("../test_files/small_members")
+        );
+        assert_eq!(decoder.member_count(), 5);
+        decoder.reset();
+    }
+}
+
+#[test]
+pub fn test_lzip_bad_header() {
+    let mut decoder = LzipDecoder::with_alloc_dict_size(1 << 20);
+    let mut input = RISCV64.to_vec();
+    input[4] = 0;
+    assert_eq!(
+        decode_all(&mut decoder, &input, 4096).unwrap_err(),
+        XzError::LzipUnsupportedVersion(0)
+    );
+
+    let mut input = RISCV64.to_vec();
+    input[5] = 11;
+    decoder.reset();
+    assert_eq!(
+        decode_all(&mut decoder, &input, 4096).unwrap_err(),
+        XzError::LzipInvalidDictionarySize(11)
+    );
+
+    //4 KiB minus 1/16.
+    let mut input = RISCV64.to_vec();
+    input[5] = 0x2C;
+    decoder.reset();
+    assert_eq!(
+        decode_all(&mut decoder, &input, 4096).unwrap_err(),
+        XzError::CorruptedData
+    );
+
+    decoder.reset();
+    assert_eq!(
+        decode_all(
+            &mut decoder,
+            include_bytes!("../test_files/java_native_utils_riscv64.so.xz"),
+            4096
+        )
+        .unwrap_err(),
+        XzError::StreamHeaderMagicNumberMismatch
+    );
+
+    let mut decoder = LzipDecoder::with_alloc_dict_size(1 << 16);
+    assert_eq!(
+        decode_all(&mut decoder, RISCV64, 4096).unwrap_err(),
+        XzError::DictionaryTooLarge(1 << 20)
+    );
+}
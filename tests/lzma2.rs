@@ -0,0 +1,60 @@
+mod common;
+
+use common::{decode_all, EXPECTED};
+use xz4rust::{Lzma2Decoder, XzError};
+
+const INPUT: &[u8] = include_bytes!("../test_files/java_native_utils_riscv64.so.lzma2");
+
+#[test]
+pub fn test_lzma2_fixed_dict() {
+    let mut dict = vec![0u8; 1 << 20];
+    let mut decoder = Lzma2Decoder::with_fixed_size_dict(1 << 20, dict.as_mut_slice());
+    assert_eq!(
+        decode_all(&mut decoder, INPUT, 4096).unwrap().as_slice(),
+        EXPECTED
+    );
+}
+
+#[test]
+pub fn test_lzma2_alloc_dict() {
+    let dict_size = Lzma2Decoder::dict_size_from_props(16).unwrap();
+    assert_eq!(dict_size, 1 << 20);
+    let mut decoder = Lzma2Decoder::with_alloc_dict_size(dict_size, 1 << 20);
+    for chunk in [1, 7, 65536] {
+        assert_eq!(
+            decode_all(&mut decoder, INPUT, chunk).unwrap().as_slice(),
+            EXPECTED
+        );
+        assert_eq!(
+            decoder.decode(INPUT, &mut [0u8; 16]).unwrap_err(),
+            XzError::NeedsReset
+        );
+        decoder.reset();
+    }
+}
+
+#[test]
+pub fn test_lzma2_dict_too_small() {
+    let mut dict = vec![0u8; 1 << 16];
+    let mut decoder = Lzma2Decoder::with_fixed_size_dict(1 << 20, dict.as_mut_slice());
+    assert_eq!(
+        decode_all(&mut decoder, INPUT, 4096).unwrap_err(),
+        XzError::DictionaryTooLarge(1 << 20)
+    );
+    assert_eq!(
+        decoder.decode(INPUT, &mut [0u8; 16]).unwrap_err(),
+        XzError::NeedsReset
+    );
+
+    decoder.reset_with_dict_size(1 << 16);
+    assert!(decode_all(&mut decoder, INPUT, 4096).is_err());
+}
+
+#[test]
+pub fn test_lzma2_invalid_props() {
+    assert_eq!(Lzma2Decoder::dict_size_from_props(0).unwrap(), 4096);
+    assert_eq!(
+        Lzma2Decoder::dict_size_from_props(40),
+        Err(XzError::UnsupportedLzmaProperties(40))
+    );
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use common::decode_all;
+use std::io::{Cursor, ErrorKind, Read};
+use xz4rust::{LzmaAloneDecoder, LzmaAloneReader, XzError};
+
+#[test]
+pub fn test_lzma_alone_known_size() {
+    let input = include_bytes!("../test_files/java_native_utils_amd64.so.lzma");
+    let expected = include_bytes!("../test_files/java_native_utils_amd64.so");
+    let mut decoder = LzmaAloneDecoder::with_alloc_dict_size(1 << 20);
+    for chunk in [1, 13, 4096, 1 << 20] {
+        assert_eq!(
+            decode_all(&mut decoder, input, chunk).unwrap().as_slice(),
+            expected
+        );
+        assert_eq!(decoder.uncompressed_size(), Some(expected.len() as u64));
+        decoder.reset();
+    }
+}
+
+#[test]
+pub fn test_lzma_alone_end_marker() {
+    let input = include_bytes!("../test_files/java_native_utils_riscv64.so.lzma");
+    let expected = include_bytes!("../test_files/java_native_utils_riscv64.so");
+
+    let mut dict = vec![0u8; 1 << 23];
+    let mut decoder = LzmaAloneDecoder::with_fixed_size_dict(dict.as_mut_slice());
+    for chunk in [1, 20, 4096, 1 << 20] {
+        assert_eq!(
+            decode_all(&mut decoder, input, chunk).unwrap().as_slice(),
+            expected
+        );
+        assert_eq!(decoder.uncompressed_size(), None);
+        decoder.reset();
+    }
+
+    // Known size followed by an end marker.
+    let mut input = input.to_vec();
+    input[5..13].copy_from_slice(&(expected.len() as u64).to_le_bytes());
+    assert_eq!(
+        decode_all(&mut decoder, &input, 4096).unwrap().as_slice(),
+        expected
+    );
+
+    // Known size that does not match the end marker.
+    input[5..13].copy_from_slice(&(expected.len() as u64 + 1).to_le_bytes());
+    decoder.reset();
+    assert_eq!(
+        decode_all(&mut decoder, &input, 4096).unwrap_err(),
+        XzError::CorruptedDataInLzma
+    );
+}
+
+#[test]
+pub fn test_lzma_alone_reader() {
+    let mut reader = LzmaAloneReader::new(Cursor::new(
+        include_bytes!("../test_files/java_native_utils_riscv64.so.lzma").to_vec(),
+    ));
+    let mut out = Vec::new();
+    reader.read_to_end(&mut out).unwrap();
+    assert!(reader.is_eos());
+    assert_eq!(
+        out.as_slice(),
+        include_bytes!("../test_files/java_native_utils_riscv64.so")
+    );
+
+    let input = include_bytes!("../test_files/java_native_utils_amd64.so.lzma");
+    let mut reader = LzmaAloneReader::new(input.as_slice());
+    let mut out = Vec::new();
+    reader.read_to_end(&mut out).unwrap();
+    assert_eq!(
+        out.as_slice(),
+        include_bytes!("../test_files/java_native_utils_amd64.so")
+    );
+}
+
+#[test]
+pub fn test_lzma_alone_truncated() {
+    for input in [
+        include_bytes!("../test_files/java_native_utils_amd64.so.lzma").as_slice(),
+        include_bytes!("../test_files/java_native_utils_riscv64.so.lzma").as_slice(),
+    ] {
+        for len in [5, 13, 100, input.len() - 1] {
+            let mut reader = LzmaAloneReader::new(Cursor::new(input[..len].to_vec()));
+            let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+            assert!(matches!(
+                err.kind(),
+                ErrorKind::UnexpectedEof | ErrorKind::InvalidData
+            ));
+        }
+    }
+}
+
+#[test]
+pub fn test_lzma_alone_dict_too_small() {
+    let mut dict = vec![0u8; 1 << 16];
+    let mut decoder = LzmaAloneDecoder::with_fixed_size_dict(dict.as_mut_slice());
+    assert_eq!(
+        decode_all(
+            &mut decoder,
+            include_bytes!("../test_files/java_native_utils_riscv64.so.lzma"),
+            4096
+        )
+        .unwrap_err(),
+        XzError::DictionaryTooLarge(1 << 23)
+    );
+}
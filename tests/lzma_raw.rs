@@ -0,0 +1,101 @@
+mod common;
+
+use common::decode_all;
+use xz4rust::{LzmaDecoder, LzmaProperties, XzError};
+
+const ARMEL_PROPERTIES: LzmaProperties = LzmaProperties {
+    lc: 0,
+    lp: 2,
+    pb: 0,
+    dict_size: 1 << 16,
+    uncompressed_size: None,
+    end_marker: true,
+};
+
+#[test]
+pub fn test_lzma_raw_end_marker() {
+    let input = include_bytes!("../test_files/java_native_utils_armel.so.lzma1");
+    let expected = include_bytes!("../test_files/java_native_utils_armel.so");
+
+    let mut dict = vec![0u8; 1 << 16];
+    let mut decoder = LzmaDecoder::with_fixed_size_dict(ARMEL_PROPERTIES, dict.as_mut_slice());
+    for chunk in [1, 21, 4096, 1 << 20] {
+        assert_eq!(
+            decode_all(&mut decoder, input, chunk).unwrap().as_slice(),
+            expected
+        );
+        decoder.reset();
+    }
+
+    // The end marker is allowed after the known size.
+    let mut properties = ARMEL_PROPERTIES;
+    properties.uncompressed_size = Some(expected.len() as u64);
+    decoder.reset_with_properties(properties);
+    assert_eq!(
+        decode_all(&mut decoder, input, 4096).unwrap().as_slice(),
+        expected
+    );
+
+    // Unless it is forbidden.
+    properties.end_marker = false;
+    decoder.reset_with_properties(properties);
+    assert_eq!(
+        decode_all(&mut decoder, input, 4096).unwrap_err(),
+        XzError::CorruptedDataInLzma
+    );
+}
+
+#[test]
+pub fn test_lzma_raw_known_size() {
+    let input = &include_bytes!("../test_files/java_native_utils_amd64.so.lzma")[13..];
+    let expected = include_bytes!("../test_files/java_native_utils_amd64.so");
+    let mut properties = LzmaProperties::from_props_byte(0x5D, 1 << 20).unwrap();
+    assert_eq!((properties.lc, properties.lp, properties.pb), (3, 0, 2));
+    assert_eq!(properties.props_byte(), Ok(0x5D));
+    properties.uncompressed_size = Some(expected.len() as u64);
+    properties.end_marker = false;
+
+    let mut decoder = LzmaDecoder::with_alloc_dict_size(properties, 1 << 20);
+    for chunk in [1, 4096, 1 << 20] {
+        assert_eq!(
+            decode_all(&mut decoder, input, chunk).unwrap().as_slice(),
+            expected
+        );
+        decoder.reset();
+    }
+
+    // A known size that is too small leaves the range decoder unfinished.
+    properties.uncompressed_size = Some(1000);
+    decoder.reset_with_properties(properties);
+    assert_eq!(
+        decode_all(&mut decoder, input, 4096).unwrap_err(),
+        XzError::CorruptedDataInLzma
+    );
+}
+
+#[test]
+pub fn test_lzma_raw_invalid_properties() {
+    assert_eq!(
+        LzmaProperties::from_props_byte(225, 4096).unwrap_err(),
+        XzError::LzmaPropertiesTooLarge
+    );
+
+    let mut properties = ARMEL_PROPERTIES;
+    properties.lc = 3;
+    let mut decoder = LzmaDecoder::with_alloc_dict_size(properties, 1 << 16);
+    assert_eq!(
+        decoder.decode(&[0; 32], &mut [0; 32]).unwrap_err(),
+        XzError::LzmaPropertiesInvalid
+    );
+    assert_eq!(
+        decoder.decode(&[0; 32], &mut [0; 32]).unwrap_err(),
+        XzError::NeedsReset
+    );
+
+    properties.lc = 9;
+    decoder.reset_with_properties(properties);
+    assert_eq!(
+        decoder.decode(&[0; 32], &mut [0; 32]).unwrap_err(),
+        XzError::LzmaPropertiesTooLarge
+    );
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use common::{COMPRESSED, EXPECTED};
+use std::io::Read;
+use std::mem::size_of;
+use xz4rust::{XzDecoder, XzError, XzNextBlockResult, XzReader};
+
+fn decoder_size() -> u64 {
+    size_of::<XzDecoder>() as u64
+}
+
+#[test]
+pub fn test_memory_usage() {
+    for (data, dict_size) in [
+        (COMPRESSED, 8 << 20),
+        (
+            include_bytes!("../test_files/good-1-3delta-lzma2.xz").as_slice(),
+            64 << 10,
+        ),
+        (
+            include_bytes!("../test_files/good-1-delta-lzma2.tiff.xz").as_slice(),
+            1 << 20,
+        ),
+    ] {
+        assert_eq!(
+            XzDecoder::memory_usage(&data[..12], &data[12..]).unwrap(),
+            decoder_size() + dict_size
+        );
+    }
+}
+
+#[test]
+pub fn test_memory_usage_errors() {
+    let block_header_size = (usize::from(COMPRESSED[12]) + 1) * 4;
+    let block_header = &COMPRESSED[12..12 + block_header_size];
+
+    assert_eq!(
+        XzDecoder::memory_usage(&COMPRESSED[..11], block_header).unwrap_err(),
+        XzError::UnexpectedEndOfInput
+    );
+    assert_eq!(
+        XzDecoder::memory_usage(&COMPRESSED[..12], &block_header[..block_header_size - 1])
+            .unwrap_err(),
+        XzError::UnexpectedEndOfInput
+    );
+    assert_eq!(
+        XzDecoder::memory_usage(&COMPRESSED[1..13], block_header).unwrap_err(),
+        XzError::StreamHeaderMagicNumberMismatch
+    );
+
+    let mut corrupted = block_header.to_vec();
+    corrupted[3] ^= 1;
+    assert!(matches!(
+        XzDecoder::memory_usage(&COMPRESSED[..12], &corrupted).unwrap_err(),
+        XzError::BlockHeaderCrc32Mismatch(_, _)
+    ));
+}
+
+#[test]
+pub fn test_memlimit_resume() {
+    let required = XzDecoder::memory_usage(&COMPRESSED[..12], &COMPRESSED[12..]).unwrap();
+    let mut decoder = XzDecoder::in_heap_with_alloc_dict_size(4096, 1 << 30);
+    assert_eq!(decoder.memlimit(), u64::MAX);
+    decoder.set_memlimit(required - 1);
+    assert_eq!(decoder.memlimit(), required - 1);
+
+    // The progress up to the block header is reported before the error.
+    let mut output = vec![0u8; EXPECTED.len()];
+    let result = decoder.decode(COMPRESSED, &mut output).unwrap();
+    assert!(matches!(result, XzNextBlockResult::NeedMoreData(_, 0)));
+    let mut consumed = result.input_consumed();
+    assert!(consumed > 12);
+
+    // The error does not consume input and can be repeated.
+    for _ in 0..2 {
+        assert_eq!(
+            decoder
+                .decode(&COMPRESSED[consumed..], &mut output)
+                .unwrap_err(),
+            XzError::MemoryLimitExceeded(required)
+        );
+    }
+
+    decoder.set_memlimit(required);
+    let mut produced = 0;
+    loop {
+        let result = decoder
+            .decode(&COMPRESSED[consumed..], &mut output[produced..])
+            .unwrap();
+        consumed += result.input_consumed();
+        produced += result.output_produced();
+        if result.is_end_of_stream() {
+            break;
+        }
+    }
+    assert_eq!(consumed, COMPRESSED.len());
+    assert_eq!(output.as_slice(), EXPECTED);
+}
+
+#[test]
+pub fn test_memlimit_reader() {
+    let required = XzDecoder::memory_usage(&COMPRESSED[..12], &COMPRESSED[12..]).unwrap();
+    let mut reader = XzReader::new(COMPRESSED);
+    reader.set_memlimit(required - 1);
+    let mut result = Vec::new();
+    let err = reader.read_to_end(&mut result).unwrap_err();
+    assert_eq!(
+        err.downcast::<XzError>().unwrap(),
+        XzError::MemoryLimitExceeded(required)
+    );
+    assert!(result.is_empty());
+
+    reader.set_memlimit(0);
+    assert!(reader.read_to_end(&mut result).is_err());
+
+    reader.set_memlimit(u64::MAX);
+    reader.read_to_end(&mut result).unwrap();
+    assert_eq!(result.as_slice(), EXPECTED);
+}
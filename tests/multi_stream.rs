@@ -0,0 +1,154 @@
+use std::io::{Cursor, ErrorKind, Read};
+use xz4rust::{XzDecoder, XzError, XzReader};
+
+fn concat(padding: usize) -> (Vec<u8>, Vec<u8>) {
+    let mut input = Vec::new();
+    input.extend_from_slice(include_bytes!(
+        "../test_files/java_native_utils_amd64.so.xz"
+    ));
+    input.resize(input.len() + padding, 0);
+    input.extend_from_slice(include_bytes!(
+        "../test_files/java_native_utils_riscv64.so.xz"
+    ));
+
+    let mut expected = Vec::new();
+    expected.extend_from_slice(include_bytes!("../test_files/java_native_utils_amd64.so"));
+    expected.extend_from_slice(include_bytes!("../test_files/java_native_utils_riscv64.so"));
+    (input, expected)
+}
+
+#[test]
+pub fn test_multi_stream_reader() {
+    for padding in [0, 4, 8, 1024] {
+        let (input, expected) = concat(padding);
+        let mut reader = XzReader::new(Cursor::new(input));
+        reader.set_multi_stream(true);
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).unwrap();
+        assert_eq!(out.as_slice(), expected.as_slice());
+        assert!(reader.is_eos());
+    }
+}
+
+#[test]
+pub fn test_single_stream_reader_stops_after_first_stream() {
+    let (input, _) = concat(4);
+    let mut reader = XzReader::new(Cursor::new(input));
+    let mut out = Vec::new();
+    reader.read_to_end(&mut out).unwrap();
+    assert_eq!(
+        out.as_slice(),
+        include_bytes!("../test_files/java_native_utils_amd64.so")
+    );
+}
+
+#[test]
+pub fn test_multi_stream_decoder_reports_boundaries() {
+    let (input, expected) = concat(12);
+    let mut decoder = XzDecoder::default();
+    decoder.set_multi_stream(true);
+
+    let mut out = Vec::new();
+    let mut buf = [0u8; 4096];
+    let mut boundaries = 0;
+    let mut data = input.as_slice();
+    while !data.is_empty() {
+        let result = decoder
+            .decode(&data[..data.len().min(333)], &mut buf)
+            .unwrap();
+        data = &data[result.input_consumed()..];
+        out.extend_from_slice(&buf[..result.output_produced()]);
+        if result.is_end_of_stream() {
+            boundaries += 1;
+        }
+    }
+
+    decoder.finish().unwrap();
+    assert_eq!(boundaries, 2);
+    assert_eq!(out.as_slice(), expected.as_slice());
+}
+
+#[test]
+pub fn test_multi_stream_bad_padding() {
+    for padding in [1, 2, 3, 5] {
+        let (input, _) = concat(padding);
+        let mut reader = XzReader::new(Cursor::new(input));
+        reader.set_multi_stream(true);
+        let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        let err: XzError = err.downcast().unwrap();
+        assert_eq!(err, XzError::InvalidStreamPadding);
+    }
+
+    // Trailing padding must also be a multiple of 4.
+    let mut input = include_bytes!("../test_files/good-1-check-crc32.xz").to_vec();
+    input.push(0);
+    let mut reader = XzReader::new(Cursor::new(input));
+    reader.set_multi_stream(true);
+    let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+    let err: XzError = err.downcast().unwrap();
+    assert_eq!(err, XzError::InvalidStreamPadding);
+}
+
+#[test]
+pub fn test_multi_stream_truncated() {
+    let (input, _) = concat(4);
+    let mut reader = XzReader::new(Cursor::new(input[..input.len() - 1].to_vec()));
+    reader.set_multi_stream(true);
+    let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+
+    let mut decoder = XzDecoder::default();
+    decoder.set_multi_stream(true);
+    let mut buf = [0u8; 4096];
+    decoder.decode(&input[..100], &mut buf).unwrap();
+    assert_eq!(decoder.finish(), Err(XzError::UnexpectedEndOfInput));
+}
+
+#[test]
+pub fn test_multi_stream_xz_test_files() {
+    for (input, expected) in [
+        (
+            include_bytes!("../test_files/good-0cat-empty.xz").as_slice(),
+            include_bytes!("../test_files/good-0cat-empty").as_slice(),
+        ),
+        (
+            include_bytes!("../test_files/good-0catpad-empty.xz").as_slice(),
+            include_bytes!("../test_files/good-0catpad-empty").as_slice(),
+        ),
+        (
+            include_bytes!("../test_files/good-0pad-empty.xz").as_slice(),
+            include_bytes!("../test_files/good-0pad-empty").as_slice(),
+        ),
+    ] {
+        let mut reader = XzReader::new(input);
+        reader.set_multi_stream(true);
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).unwrap();
+        assert_eq!(out.as_slice(), expected);
+    }
+
+    for (input, expected) in [
+        (
+            include_bytes!("../test_files/bad-0catpad-empty.xz").as_slice(),
+            XzError::InvalidStreamPadding,
+        ),
+        (
+            include_bytes!("../test_files/bad-0pad-empty.xz").as_slice(),
+            XzError::InvalidStreamPadding,
+        ),
+        (
+            include_bytes!("../test_files/bad-0cat-header_magic.xz").as_slice(),
+            XzError::StreamHeaderMagicNumberMismatch,
+        ),
+        (
+            include_bytes!("../test_files/bad-0cat-alone.xz").as_slice(),
+            XzError::StreamHeaderMagicNumberMismatch,
+        ),
+    ] {
+        let mut reader = XzReader::new(input);
+        reader.set_multi_stream(true);
+        let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        let err: XzError = err.downcast().unwrap();
+        assert_eq!(err, expected);
+    }
+}
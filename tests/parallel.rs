@@ -0,0 +1,142 @@
+use std::io::{Cursor, ErrorKind, Read};
+use std::num::NonZeroUsize;
+use xz4rust::{ParallelXzReader, XzError, XzFileInfo};
+
+const MULTI: &[u8] = include_bytes!("../test_files/multi_block_streams.xz");
+
+fn expected() -> Vec<u8> {
+    let mut expected = Vec::new();
+    expected.extend_from_slice(include_bytes!("../test_files/java_native_utils_amd64.so"));
+    expected.extend_from_slice(include_bytes!("../test_files/java_native_utils_riscv64.so"));
+    expected
+}
+
+#[test]
+pub fn test_parallel_read_all() {
+    let expected = expected();
+    for threads in [1, 4, 16] {
+        for memory_limit in [0, 100_000, 1 << 30] {
+            let mut reader = ParallelXzReader::new_with_threads_and_memory_limit(
+                Cursor::new(MULTI),
+                NonZeroUsize::new(threads).unwrap(),
+                memory_limit,
+            )
+            .unwrap();
+            assert_eq!(reader.info().block_count(), 75);
+            let mut out = Vec::new();
+            reader.read_to_end(&mut out).unwrap();
+            assert_eq!(out, expected);
+            assert_eq!(reader.read(&mut [0; 16]).unwrap(), 0);
+        }
+    }
+}
+
+#[test]
+pub fn test_parallel_small_reads() {
+    let expected = expected();
+    let mut reader = ParallelXzReader::new(Cursor::new(MULTI)).unwrap();
+    let mut out = Vec::new();
+    let mut buf = [0u8; 1000];
+    loop {
+        let count = reader.read(&mut buf).unwrap();
+        if count == 0 {
+            break;
+        }
+        out.extend_from_slice(&buf[..count]);
+    }
+    assert_eq!(out, expected);
+}
+
+#[test]
+pub fn test_parallel_single_block() {
+    let mut reader = ParallelXzReader::new(Cursor::new(include_bytes!(
+        "../test_files/java_native_utils_amd64.so.xz"
+    )))
+    .unwrap();
+    let mut out = Vec::new();
+    reader.read_to_end(&mut out).unwrap();
+    assert_eq!(
+        out.as_slice(),
+        include_bytes!("../test_files/java_native_utils_amd64.so")
+    );
+
+    let mut reader = ParallelXzReader::new(Cursor::new(include_bytes!(
+        "../test_files/good-0cat-empty.xz"
+    )))
+    .unwrap();
+    assert_eq!(reader.read(&mut [0; 16]).unwrap(), 0);
+}
+
+#[test]
+pub fn test_parallel_corrupted_block_in_order() {
+    let expected = expected();
+    let info = XzFileInfo::from_slice(MULTI).unwrap();
+    for index in [5, 40] {
+        let mut input = MULTI.to_vec();
+        let block = info.streams[0].blocks[index];
+        input[block.compressed_offset as usize + 100] ^= 0x55;
+        let mut reader = ParallelXzReader::new_with_threads_and_memory_limit(
+            Cursor::new(input),
+            NonZeroUsize::new(16).unwrap(),
+            1 << 30,
+        )
+        .unwrap();
+
+        // The failed block finishes before the blocks in front of it, their output is still returned first.
+        let mut out = Vec::new();
+        let err = reader.read_to_end(&mut out).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(
+            out.as_slice(),
+            &expected[..block.uncompressed_offset as usize]
+        );
+    }
+}
+
+#[test]
+pub fn test_parallel_corrupted_block() {
+    let expected = expected();
+    let mut input = MULTI.to_vec();
+    let block = XzFileInfo::from_slice(MULTI).unwrap().streams[0].blocks[29];
+    input[block.compressed_offset as usize + 100] ^= 0x55;
+    let mut reader = ParallelXzReader::new(Cursor::new(input)).unwrap();
+
+    let mut out = vec![0u8; 16384];
+    reader.read_exact(&mut out).unwrap();
+    assert_eq!(out.as_slice(), &expected[..16384]);
+
+    let mut rest = Vec::new();
+    let err = reader.read_to_end(&mut rest).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert_eq!(
+        rest.as_slice(),
+        &expected[16384..block.uncompressed_offset as usize]
+    );
+    let err = reader.read(&mut out).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert!(err.into_inner().unwrap().downcast::<XzError>().is_ok());
+}
+
+#[test]
+pub fn test_parallel_streamed_corrupted_block() {
+    let expected = expected();
+    let mut input = MULTI.to_vec();
+    let block = XzFileInfo::from_slice(MULTI).unwrap().streams[0].blocks[5];
+    input[block.compressed_offset as usize + 100] ^= 0x55;
+
+    // No block fits into the memory limit, so every block is decoded directly from the input.
+    let mut reader = ParallelXzReader::new_with_threads_and_memory_limit(
+        Cursor::new(input),
+        NonZeroUsize::new(4).unwrap(),
+        0,
+    )
+    .unwrap();
+    let mut out = Vec::new();
+    let err = reader.read_to_end(&mut out).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    let offset = block.uncompressed_offset as usize;
+    assert!(out.len() >= offset);
+    assert_eq!(&out[..offset], &expected[..offset]);
+    let err = reader.read(&mut [0; 16]).unwrap_err();
+    assert!(err.into_inner().unwrap().downcast::<XzError>().is_ok());
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use common::COMPRESSED;
+use xz4rust::{probe, XzBlockInfo, XzCheckType, XzDecoder, XzError, XzProbe};
+
+#[test]
+pub fn test_probe() {
+    let expected = XzProbe::Block(
+        XzCheckType::Crc64,
+        XzBlockInfo::parse(&COMPRESSED[12..]).unwrap(),
+    );
+    assert_eq!(probe(COMPRESSED).unwrap(), expected);
+    assert_eq!(expected.check_type(), Some(XzCheckType::Crc64));
+    assert_eq!(expected.block_info().unwrap().dict_size(), 8 << 20);
+    assert_eq!(
+        expected.memory_usage(),
+        Some(XzDecoder::memory_usage(&COMPRESSED[..12], &COMPRESSED[12..]).unwrap())
+    );
+
+    // Feeding the data byte by byte needs exactly the requested amount of bytes.
+    let mut len = 0;
+    let mut requests = Vec::new();
+    loop {
+        match probe(&COMPRESSED[..len]).unwrap() {
+            XzProbe::NeedMoreData(needed) => {
+                assert!(needed > len);
+                requests.push(needed);
+                len = needed;
+            }
+            result => {
+                assert_eq!(result, expected);
+                break;
+            }
+        }
+    }
+    assert_eq!(requests, [12, 13, 32]);
+    for len in 0..32 {
+        assert!(matches!(
+            probe(&COMPRESSED[..len]).unwrap(),
+            XzProbe::NeedMoreData(_)
+        ));
+    }
+}
+
+#[test]
+pub fn test_probe_empty() {
+    let result = probe(include_bytes!("../test_files/good-0-empty.xz")).unwrap();
+    assert_eq!(result, XzProbe::Empty(XzCheckType::Crc32));
+    assert!(result.block_info().is_none());
+    assert!(result.memory_usage().is_none());
+    assert!(XzProbe::NeedMoreData(12).check_type().is_none());
+}
+
+#[test]
+pub fn test_probe_errors() {
+    for data in [b"\xFD7zXY".as_slice(), b"hello", b"BZh91AY&SY", b"\x1f\x8b"] {
+        assert_eq!(
+            probe(data).unwrap_err(),
+            XzError::StreamHeaderMagicNumberMismatch
+        );
+    }
+
+    assert!(matches!(
+        probe(include_bytes!("../test_files/unsupported-check.xz")).unwrap_err(),
+        XzError::UnsupportedCheckType(_)
+    ));
+    assert_eq!(
+        probe(include_bytes!(
+            "../test_files/unsupported-filter_flags-1.xz"
+        ))
+        .unwrap_err(),
+        XzError::UnsupportedBlockHeaderOption
+    );
+
+    let mut corrupted = COMPRESSED[..32].to_vec();
+    corrupted[8] ^= 1;
+    assert!(matches!(
+        probe(&corrupted).unwrap_err(),
+        XzError::StreamHeaderCrc32Mismatch(_, _)
+    ));
+}
@@ -0,0 +1,207 @@
+use std::io::{Cursor, Read};
+use xz4rust::{XzDamagedBlock, XzDecoder, XzError, XzFileInfo, XzIndexRecord, XzReader};
+
+const MULTI: &[u8] = include_bytes!("../test_files/multi_block_streams.xz");
+const NO_SIZES: &[u8] = include_bytes!("../test_files/multi_block_no_sizes.xz");
+const NO_SIZES_EXPECTED: &[u8] = include_bytes!("../test_files/java_native_utils_riscv64.so");
+
+fn decode_recovering(data: &[u8]) -> (Vec<u8>, Vec<XzDamagedBlock>) {
+    decode_recovering_chunked(data, data.len())
+}
+
+/// Same as `decode_recovering` but passes at most `chunk` bytes of input to each call.
+fn decode_recovering_chunked(data: &[u8], chunk: usize) -> (Vec<u8>, Vec<XzDamagedBlock>) {
+    let mut decoder = XzDecoder::in_heap();
+    decoder.set_multi_stream(true);
+    decoder.set_recovery(true);
+    let mut output = vec![0u8; 4096];
+    let mut result = Vec::new();
+    let mut damaged = Vec::new();
+    let mut consumed = 0;
+    while consumed < data.len() {
+        let end = data.len().min(consumed + chunk);
+        let next = decoder.decode(&data[consumed..end], &mut output).unwrap();
+        consumed += next.input_consumed();
+        result.extend_from_slice(&output[..next.output_produced()]);
+        damaged.extend(decoder.take_damaged_block());
+    }
+    decoder.finish().unwrap();
+    assert_eq!(decoder.stats().damaged_blocks, damaged.len() as u64);
+    (result, damaged)
+}
+
+fn decode(data: &[u8]) -> Vec<u8> {
+    let mut reader = XzReader::new(Cursor::new(data.to_vec()));
+    reader.set_multi_stream(true);
+    let mut result = Vec::new();
+    reader.read_to_end(&mut result).unwrap();
+    result
+}
+
+/// Checks that everything but the damaged block was decoded correctly.
+fn assert_output(actual: &[u8], expected: &[u8], damaged: &XzDamagedBlock, block: &XzIndexRecord) {
+    let start = block.uncompressed_offset as usize;
+    let produced = damaged.uncompressed_produced as usize;
+    assert_eq!(damaged.uncompressed_offset, block.uncompressed_offset);
+    assert_eq!(
+        actual.len(),
+        expected.len() - block.uncompressed_size as usize + produced
+    );
+    assert_eq!(&actual[..start], &expected[..start]);
+    assert_eq!(
+        &actual[start + produced..],
+        &expected[start + block.uncompressed_size as usize..]
+    );
+}
+
+#[test]
+pub fn test_recovery_with_compressed_size() {
+    let info = XzFileInfo::from_slice(MULTI).unwrap();
+    let block = &info.streams[0].blocks[3];
+    let mut data = MULTI.to_vec();
+    data[block.compressed_offset as usize + 100] ^= 0x55;
+
+    let (output, damaged) = decode_recovering(&data);
+    assert_eq!(damaged.len(), 1);
+    assert_eq!(damaged[0].stream_index, 0);
+    assert_eq!(damaged[0].block_index, 3);
+    assert_eq!(damaged[0].compressed_offset, block.compressed_offset);
+    assert!(damaged[0].error.is_corruption());
+    assert_output(&output, &decode(MULTI), &damaged[0], block);
+}
+
+#[test]
+pub fn test_recovery_by_search() {
+    let info = XzFileInfo::from_slice(NO_SIZES).unwrap();
+    let blocks = &info.streams[0].blocks;
+    for (index, offset) in [(3, 100), (0, 5000), (10, 100)] {
+        let block = &blocks[index];
+        let mut data = NO_SIZES.to_vec();
+        data[block.compressed_offset as usize + offset] ^= 0x55;
+
+        let (output, damaged) = decode_recovering(&data);
+        assert_eq!(damaged.len(), 1);
+        assert_eq!(damaged[0].block_index, index as u64);
+        assert_eq!(damaged[0].compressed_offset, block.compressed_offset);
+        assert_output(&output, NO_SIZES_EXPECTED, &damaged[0], block);
+    }
+}
+
+#[test]
+pub fn test_recovery_chunked_input() {
+    let info = XzFileInfo::from_slice(NO_SIZES).unwrap();
+    let block = &info.streams[0].blocks[3];
+    let mut data = NO_SIZES.to_vec();
+    data[block.compressed_offset as usize + 100] ^= 0x55;
+
+    // The header of the next block is found across several calls.
+    for chunk in [1, 3, 7, 4096] {
+        let (output, damaged) = decode_recovering_chunked(&data, chunk);
+        assert_eq!(damaged.len(), 1);
+        assert_eq!(damaged[0].block_index, 3);
+        assert_eq!(damaged[0].compressed_offset, block.compressed_offset);
+        assert_output(&output, NO_SIZES_EXPECTED, &damaged[0], block);
+    }
+}
+
+#[test]
+pub fn test_recovery_damaged_block_header() {
+    let info = XzFileInfo::from_slice(NO_SIZES).unwrap();
+    let block = &info.streams[0].blocks[2];
+    let mut data = NO_SIZES.to_vec();
+    data[block.compressed_offset as usize + 2] ^= 0x55;
+
+    let (output, damaged) = decode_recovering(&data);
+    assert_eq!(damaged.len(), 1);
+    assert_eq!(damaged[0].block_index, 2);
+    assert_eq!(damaged[0].uncompressed_produced, 0);
+    assert!(matches!(
+        damaged[0].error,
+        XzError::BlockHeaderCrc32Mismatch(_, _)
+    ));
+    assert_output(&output, NO_SIZES_EXPECTED, &damaged[0], block);
+}
+
+#[test]
+pub fn test_recovery_check_mismatch() {
+    let (output, damaged) = decode_recovering(include_bytes!("../test_files/bad-1-check-crc32.xz"));
+    assert_eq!(damaged.len(), 1);
+    assert_eq!(damaged[0].uncompressed_produced, 13);
+    assert_eq!(output.len(), 13);
+    assert_eq!(
+        damaged[0].error,
+        XzError::ContentCrc32Mismatch(362980163, 346202947)
+    );
+}
+
+#[test]
+pub fn test_recovery_multiple_blocks() {
+    let info = XzFileInfo::from_slice(MULTI).unwrap();
+    let mut data = MULTI.to_vec();
+    let damaged_blocks = [
+        (0, &info.streams[0].blocks[10]),
+        (0, &info.streams[0].blocks[11]),
+        (2, &info.streams[2].blocks[0]),
+    ];
+    for (_, block) in damaged_blocks {
+        data[block.compressed_offset as usize + 200] ^= 0x55;
+    }
+
+    let (output, damaged) = decode_recovering(&data);
+    let actual: Vec<_> = damaged
+        .iter()
+        .map(|damaged| (damaged.stream_index, damaged.compressed_offset))
+        .collect();
+    let expected: Vec<_> = damaged_blocks
+        .iter()
+        .map(|(stream_index, block)| (*stream_index, block.compressed_offset))
+        .collect();
+    assert_eq!(actual, expected);
+    assert!(output.len() < decode(MULTI).len());
+}
+
+#[test]
+pub fn test_recovery_reader() {
+    let info = XzFileInfo::from_slice(NO_SIZES).unwrap();
+    let mut data = NO_SIZES.to_vec();
+    data[info.streams[0].blocks[5].compressed_offset as usize + 100] ^= 0x55;
+
+    let mut reader = XzReader::new(Cursor::new(data.clone()));
+    let mut result = Vec::new();
+    assert!(reader.read_to_end(&mut result).is_err());
+
+    let mut reader = XzReader::new(Cursor::new(data));
+    reader.set_recovery(true);
+    result.clear();
+    reader.read_to_end(&mut result).unwrap();
+    assert_eq!(reader.stats().damaged_blocks, 1);
+    assert!(reader.is_eos());
+    let damaged = reader.take_damaged_block().unwrap();
+    assert_eq!(damaged.block_index, 5);
+    assert!(reader.take_damaged_block().is_none());
+}
+
+#[test]
+pub fn test_recovery_reader_multiple_blocks() {
+    let info = XzFileInfo::from_slice(NO_SIZES).unwrap();
+    let mut data = NO_SIZES.to_vec();
+    for index in [2, 3, 7] {
+        data[info.streams[0].blocks[index].compressed_offset as usize + 100] ^= 0x55;
+    }
+
+    // All damaged blocks are kept, even if they are skipped within a single read.
+    let mut reader = XzReader::new(Cursor::new(data.clone()));
+    reader.set_recovery(true);
+    reader.read_to_end(&mut Vec::new()).unwrap();
+    let mut damaged = Vec::new();
+    while let Some(block) = reader.take_damaged_block() {
+        damaged.push(block.block_index);
+    }
+    assert_eq!(damaged, [2, 3, 7]);
+
+    let mut reader = XzReader::new(Cursor::new(data));
+    reader.set_recovery(true);
+    reader.verify().unwrap();
+    assert_eq!(reader.stats().damaged_blocks, 3);
+    assert_eq!(reader.take_damaged_block().unwrap().block_index, 2);
+}
@@ -0,0 +1,178 @@
+use std::io::{Cursor, ErrorKind, Read, Seek, SeekFrom};
+use xz4rust::{XzError, XzSeekableReader};
+
+const MULTI: &[u8] = include_bytes!("../test_files/multi_block_streams.xz");
+
+fn expected() -> Vec<u8> {
+    let mut expected = Vec::new();
+    expected.extend_from_slice(include_bytes!("../test_files/java_native_utils_amd64.so"));
+    expected.extend_from_slice(include_bytes!("../test_files/java_native_utils_riscv64.so"));
+    expected
+}
+
+#[test]
+pub fn test_seekable_read_all() {
+    let mut reader = XzSeekableReader::new(Cursor::new(MULTI)).unwrap();
+    assert_eq!(reader.stream_count(), 3);
+    assert_eq!(reader.block_count(), 75);
+    assert_eq!(reader.uncompressed_size(), expected().len() as u64);
+
+    let mut out = Vec::new();
+    reader.read_to_end(&mut out).unwrap();
+    assert_eq!(out, expected());
+    assert_eq!(reader.read(&mut [0; 16]).unwrap(), 0);
+}
+
+#[test]
+pub fn test_seekable_random_access() {
+    let expected = expected();
+    let mut reader = XzSeekableReader::new(Cursor::new(MULTI)).unwrap();
+    let mut seed = 0x1234_5678_u64;
+    for _ in 0..200 {
+        seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+        let offset = (seed >> 33) as usize % expected.len();
+        let len = ((seed >> 13) as usize % 40000).min(expected.len() - offset);
+        assert_eq!(
+            reader.seek(SeekFrom::Start(offset as u64)).unwrap(),
+            offset as u64
+        );
+        let mut out = vec![0u8; len];
+        reader.read_exact(&mut out).unwrap();
+        assert_eq!(out.as_slice(), &expected[offset..offset + len]);
+    }
+
+    // Across the boundary of the first stream.
+    let boundary = include_bytes!("../test_files/java_native_utils_amd64.so").len();
+    reader.seek(SeekFrom::Start(boundary as u64 - 100)).unwrap();
+    let mut out = vec![0u8; 200];
+    reader.read_exact(&mut out).unwrap();
+    assert_eq!(out.as_slice(), &expected[boundary - 100..boundary + 100]);
+
+    reader.seek(SeekFrom::Current(-300)).unwrap();
+    reader.read_exact(&mut out).unwrap();
+    assert_eq!(out.as_slice(), &expected[boundary - 200..boundary]);
+
+    assert_eq!(
+        reader.seek(SeekFrom::End(-10)).unwrap(),
+        expected.len() as u64 - 10
+    );
+    let mut out = Vec::new();
+    reader.read_to_end(&mut out).unwrap();
+    assert_eq!(out.as_slice(), &expected[expected.len() - 10..]);
+
+    // Past the end is eof.
+    reader.seek(SeekFrom::End(10)).unwrap();
+    assert_eq!(reader.read(&mut [0; 16]).unwrap(), 0);
+    assert_eq!(
+        reader
+            .seek(SeekFrom::Current(-(expected.len() as i64) - 11))
+            .unwrap_err()
+            .kind(),
+        ErrorKind::InvalidInput
+    );
+}
+
+#[test]
+pub fn test_seekable_single_block() {
+    let expected = include_bytes!("../test_files/java_native_utils_riscv64.so");
+    let mut reader = XzSeekableReader::new(Cursor::new(include_bytes!(
+        "../test_files/java_native_utils_riscv64.so.xz"
+    )))
+    .unwrap();
+    assert_eq!(reader.block_count(), 1);
+    for offset in [500_000, 100, 673_000, 0] {
+        reader.seek(SeekFrom::Start(offset as u64)).unwrap();
+        let mut out = [0u8; 64];
+        reader.read_exact(&mut out).unwrap();
+        assert_eq!(out.as_slice(), &expected[offset..offset + 64]);
+    }
+}
+
+#[test]
+pub fn test_seekable_empty_streams() {
+    for input in [
+        include_bytes!("../test_files/good-0-empty.xz").as_slice(),
+        include_bytes!("../test_files/good-0cat-empty.xz").as_slice(),
+        include_bytes!("../test_files/good-0catpad-empty.xz").as_slice(),
+        include_bytes!("../test_files/good-0pad-empty.xz").as_slice(),
+    ] {
+        let mut reader = XzSeekableReader::new(Cursor::new(input)).unwrap();
+        assert_eq!(reader.uncompressed_size(), 0);
+        assert_eq!(reader.read(&mut [0; 16]).unwrap(), 0);
+    }
+}
+
+#[test]
+pub fn test_seekable_invalid_index() {
+    let invalid = |input: Vec<u8>| {
+        let err = XzSeekableReader::new(Cursor::new(input)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        err.into_inner().unwrap().downcast::<XzError>().unwrap()
+    };
+
+    // Crc32 of the index of the last stream.
+    let mut input = MULTI.to_vec();
+    let crc = input.len() - 4 - 12 - 1;
+    input[crc] ^= 1;
+    assert!(matches!(*invalid(input), XzError::IndexCrc32Mismatch(_, _)));
+
+    // Stream padding that is not a multiple of 4.
+    let mut input = MULTI.to_vec();
+    input.push(0);
+    assert_eq!(*invalid(input), XzError::FooterMagicNumberMismatch);
+
+    // Stream header of the first stream.
+    let mut input = MULTI.to_vec();
+    input[0] = 0;
+    assert_eq!(*invalid(input), XzError::StreamHeaderMagicNumberMismatch);
+
+    for input in [
+        include_bytes!("../test_files/bad-0-backward_size.xz").as_slice(),
+        include_bytes!("../test_files/bad-0-footer_magic.xz").as_slice(),
+        include_bytes!("../test_files/bad-0-header_magic.xz").as_slice(),
+        include_bytes!("../test_files/bad-0-nonempty_index.xz").as_slice(),
+        include_bytes!("../test_files/bad-0pad-empty.xz").as_slice(),
+    ] {
+        invalid(input.to_vec());
+    }
+
+    // Indices that are only found to be wrong once the blocks are decoded.
+    for input in [
+        include_bytes!("../test_files/bad-2-index-1.xz").as_slice(),
+        include_bytes!("../test_files/bad-2-index-2.xz").as_slice(),
+        include_bytes!("../test_files/bad-2-index-3.xz").as_slice(),
+        include_bytes!("../test_files/bad-2-index-4.xz").as_slice(),
+        include_bytes!("../test_files/bad-2-index-5.xz").as_slice(),
+        include_bytes!("../test_files/bad-3-index-uncomp-overflow.xz").as_slice(),
+    ] {
+        let err = match XzSeekableReader::new(Cursor::new(input)) {
+            Ok(mut reader) => reader.read_to_end(&mut Vec::new()).unwrap_err(),
+            Err(err) => err,
+        };
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+}
+
+#[test]
+pub fn test_seekable_corrupted_block() {
+    let mut input = MULTI.to_vec();
+    input[5000] ^= 0x55;
+    let mut reader = XzSeekableReader::new(Cursor::new(input)).unwrap();
+
+    // Blocks before and after the corrupted block are fine.
+    let expected = expected();
+    let mut out = vec![0u8; 1000];
+    reader.read_exact(&mut out).unwrap();
+    assert_eq!(out.as_slice(), &expected[..1000]);
+    reader.seek(SeekFrom::Start(40000)).unwrap();
+    reader.read_exact(&mut out).unwrap();
+    assert_eq!(out.as_slice(), &expected[40000..41000]);
+
+    // The check of the block is only verified at its end.
+    reader.seek(SeekFrom::Start(17000)).unwrap();
+    let mut out = vec![0u8; 16000];
+    assert_eq!(
+        reader.read_exact(&mut out).unwrap_err().kind(),
+        ErrorKind::InvalidData
+    );
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use common::{COMPRESSED, EXPECTED, HELLO};
+use std::io::{Cursor, Read};
+use std::sync::Mutex;
+use xz4rust::{XzCheckType, XzDecoder, XzDecoderStats, XzReader, XzStaticDecoder};
+
+static DECODER: Mutex<XzStaticDecoder<{ xz4rust::DICT_SIZE_PROFILE_0 }>> =
+    Mutex::new(XzStaticDecoder::new());
+
+#[test]
+pub fn test_stats() {
+    let mut decoder = XzDecoder::in_heap();
+    assert_eq!(decoder.stats(), XzDecoderStats::default());
+
+    let mut output = vec![0u8; 4096];
+    let mut consumed = 0;
+    let mut produced = 0;
+    while consumed < COMPRESSED.len() {
+        let end = COMPRESSED.len().min(consumed + 1000);
+        let result = decoder
+            .decode(&COMPRESSED[consumed..end], &mut output)
+            .unwrap();
+        consumed += result.input_consumed();
+        produced += result.output_produced();
+
+        let stats = decoder.stats();
+        assert_eq!(stats.compressed_consumed, consumed as u64);
+        assert_eq!(stats.uncompressed_produced, produced as u64);
+        assert_eq!(stats.stream_index, 0);
+        assert_eq!(stats.check_type, XzCheckType::Crc64);
+        assert_eq!(stats.dict_size, 8 << 20);
+        if result.is_end_of_stream() {
+            assert_eq!(stats.block_index, 1);
+            break;
+        }
+
+        if produced < EXPECTED.len() {
+            assert_eq!(stats.block_index, 0);
+            assert_eq!(stats.block_uncompressed_position, produced as u64);
+            assert!(stats.block_compressed_position < consumed as u64);
+        }
+    }
+    assert_eq!(consumed, COMPRESSED.len());
+    assert_eq!(produced, EXPECTED.len());
+
+    decoder.reset();
+    assert_eq!(decoder.stats(), XzDecoderStats::default());
+}
+
+#[test]
+pub fn test_stats_multi_stream() {
+    let mut data = COMPRESSED.to_vec();
+    data.extend_from_slice(&[0; 4]);
+    data.extend_from_slice(HELLO);
+
+    let mut reader = XzReader::new(Cursor::new(data.clone()));
+    reader.set_multi_stream(true);
+    let mut result = Vec::new();
+    reader.read_to_end(&mut result).unwrap();
+
+    let stats = reader.stats();
+    assert_eq!(stats.compressed_consumed, data.len() as u64);
+    assert_eq!(stats.uncompressed_produced, result.len() as u64);
+    assert_eq!(stats.stream_index, 1);
+    assert_eq!(stats.block_index, 1);
+    assert_eq!(stats.check_type, XzCheckType::Crc32);
+    assert_eq!(stats.dict_size, 64 << 10);
+    assert_eq!(stats.block_uncompressed_position, 13);
+
+    reader.reset();
+    assert_eq!(reader.stats(), XzDecoderStats::default());
+}
+
+#[test]
+pub fn test_stats_static() {
+    let mut decoder = DECODER.lock().unwrap();
+    decoder.reset();
+    let mut output = [0u8; 64];
+    let result = decoder.decode(HELLO, &mut output).unwrap();
+    assert!(result.is_end_of_stream());
+
+    let stats = decoder.stats();
+    assert_eq!(stats.compressed_consumed, HELLO.len() as u64);
+    assert_eq!(stats.uncompressed_produced, 13);
+    assert_eq!(stats.block_index, 1);
+    assert_eq!(stats.check_type, XzCheckType::Crc32);
+    assert_eq!(stats.dict_size, 64 << 10);
+
+    decoder.reset();
+    assert_eq!(decoder.stats(), XzDecoderStats::default());
+}
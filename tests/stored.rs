@@ -0,0 +1,171 @@
+use std::io::{Cursor, Read, Write};
+use std::process::{Command, Stdio};
+use xz4rust::{XzCheckType, XzError, XzFileInfo, XzNextBlockResult, XzReader, XzStoredEncoder};
+
+const RISCV64: &[u8] = include_bytes!("../test_files/java_native_utils_riscv64.so");
+
+/// Encodes data by feeding input_step bytes at once into an output buffer of output_size bytes.
+fn store(data: &[u8], check: XzCheckType, input_step: usize, output_size: usize) -> Vec<u8> {
+    let mut encoder = XzStoredEncoder::new(check);
+    let mut output = vec![0u8; output_size];
+    let mut result = Vec::new();
+    let mut position = 0;
+    while position < data.len() {
+        let end = data.len().min(position + input_step);
+        let XzNextBlockResult::NeedMoreData(consumed, produced) =
+            encoder.encode(&data[position..end], &mut output).unwrap()
+        else {
+            panic!("encode never ends the stream");
+        };
+        position += consumed;
+        result.extend_from_slice(&output[..produced]);
+    }
+
+    loop {
+        match encoder.finish(&mut output).unwrap() {
+            XzNextBlockResult::NeedMoreData(_, produced) => {
+                result.extend_from_slice(&output[..produced]);
+            }
+            XzNextBlockResult::EndOfStream(_, produced) => {
+                result.extend_from_slice(&output[..produced]);
+                return result;
+            }
+        }
+    }
+}
+
+fn decompress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    XzReader::new(Cursor::new(data.to_vec()))
+        .read_to_end(&mut out)
+        .unwrap();
+    out
+}
+
+#[test]
+pub fn test_stored_check_types() {
+    for check in [
+        XzCheckType::None,
+        XzCheckType::Crc32,
+        XzCheckType::Crc64,
+        XzCheckType::Sha256,
+    ] {
+        let compressed = store(RISCV64, check, RISCV64.len(), 1 << 20);
+        let info = XzFileInfo::from_slice(&compressed).unwrap();
+        assert_eq!(info.streams.len(), 1);
+        assert_eq!(info.streams[0].check_type, check);
+        assert_eq!(info.block_count(), 1);
+        assert_eq!(info.uncompressed_size(), RISCV64.len() as u64);
+        assert_eq!(info.compressed_size(), compressed.len() as u64);
+        assert_eq!(decompress(&compressed).as_slice(), RISCV64);
+    }
+}
+
+#[test]
+pub fn test_stored_small_buffers() {
+    let expected = store(RISCV64, XzCheckType::Crc64, RISCV64.len(), 1 << 20);
+    for (input_step, output_size) in [(1, 1), (7, 3), (100_000, 1), (1, 100_000), (65536, 65539)] {
+        let compressed = store(
+            &RISCV64[..200_000],
+            XzCheckType::Crc64,
+            input_step,
+            output_size,
+        );
+        assert_eq!(decompress(&compressed).as_slice(), &RISCV64[..200_000]);
+    }
+
+    // The chunk sizes only depend on the input that is available, not on the output space.
+    let compressed = store(RISCV64, XzCheckType::Crc64, RISCV64.len(), 1);
+    assert_eq!(compressed, expected);
+}
+
+#[test]
+pub fn test_stored_empty() {
+    let compressed = store(&[], XzCheckType::Crc32, 1, 1);
+    assert_eq!(
+        compressed.as_slice(),
+        include_bytes!("../test_files/good-0-empty.xz")
+    );
+
+    let mut encoder = XzStoredEncoder::new(XzCheckType::Crc64);
+    let mut output = [0u8; 64];
+    let result = encoder.encode(&[], &mut output).unwrap();
+    assert!(matches!(result, XzNextBlockResult::NeedMoreData(0, 0)));
+    let result = encoder.finish(&mut output).unwrap();
+    assert!(matches!(result, XzNextBlockResult::EndOfStream(0, 32)));
+    assert!(decompress(&output[..32]).is_empty());
+}
+
+#[test]
+pub fn test_stored_reset() {
+    let mut encoder = XzStoredEncoder::new(XzCheckType::Sha256);
+    assert_eq!(encoder.check_type(), XzCheckType::Sha256);
+    let mut output = vec![0u8; 1024];
+    for data in [b"Hello\nWorld!".as_slice(), b"Hello again".as_slice()] {
+        let result = encoder.encode(data, &mut output).unwrap();
+        assert_eq!(result.input_consumed(), data.len());
+        let mut size = result.output_produced();
+        let result = encoder.finish(&mut output[size..]).unwrap();
+        assert!(matches!(result, XzNextBlockResult::EndOfStream(_, _)));
+        size += result.output_produced();
+        assert_eq!(decompress(&output[..size]).as_slice(), data);
+
+        assert_eq!(
+            encoder.encode(data, &mut output).unwrap_err(),
+            XzError::NeedsReset
+        );
+        assert_eq!(
+            encoder.finish(&mut output).unwrap_err(),
+            XzError::NeedsReset
+        );
+        encoder.reset();
+    }
+}
+
+#[test]
+pub fn test_stored_missing_input() {
+    let mut encoder = XzStoredEncoder::new(XzCheckType::Crc32);
+    let mut output = [0u8; 100];
+    let result = encoder.encode(&[1u8; 1000], &mut output).unwrap();
+    assert!(result.input_consumed() < 1000);
+    assert_eq!(
+        encoder.finish(&mut output).unwrap_err(),
+        XzError::UnexpectedEndOfInput
+    );
+}
+
+#[test]
+pub fn test_stored_multi_stream() {
+    let mut compressed = store(b"Hello\n", XzCheckType::Crc32, 100, 100);
+    compressed.extend_from_slice(&store(b"World!", XzCheckType::None, 100, 100));
+    let mut reader = XzReader::new(Cursor::new(compressed));
+    reader.set_multi_stream(true);
+    let mut out = Vec::new();
+    reader.read_to_end(&mut out).unwrap();
+    assert_eq!(out.as_slice(), b"Hello\nWorld!");
+}
+
+#[test]
+pub fn test_stored_xz_compat() {
+    let compressed = store(RISCV64, XzCheckType::Sha256, 10_000, 4096);
+    let child = Command::new("/usr/bin/env")
+        .args(["xz", "-dc"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn();
+    let Ok(mut child) = child else {
+        println!("xz is not installed, skipping test");
+        return;
+    };
+
+    let mut stdin = child.stdin.take().unwrap();
+    let feeder = std::thread::spawn(move || stdin.write_all(&compressed).unwrap());
+    let output = child.wait_with_output().unwrap();
+    feeder.join().unwrap();
+    if output.status.code() == Some(127) {
+        println!("xz is not installed, skipping test");
+        return;
+    }
+    assert!(output.status.success());
+    assert_eq!(output.stdout.as_slice(), RISCV64);
+}
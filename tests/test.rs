@@ -187,11 +187,10 @@ fn t7() {
 
 #[test]
 fn t8() {
-    run_test_expect_error(
+    run_test2(
         include_bytes!("../test_files/good-1-arm64-lzma2-2.xz"),
-        |err| assert_eq!(err, XzError::BcjFilterWithOffsetNotSupported),
+        include_bytes!("../test_files/good-1-arm64-lzma2-2"),
     );
-    //Custom start offset not supported
 }
 
 #[test]
@@ -695,3 +694,54 @@ fn t56() {
         |e| assert_eq!(e, XzError::LzmaPropertiesTooLarge),
     );
 }
+
+#[test]
+fn t57() {
+    let amd64 = &include_bytes!("../test_files/java_native_utils_amd64.so")[..65536];
+    let armel = &include_bytes!("../test_files/java_native_utils_armel.so")[..65536];
+    let riscv64 = &include_bytes!("../test_files/java_native_utils_riscv64.so")[..65536];
+    for (data, expected) in [
+        (
+            include_bytes!("../test_files/bcj-offset-x86.xz").as_slice(),
+            amd64,
+        ),
+        (
+            include_bytes!("../test_files/bcj-offset-powerpc.xz").as_slice(),
+            amd64,
+        ),
+        (
+            include_bytes!("../test_files/bcj-offset-ia64.xz").as_slice(),
+            amd64,
+        ),
+        (
+            include_bytes!("../test_files/bcj-offset-arm.xz").as_slice(),
+            armel,
+        ),
+        (
+            include_bytes!("../test_files/bcj-offset-armthumb.xz").as_slice(),
+            armel,
+        ),
+        (
+            include_bytes!("../test_files/bcj-offset-arm64.xz").as_slice(),
+            amd64,
+        ),
+        (
+            include_bytes!("../test_files/bcj-offset-sparc.xz").as_slice(),
+            amd64,
+        ),
+        (
+            include_bytes!("../test_files/bcj-offset-riscv.xz").as_slice(),
+            riscv64,
+        ),
+    ] {
+        run_test2(data, expected);
+    }
+}
+
+#[test]
+fn t58() {
+    run_test_expect_error(
+        include_bytes!("../test_files/bad-bcj-offset-unaligned.xz"),
+        |e| assert_eq!(e, XzError::BcjFilterOffsetUnaligned(0x8006)),
+    );
+}
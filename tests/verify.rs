@@ -0,0 +1,171 @@
+mod common;
+
+use common::{COMPRESSED, EXPECTED, HELLO};
+use std::io::{BufRead, Cursor, ErrorKind};
+use std::sync::Mutex;
+use xz4rust::{XzCheckType, XzDecoder, XzError, XzNextBlockResult, XzReader, XzStaticDecoder};
+
+static DECODER: Mutex<XzStaticDecoder<{ xz4rust::DICT_SIZE_PROFILE_0 }>> =
+    Mutex::new(XzStaticDecoder::new());
+
+#[test]
+pub fn test_verify() {
+    let mut decoder = XzDecoder::in_heap();
+    let result = decoder.verify(COMPRESSED).unwrap();
+    assert!(matches!(result, XzNextBlockResult::EndOfStream(_, _)));
+    assert_eq!(result.input_consumed(), COMPRESSED.len());
+    assert_eq!(result.output_produced(), EXPECTED.len());
+
+    // Small pieces of input.
+    decoder.reset();
+    let mut consumed = 0;
+    let mut produced = 0;
+    loop {
+        let end = COMPRESSED.len().min(consumed + 1000);
+        let result = decoder.verify(&COMPRESSED[consumed..end]).unwrap();
+        consumed += result.input_consumed();
+        produced += result.output_produced();
+        if result.is_end_of_stream() {
+            break;
+        }
+        assert_eq!(consumed, end);
+    }
+    assert_eq!(consumed, COMPRESSED.len());
+    assert_eq!(produced, EXPECTED.len());
+    assert_eq!(decoder.stats().uncompressed_produced, EXPECTED.len() as u64);
+}
+
+#[test]
+pub fn test_verify_filters_and_uncompressed_chunks() {
+    let files: [(&[u8], &[u8]); 5] = [
+        (
+            include_bytes!("../test_files/good-1-3delta-lzma2.xz"),
+            include_bytes!("../test_files/good-1-3delta-lzma2"),
+        ),
+        (
+            include_bytes!("../test_files/good-1-delta-lzma2.tiff.xz"),
+            include_bytes!("../test_files/good-1-delta-lzma2.tiff"),
+        ),
+        (
+            include_bytes!("../test_files/good-1-arm64-lzma2-2.xz"),
+            include_bytes!("../test_files/good-1-arm64-lzma2-2"),
+        ),
+        (
+            include_bytes!("../test_files/good-1-lzma2-3.xz"),
+            include_bytes!("../test_files/good-1-lzma2-3"),
+        ),
+        (
+            include_bytes!("../test_files/good-1-check-sha256.xz"),
+            include_bytes!("../test_files/good-1-check-sha256"),
+        ),
+    ];
+
+    let mut decoder = XzDecoder::in_heap();
+    for (compressed, expected) in files {
+        for chunk in [7, compressed.len()] {
+            decoder.reset();
+            let mut consumed = 0;
+            let mut produced = 0;
+            loop {
+                let end = compressed.len().min(consumed + chunk);
+                let result = decoder.verify(&compressed[consumed..end]).unwrap();
+                consumed += result.input_consumed();
+                produced += result.output_produced();
+                if result.is_end_of_stream() {
+                    break;
+                }
+                assert_eq!(consumed, end);
+            }
+            assert_eq!(consumed, compressed.len());
+            assert_eq!(produced, expected.len());
+        }
+    }
+}
+
+#[test]
+pub fn test_verify_errors() {
+    let mut decoder = XzDecoder::in_heap();
+    assert!(matches!(
+        decoder
+            .verify(include_bytes!("../test_files/bad-1-check-sha256.xz"))
+            .unwrap_err(),
+        XzError::ContentSha256Mismatch(_, _)
+    ));
+
+    decoder.reset();
+    assert_eq!(
+        decoder
+            .verify(include_bytes!("../test_files/bad-2-index-4.xz"))
+            .unwrap_err(),
+        XzError::IndexCrc32Mismatch(1575476230, 1558699014)
+    );
+}
+
+#[test]
+pub fn test_verify_stops_at_block_end() {
+    let data = include_bytes!("../test_files/multi_block_streams.xz");
+    let mut decoder = XzDecoder::in_heap();
+    decoder.set_stop_at_block_end(true);
+    let mut consumed = 0;
+    let mut blocks = 0;
+    loop {
+        let result = decoder.verify(&data[consumed..]).unwrap();
+        consumed += result.input_consumed();
+        if decoder.take_block_end().is_some() {
+            blocks += 1;
+        }
+        if result.is_end_of_stream() {
+            break;
+        }
+    }
+    assert_eq!(blocks, 52);
+}
+
+#[test]
+pub fn test_verify_static() {
+    let mut decoder = DECODER.lock().unwrap();
+    decoder.reset();
+    let result = decoder.verify(HELLO).unwrap();
+    assert!(result.is_end_of_stream());
+    assert_eq!(result.input_consumed(), HELLO.len());
+    assert_eq!(result.output_produced(), 13);
+}
+
+#[test]
+pub fn test_verify_reader() {
+    let stats = XzReader::new(COMPRESSED).verify().unwrap();
+    assert_eq!(stats.compressed_consumed, COMPRESSED.len() as u64);
+    assert_eq!(stats.uncompressed_produced, EXPECTED.len() as u64);
+    assert_eq!(stats.check_type, XzCheckType::Crc64);
+
+    let mut data = COMPRESSED.to_vec();
+    data.extend_from_slice(&[0; 4]);
+    data.extend_from_slice(HELLO);
+    let mut reader = XzReader::new(Cursor::new(data.clone()));
+    reader.set_multi_stream(true);
+    // Data that is buffered for BufRead is skipped.
+    assert_eq!(reader.fill_buf().unwrap()[0], EXPECTED[0]);
+    let stats = reader.verify().unwrap();
+    assert_eq!(stats.compressed_consumed, data.len() as u64);
+    assert_eq!(stats.uncompressed_produced, EXPECTED.len() as u64 + 13);
+    assert_eq!(stats.stream_index, 1);
+    assert!(reader.is_eos());
+    assert!(reader.fill_buf().unwrap().is_empty());
+}
+
+#[test]
+pub fn test_verify_reader_errors() {
+    let err = XzReader::new(&COMPRESSED[..COMPRESSED.len() - 1])
+        .verify()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+
+    let err = XzReader::new(include_bytes!("../test_files/bad-1-check-crc64.xz").as_slice())
+        .verify()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert!(matches!(
+        err.downcast::<XzError>().unwrap(),
+        XzError::ContentCrc64Mismatch(_, _)
+    ));
+}
@@ -0,0 +1,229 @@
+use std::io::{Cursor, Read, Write};
+use std::process::{Command, Stdio};
+use xz4rust::{
+    Lzma2Decoder, Lzma2Encoder, Lzma2EncoderOptions, XzCheckType, XzError, XzFileInfo,
+    XzNextBlockResult, XzReader, XzWriter,
+};
+
+const AMD64: &[u8] = include_bytes!("../test_files/java_native_utils_amd64.so");
+const RISCV64: &[u8] = include_bytes!("../test_files/java_native_utils_riscv64.so");
+
+/// Data that does not compress at all.
+fn noise(size: usize) -> Vec<u8> {
+    let mut state = 0x2545_F491_4F6C_DD1Du64;
+    (0..size)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 32) as u8
+        })
+        .collect()
+}
+
+fn compress(data: &[u8], options: &Lzma2EncoderOptions, check: XzCheckType) -> Vec<u8> {
+    let mut writer = XzWriter::new_with_options(Vec::new(), options, check).unwrap();
+    writer.write_all(data).unwrap();
+    writer.finish().unwrap()
+}
+
+fn decompress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    XzReader::new(Cursor::new(data.to_vec()))
+        .read_to_end(&mut out)
+        .unwrap();
+    out
+}
+
+#[test]
+pub fn test_writer_presets() {
+    for level in [0, 1, 3, 6, 9] {
+        let mut writer = XzWriter::new_with_preset(Vec::new(), level);
+        writer.write_all(AMD64).unwrap();
+        let compressed = writer.finish().unwrap();
+        assert!(compressed.len() < AMD64.len() / 5, "{}", compressed.len());
+        assert_eq!(decompress(&compressed).as_slice(), AMD64);
+    }
+}
+
+#[test]
+pub fn test_writer_check_types() {
+    let options = Lzma2EncoderOptions::preset(1);
+    for check in [
+        XzCheckType::None,
+        XzCheckType::Crc32,
+        XzCheckType::Crc64,
+        XzCheckType::Sha256,
+    ] {
+        let compressed = compress(RISCV64, &options, check);
+        let info = XzFileInfo::from_slice(&compressed).unwrap();
+        assert_eq!(info.streams.len(), 1);
+        assert_eq!(info.streams[0].check_type, check);
+        assert_eq!(info.block_count(), 1);
+        assert_eq!(info.uncompressed_size(), RISCV64.len() as u64);
+        assert_eq!(decompress(&compressed).as_slice(), RISCV64);
+    }
+}
+
+#[test]
+pub fn test_writer_empty() {
+    let writer = XzWriter::new_with_options(
+        Vec::new(),
+        &Lzma2EncoderOptions::default(),
+        XzCheckType::Crc32,
+    )
+    .unwrap();
+    let compressed = writer.finish().unwrap();
+    assert_eq!(
+        compressed.as_slice(),
+        include_bytes!("../test_files/good-0-empty.xz")
+    );
+    assert!(decompress(&compressed).is_empty());
+
+    let mut compressed = Vec::new();
+    drop(XzWriter::new(&mut compressed));
+    assert!(decompress(&compressed).is_empty());
+}
+
+#[test]
+pub fn test_writer_small_writes() {
+    let mut expected = Vec::new();
+    let mut writer = XzWriter::new_with_preset(Vec::new(), 0);
+    for i in 0..20_000u32 {
+        let bytes = i.to_string();
+        writer.write_all(bytes.as_bytes()).unwrap();
+        expected.extend_from_slice(bytes.as_bytes());
+        if i % 1000 == 0 {
+            writer.flush().unwrap();
+        }
+    }
+    writer.write_all(&[]).unwrap();
+    let compressed = writer.finish().unwrap();
+    assert_eq!(decompress(&compressed), expected);
+
+    let compressed = compress(b"a", &Lzma2EncoderOptions::default(), XzCheckType::Crc32);
+    assert_eq!(decompress(&compressed).as_slice(), b"a");
+}
+
+#[test]
+pub fn test_writer_incompressible() {
+    // Noise is stored in uncompressed chunks, the lzma chunks after it must reset the state.
+    let mut data = noise(300_000);
+    data.extend_from_slice(AMD64);
+    data.extend_from_slice(&noise(70_000));
+    data.extend_from_slice(&vec![0u8; 5_000_000]);
+    data.extend_from_slice(RISCV64);
+    let compressed = compress(&data, &Lzma2EncoderOptions::preset(0), XzCheckType::Crc64);
+    assert!(compressed.len() < 300_000 + 70_000 + 300_000);
+    assert_eq!(decompress(&compressed), data);
+
+    let compressed = compress(
+        &noise(100_000),
+        &Lzma2EncoderOptions::preset(0),
+        XzCheckType::Crc64,
+    );
+    assert!(compressed.len() < 100_000 + 100);
+}
+
+#[test]
+pub fn test_writer_dictionary_window() {
+    // The window slides multiple times, repetitions further back than the dictionary must not be used.
+    let options = Lzma2EncoderOptions {
+        dict_size: 4096,
+        ..Lzma2EncoderOptions::preset(3)
+    };
+    let mut data = Vec::new();
+    for i in 0..40 {
+        data.extend_from_slice(&AMD64[i * 10_000..i * 10_000 + 200_000]);
+    }
+    let compressed = compress(&data, &options, XzCheckType::Crc32);
+    assert_eq!(decompress(&compressed), data);
+
+    let mut dict = vec![0u8; 4096];
+    let mut decoder = xz4rust::XzDecoder::with_fixed_size_dict(&mut dict);
+    let mut out = vec![0u8; data.len()];
+    let result = decoder.decode(&compressed, &mut out).unwrap();
+    assert!(matches!(result, XzNextBlockResult::EndOfStream(_, _)));
+    assert_eq!(out, data);
+}
+
+#[test]
+pub fn test_lzma2_encoder_raw() {
+    let options = Lzma2EncoderOptions {
+        lc: 0,
+        lp: 2,
+        pb: 0,
+        ..Lzma2EncoderOptions::preset(2)
+    };
+    let mut encoder = Lzma2Encoder::new(&options).unwrap();
+    assert_eq!(encoder.dict_size_props(), 18);
+    for _ in 0..2 {
+        let mut compressed = Vec::new();
+        for chunk in RISCV64.chunks(10_000) {
+            encoder.encode(chunk, &mut compressed);
+        }
+        encoder.finish(&mut compressed);
+        assert_eq!(compressed.last(), Some(&0));
+
+        let mut decoder = Lzma2Decoder::with_alloc_dict_size(options.dict_size, options.dict_size);
+        let mut out = vec![0u8; RISCV64.len() + 1];
+        let result = decoder.decode(&compressed, &mut out).unwrap();
+        assert!(matches!(result, XzNextBlockResult::EndOfStream(_, _)));
+        assert_eq!(result.input_consumed(), compressed.len());
+        assert_eq!(&out[..result.output_produced()], RISCV64);
+    }
+
+    let mut compressed = Vec::new();
+    encoder.finish(&mut compressed);
+    assert_eq!(compressed, [0]);
+}
+
+#[test]
+pub fn test_lzma2_encoder_invalid_options() {
+    for (lc, lp, pb) in [(5, 0, 0), (0, 5, 0), (0, 0, 5), (3, 2, 0)] {
+        let options = Lzma2EncoderOptions {
+            lc,
+            lp,
+            pb,
+            ..Lzma2EncoderOptions::default()
+        };
+        assert_eq!(
+            Lzma2Encoder::new(&options).unwrap_err(),
+            XzError::LzmaPropertiesInvalid
+        );
+    }
+
+    let options = Lzma2EncoderOptions {
+        dict_size: 1 << 31,
+        ..Lzma2EncoderOptions::default()
+    };
+    assert_eq!(
+        XzWriter::new_with_options(Vec::new(), &options, XzCheckType::Crc32).unwrap_err(),
+        XzError::DictionaryTooLarge(1 << 31)
+    );
+}
+
+#[test]
+pub fn test_writer_xz_compat() {
+    let compressed = compress(AMD64, &Lzma2EncoderOptions::preset(6), XzCheckType::Sha256);
+    let child = Command::new("/usr/bin/env")
+        .args(["xz", "-dc"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn();
+    let Ok(mut child) = child else {
+        println!("xz is not installed, skipping test");
+        return;
+    };
+
+    let mut stdin = child.stdin.take().unwrap();
+    let feeder = std::thread::spawn(move || stdin.write_all(&compressed).unwrap());
+    let output = child.wait_with_output().unwrap();
+    feeder.join().unwrap();
+    if output.status.code() == Some(127) {
+        println!("xz is not installed, skipping test");
+        return;
+    }
+    assert!(output.status.success());
+    assert_eq!(output.stdout.as_slice(), AMD64);
+}
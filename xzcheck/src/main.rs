@@ -1,17 +1,79 @@
 use std::fs::File;
-use std::io::Read;
 use std::{env, io};
-use xz4rust::XzReader;
+use xz4rust::{XzFileInfo, XzReader};
 
 fn do_io(path: &str) -> io::Result<()> {
-    let mut reader = XzReader::new(File::open(path)?);
-    let mut buffer = Vec::with_capacity(0x10000);
-    buffer.resize(0x10000, 0);
-    loop {
-        if reader.read(&mut buffer)? == 0 {
-            return Ok(());
+    XzReader::new(File::open(path)?).verify()?;
+    Ok(())
+}
+
+fn ratio(compressed: u64, uncompressed: u64) -> String {
+    if uncompressed == 0 {
+        return "---".to_string();
+    }
+
+    format!("{:.3}", compressed as f64 / uncompressed as f64)
+}
+
+fn do_list(path: &str) -> io::Result<()> {
+    let info = XzFileInfo::from_reader(&mut File::open(path)?)?;
+    let mut check_types = info
+        .streams
+        .iter()
+        .map(|stream| stream.check_type.to_string())
+        .collect::<Vec<String>>();
+    check_types.sort();
+    check_types.dedup();
+
+    println!("{}", path);
+    println!("  Streams:           {}", info.streams.len());
+    println!("  Blocks:            {}", info.block_count());
+    println!("  Compressed size:   {} B", info.compressed_size());
+    println!("  Uncompressed size: {} B", info.uncompressed_size());
+    println!(
+        "  Ratio:             {}",
+        ratio(info.compressed_size(), info.uncompressed_size())
+    );
+    println!("  Check:             {}", check_types.join(", "));
+    println!("  Stream Padding:    {} B", info.stream_padding());
+    println!("  Streams:");
+    println!("    Stream    Blocks      CompOffset    UncompOffset        CompSize      UncompSize  Ratio  Check     Padding");
+    for (index, stream) in info.streams.iter().enumerate() {
+        println!(
+            "    {:>6} {:>9} {:>15} {:>15} {:>15} {:>15} {:>6}  {:<8} {:>8}",
+            index + 1,
+            stream.blocks.len(),
+            stream.compressed_offset,
+            stream.uncompressed_offset,
+            stream.compressed_size,
+            stream.uncompressed_size,
+            ratio(stream.compressed_size, stream.uncompressed_size),
+            stream.check_type,
+            stream.stream_padding
+        );
+    }
+
+    println!("  Blocks:");
+    println!("    Stream     Block      CompOffset    UncompOffset       TotalSize      UncompSize  Ratio  Check");
+    let mut number = 0;
+    for (index, stream) in info.streams.iter().enumerate() {
+        for block in &stream.blocks {
+            number += 1;
+            println!(
+                "    {:>6} {:>9} {:>15} {:>15} {:>15} {:>15} {:>6}  {}",
+                index + 1,
+                number,
+                block.compressed_offset,
+                block.uncompressed_offset,
+                block.total_size(),
+                block.uncompressed_size,
+                ratio(block.total_size(), block.uncompressed_size),
+                stream.check_type
+            );
         }
     }
+
+    Ok(())
 }
 
 fn main() {
@@ -21,15 +83,21 @@ fn main() {
             "xzcheck verifies that .xz files are valid and can be decoded using the xz4rust library."
         );
         eprintln!("xzcheck exits with code 0 on success or 255 on failure. A error message will be printed to stderr.");
-        eprintln!("Usage: xzcheck <file>");
+        eprintln!("With --list the streams and blocks of the file are printed instead, similar to \"xz --list\".");
+        eprintln!("Usage: xzcheck [--list] <file>");
         std::process::exit(0);
     }
-    if args.len() != 2 {
-        eprintln!("Usage: xzcheck <file>");
-        std::process::exit(1);
-    }
 
-    if let Err(err) = do_io(&args[1]) {
+    let result = match args.as_slice() {
+        [_, path] => do_io(path),
+        [_, flag, path] if flag == "--list" || flag == "-l" => do_list(path),
+        _ => {
+            eprintln!("Usage: xzcheck [--list] <file>");
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(err) = result {
         eprintln!("Error: {}", err);
         std::process::exit(1);
     }